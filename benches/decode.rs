@@ -0,0 +1,54 @@
+//! Decoding throughput for raw order book frames, the highest-volume feed.
+//!
+//! Run with `cargo bench --bench decode`, and with `--features simd` for the simd-json backend.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::Message;
+
+use sfox::websocket::message::event::WsEvent;
+use sfox::websocket::message::OrderbookResponse;
+
+// The fixtures refer to the message types through `crate::websocket`.
+mod websocket {
+    pub use sfox::websocket::message;
+}
+
+#[allow(dead_code)]
+#[path = "../src/util/fixtures.rs"]
+mod fixtures;
+
+fn raw_orderbook_frame() -> String {
+    fixtures::NET_ORDERBOOK_PAYLOAD.replace("orderbook.net", "orderbook.sfox")
+}
+
+fn decode_orderbook(c: &mut Criterion) {
+    let frame = raw_orderbook_frame();
+    let mut group = c.benchmark_group("raw orderbook");
+    group.throughput(Throughput::Bytes(frame.len() as u64));
+
+    // What decoding cost before events: a `Value` tree to find the recipient, then the typed
+    // response built from it.
+    group.bench_function("value then typed", |b| {
+        b.iter(|| {
+            let value: Value = serde_json::from_str(black_box(&frame)).unwrap();
+            let _ = value.get("recipient").and_then(Value::as_str);
+            let response: OrderbookResponse = serde_json::from_value(value).unwrap();
+            response
+        })
+    });
+
+    group.bench_function("WsEvent::from_text", |b| {
+        b.iter(|| WsEvent::from_text(black_box(&frame)).unwrap())
+    });
+
+    // Includes the copy of the frame into a fresh message, as a socket read would allocate one.
+    group.bench_function("WsEvent::from_message", |b| {
+        b.iter(|| WsEvent::from_message(Message::Text(black_box(&frame).clone())))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decode_orderbook);
+criterion_main!(benches);
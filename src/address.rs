@@ -0,0 +1,677 @@
+use std::fmt;
+
+use bech32::primitives::decode::{SegwitHrpstring, SegwitHrpstringError};
+use sha3::{Digest, Keccak256};
+use thiserror::Error;
+
+use crate::http::v1::currency::Currency;
+
+/// The blockchain networks whose address formats can be checked.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Network {
+    Bitcoin,
+    BitcoinCash,
+    Litecoin,
+    /// Ether and ERC-20 tokens.
+    Ethereum,
+    Ripple,
+    Stellar,
+}
+
+/// Whether a network's addresses take a memo or destination tag.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TagPolicy {
+    /// The network has no tags; any tag given is an error.
+    Unsupported,
+    /// A tag may be given and must then be well formed.
+    Optional,
+    /// A tag must be given and be well formed, e.g. for an address whitelisted with one. Sending
+    /// to such an address without its tag can lose the funds.
+    Required,
+}
+
+/// Why an address was rejected.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum AddressError {
+    #[error("Address is empty")]
+    Empty,
+    #[error("Address validation is not supported for network `{0}`")]
+    UnsupportedNetwork(String),
+    #[error("Invalid {network} address: {reason}")]
+    InvalidEncoding { network: Network, reason: String },
+    #[error("Invalid {network} address checksum")]
+    InvalidChecksum { network: Network },
+    #[error("{network} addresses must be {expected} bytes long, got {length}")]
+    InvalidLength {
+        network: Network,
+        expected: usize,
+        length: usize,
+    },
+    /// The address is well formed but belongs to another network or a test network.
+    #[error("Address prefix `{prefix}` is not a {network} mainnet prefix")]
+    WrongPrefix { network: Network, prefix: String },
+    #[error("{network} addresses do not take a memo or destination tag")]
+    UnexpectedTag { network: Network },
+    #[error("This {network} address requires a memo or destination tag")]
+    MissingTag { network: Network },
+    #[error("Invalid {network} tag `{tag}`: {reason}")]
+    InvalidTag {
+        network: Network,
+        tag: String,
+        reason: String,
+    },
+}
+
+impl Network {
+    /// The network with the given name or currency symbol, e.g. `"bitcoin"` or `"eth"`. Case,
+    /// spaces, dashes and underscores are ignored.
+    pub fn from_name(name: &str) -> Option<Network> {
+        let name: String = name
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '_'))
+            .flat_map(char::to_lowercase)
+            .collect();
+
+        match name.as_str() {
+            "bitcoin" | "btc" => Some(Network::Bitcoin),
+            "bitcoincash" | "bch" => Some(Network::BitcoinCash),
+            "litecoin" | "ltc" => Some(Network::Litecoin),
+            "ethereum" | "eth" | "erc20" => Some(Network::Ethereum),
+            "ripple" | "xrp" | "xrpl" => Some(Network::Ripple),
+            "stellar" | "xlm" => Some(Network::Stellar),
+            _ => None,
+        }
+    }
+
+    /// The network a currency is withdrawn on: its `primary_network` if set, else its symbol.
+    /// Tokens with a contract address and no known network are taken to be ERC-20 tokens.
+    pub fn of_currency(currency: &Currency) -> Result<Network, AddressError> {
+        let name = currency
+            .primary_network
+            .as_deref()
+            .filter(|network| !network.is_empty())
+            .unwrap_or(&currency.symbol);
+
+        Network::from_name(name)
+            .or_else(|| Network::from_name(&currency.symbol))
+            .or_else(|| {
+                currency
+                    .contract_address
+                    .as_deref()
+                    .filter(|address| address.starts_with("0x"))
+                    .map(|_| Network::Ethereum)
+            })
+            .ok_or_else(|| AddressError::UnsupportedNetwork(name.to_string()))
+    }
+
+    pub fn tag_policy(&self) -> TagPolicy {
+        match self {
+            Network::Ripple | Network::Stellar => TagPolicy::Optional,
+            _ => TagPolicy::Unsupported,
+        }
+    }
+
+    /// Checks the address format and checksum, and the tag against the network's
+    /// [`TagPolicy`]. An empty tag counts as no tag.
+    pub fn validate(&self, address: &str, tag: Option<&str>) -> Result<(), AddressError> {
+        self.validate_with(address, tag, self.tag_policy())
+    }
+
+    /// [`Network::validate`] with a stricter tag policy, e.g. [`TagPolicy::Required`] for an
+    /// address whitelisted with a tag. Networks without tags still reject any tag.
+    pub fn validate_with(
+        &self,
+        address: &str,
+        tag: Option<&str>,
+        policy: TagPolicy,
+    ) -> Result<(), AddressError> {
+        let address = address.trim();
+        if address.is_empty() {
+            return Err(AddressError::Empty);
+        }
+
+        match self {
+            Network::Bitcoin => validate_bitcoin(*self, address, &[0x00, 0x05], "bc"),
+            Network::Litecoin => validate_bitcoin(*self, address, &[0x30, 0x32, 0x05], "ltc"),
+            Network::BitcoinCash => validate_bitcoin_cash(address),
+            Network::Ethereum => validate_ethereum(address),
+            Network::Ripple => {
+                validate_base58check(*self, address, bs58::Alphabet::RIPPLE, &[0x00])
+            }
+            Network::Stellar => validate_stellar(address),
+        }?;
+
+        let policy = match self.tag_policy() {
+            TagPolicy::Unsupported => TagPolicy::Unsupported,
+            _ => policy,
+        };
+        self.validate_tag(tag.map(str::trim).filter(|tag| !tag.is_empty()), policy)
+    }
+
+    fn validate_tag(&self, tag: Option<&str>, policy: TagPolicy) -> Result<(), AddressError> {
+        let network = *self;
+        let tag = match (policy, tag) {
+            (TagPolicy::Required, None) => return Err(AddressError::MissingTag { network }),
+            (_, None) => return Ok(()),
+            (TagPolicy::Unsupported, Some(_)) => {
+                return Err(AddressError::UnexpectedTag { network })
+            }
+            (_, Some(tag)) => tag,
+        };
+
+        let reason = match self {
+            Network::Ripple if tag.parse::<u32>().is_err() => {
+                Some("destination tags are whole numbers below 2^32")
+            }
+            Network::Stellar if tag.len() > 28 => Some("text memos are at most 28 bytes"),
+            _ => None,
+        };
+
+        match reason {
+            Some(reason) => Err(AddressError::InvalidTag {
+                network,
+                tag: tag.to_string(),
+                reason: reason.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Network::Bitcoin => "Bitcoin",
+            Network::BitcoinCash => "Bitcoin Cash",
+            Network::Litecoin => "Litecoin",
+            Network::Ethereum => "Ethereum",
+            Network::Ripple => "XRP Ledger",
+            Network::Stellar => "Stellar",
+        })
+    }
+}
+
+/// Checks an address, and its memo or destination tag if any, for the network `currency` is
+/// withdrawn on. Returns the network it was checked against.
+pub fn validate_address(
+    currency: &Currency,
+    address: &str,
+    tag: Option<&str>,
+) -> Result<Network, AddressError> {
+    let network = Network::of_currency(currency)?;
+    network.validate(address, tag)?;
+
+    Ok(network)
+}
+
+/// Base58Check with one of `versions`, or a segwit address with the `hrp` prefix.
+fn validate_bitcoin(
+    network: Network,
+    address: &str,
+    versions: &[u8],
+    hrp: &str,
+) -> Result<(), AddressError> {
+    let separator = address.rfind('1').unwrap_or(0);
+    if separator == 0 || !address[..separator].eq_ignore_ascii_case(hrp) {
+        return validate_base58check(network, address, bs58::Alphabet::BITCOIN, versions);
+    }
+
+    match SegwitHrpstring::new(address) {
+        Ok(_) => Ok(()),
+        Err(SegwitHrpstringError::Checksum(_)) => Err(AddressError::InvalidChecksum { network }),
+        Err(e) => Err(AddressError::InvalidEncoding {
+            network,
+            reason: e.to_string(),
+        }),
+    }
+}
+
+/// A version byte from `versions` followed by a 20-byte hash, with a double SHA-256 checksum.
+fn validate_base58check(
+    network: Network,
+    address: &str,
+    alphabet: &bs58::Alphabet,
+    versions: &[u8],
+) -> Result<(), AddressError> {
+    let decoded = bs58::decode(address)
+        .with_alphabet(alphabet)
+        .with_check(None)
+        .into_vec()
+        .map_err(|e| match e {
+            bs58::decode::Error::InvalidChecksum { .. } => {
+                AddressError::InvalidChecksum { network }
+            }
+            e => AddressError::InvalidEncoding {
+                network,
+                reason: e.to_string(),
+            },
+        })?;
+
+    if decoded.len() != 21 {
+        return Err(AddressError::InvalidLength {
+            network,
+            expected: 21,
+            length: decoded.len(),
+        });
+    }
+    if !versions.contains(&decoded[0]) {
+        return Err(AddressError::WrongPrefix {
+            network,
+            prefix: address.chars().take(1).collect(),
+        });
+    }
+
+    Ok(())
+}
+
+const CASHADDR_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// A legacy address or a CashAddr (`bitcoincash:q...`), with or without its prefix.
+fn validate_bitcoin_cash(address: &str) -> Result<(), AddressError> {
+    let network = Network::BitcoinCash;
+    let lowercase = address.to_ascii_lowercase();
+    let (prefix, payload) = match lowercase.split_once(':') {
+        Some((prefix, payload)) => (prefix, payload),
+        None if lowercase.starts_with(['q', 'p']) => ("bitcoincash", lowercase.as_str()),
+        None => {
+            return validate_base58check(network, address, bs58::Alphabet::BITCOIN, &[0x00, 0x05])
+        }
+    };
+
+    if prefix != "bitcoincash" {
+        return Err(AddressError::WrongPrefix {
+            network,
+            prefix: prefix.to_string(),
+        });
+    }
+    if address.chars().any(char::is_uppercase) && address.chars().any(char::is_lowercase) {
+        return Err(AddressError::InvalidEncoding {
+            network,
+            reason: "mixed case".into(),
+        });
+    }
+
+    let mut values: Vec<u8> = prefix.bytes().map(|b| b & 0x1f).collect();
+    values.push(0);
+    for c in payload.chars() {
+        let value = CASHADDR_CHARSET
+            .find(c)
+            .ok_or_else(|| AddressError::InvalidEncoding {
+                network,
+                reason: format!("invalid character `{}`", c),
+            })?;
+        values.push(value as u8);
+    }
+
+    // 34 characters of version and 160-bit hash, then an 8 character checksum.
+    if payload.len() != 42 {
+        return Err(AddressError::InvalidLength {
+            network,
+            expected: 42,
+            length: payload.len(),
+        });
+    }
+    if cashaddr_polymod(&values) != 0 {
+        return Err(AddressError::InvalidChecksum { network });
+    }
+
+    Ok(())
+}
+
+fn cashaddr_polymod(values: &[u8]) -> u64 {
+    const GENERATORS: [u64; 5] = [
+        0x98_f2bc_8e61,
+        0x79_b76d_99e2,
+        0xf3_3e5f_b3c4,
+        0xae_2eab_e2a8,
+        0x1e_4f43_e470,
+    ];
+
+    let mut checksum: u64 = 1;
+    for value in values {
+        let top = checksum >> 35;
+        checksum = ((checksum & 0x07_ffff_ffff) << 5) ^ u64::from(*value);
+        for (i, generator) in GENERATORS.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+
+    checksum ^ 1
+}
+
+/// `0x` and 40 hex digits. Mixed-case addresses must carry a valid EIP-55 checksum; all lower or
+/// all upper case addresses have none to check.
+fn validate_ethereum(address: &str) -> Result<(), AddressError> {
+    let network = Network::Ethereum;
+    let hex = address
+        .strip_prefix("0x")
+        .ok_or_else(|| AddressError::WrongPrefix {
+            network,
+            prefix: address.chars().take(2).collect(),
+        })?;
+
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AddressError::InvalidEncoding {
+            network,
+            reason: "not hexadecimal".into(),
+        });
+    }
+    if hex.len() != 40 {
+        return Err(AddressError::InvalidLength {
+            network,
+            expected: 20,
+            length: hex.len() / 2,
+        });
+    }
+
+    let has_upper = hex.chars().any(|c| c.is_ascii_uppercase());
+    let has_lower = hex.chars().any(|c| c.is_ascii_lowercase());
+    if !(has_upper && has_lower) {
+        return Ok(());
+    }
+
+    let hash = Keccak256::digest(hex.to_ascii_lowercase().as_bytes());
+    let checksum_ok = hex.chars().enumerate().all(|(i, c)| {
+        let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+        !c.is_ascii_alphabetic() || c.is_ascii_uppercase() == (nibble >= 8)
+    });
+
+    if checksum_ok {
+        Ok(())
+    } else {
+        Err(AddressError::InvalidChecksum { network })
+    }
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// A `G...` account id: base32 of a version byte, a 32-byte key and a CRC16-XModem checksum.
+fn validate_stellar(address: &str) -> Result<(), AddressError> {
+    let network = Network::Stellar;
+    if address.len() != 56 {
+        return Err(AddressError::InvalidLength {
+            network,
+            expected: 35,
+            length: address.len() * 5 / 8,
+        });
+    }
+    if !address.starts_with('G') {
+        return Err(AddressError::WrongPrefix {
+            network,
+            prefix: address.chars().take(1).collect(),
+        });
+    }
+
+    let mut bytes = Vec::with_capacity(35);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in address.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| AddressError::InvalidEncoding {
+                network,
+                reason: format!("invalid character `{}`", c as char),
+            })?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    let (payload, checksum) = bytes.split_at(33);
+    if crc16_xmodem(payload) != u16::from_le_bytes([checksum[0], checksum[1]]) {
+        return Err(AddressError::InvalidChecksum { network });
+    }
+
+    Ok(())
+}
+
+fn crc16_xmodem(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in bytes {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn currency(symbol: &str, primary_network: Option<&str>, contract: Option<&str>) -> Currency {
+        serde_json::from_value(serde_json::json!({
+            "id": 1, "symbol": symbol, "name": symbol, "is_fiat": 0, "is_lending_enabled": 0,
+            "can_deposit": 1, "can_withdraw": 1, "min_withdrawal": 0, "confirmations_needed": 1,
+            "precision": 8, "ascii_sign": "", "contract_address": contract, "custody_enabled": 1,
+            "trading_enabled": 1, "primary_network": primary_network, "code": symbol,
+            "currency": symbol
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_network_of_currency() {
+        let of =
+            |symbol, network, contract| Network::of_currency(&currency(symbol, network, contract));
+
+        assert_eq!(of("btc", None, None), Ok(Network::Bitcoin));
+        assert_eq!(of("usdc", Some("Ethereum"), None), Ok(Network::Ethereum));
+        assert_eq!(
+            of(
+                "link",
+                None,
+                Some("0x514910771af9ca656af840dff83e8264ecf986ca")
+            ),
+            Ok(Network::Ethereum)
+        );
+        assert_eq!(of("xrp", Some(""), None), Ok(Network::Ripple));
+        assert_eq!(
+            of("sol", Some("solana"), None),
+            Err(AddressError::UnsupportedNetwork("solana".into()))
+        );
+    }
+
+    #[test]
+    fn test_bitcoin() {
+        let btc = Network::Bitcoin;
+
+        assert_eq!(
+            btc.validate("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", None),
+            Ok(())
+        );
+        assert_eq!(
+            btc.validate("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", None),
+            Ok(())
+        );
+        assert_eq!(
+            btc.validate("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", None),
+            Ok(())
+        );
+        assert_eq!(
+            btc.validate("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", None),
+            Ok(())
+        );
+        assert_eq!(
+            btc.validate(
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                None
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            btc.validate("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb", None),
+            Err(AddressError::InvalidChecksum { network: btc })
+        );
+        assert_eq!(
+            btc.validate("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5", None),
+            Err(AddressError::InvalidChecksum { network: btc })
+        );
+        assert!(matches!(
+            btc.validate("1A1zP1eP5QGefi2DMPTfTL5SLmv7Div0Na", None),
+            Err(AddressError::InvalidEncoding { .. })
+        ));
+        // A Litecoin address is well formed but not a Bitcoin one.
+        assert!(matches!(
+            btc.validate("LKDyUEtTR1HXamkiEphisSiBJu6o3ZPE34", None),
+            Err(AddressError::WrongPrefix { .. })
+        ));
+        assert_eq!(
+            btc.validate("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", Some("123")),
+            Err(AddressError::UnexpectedTag { network: btc })
+        );
+    }
+
+    #[test]
+    fn test_litecoin_and_bitcoin_cash() {
+        assert_eq!(
+            Network::Litecoin.validate("LKDyUEtTR1HXamkiEphisSiBJu6o3ZPE34", None),
+            Ok(())
+        );
+
+        let bch = Network::BitcoinCash;
+        assert_eq!(
+            bch.validate(
+                "bitcoincash:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a",
+                None
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            bch.validate("qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a", None),
+            Ok(())
+        );
+        assert_eq!(
+            bch.validate("1BoatSLRHtKNngkdXEeobR76b53LETtpyT", None),
+            Ok(())
+        );
+        assert_eq!(
+            bch.validate(
+                "bitcoincash:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6c",
+                None
+            ),
+            Err(AddressError::InvalidChecksum { network: bch })
+        );
+        assert!(matches!(
+            bch.validate("bchtest:qpm2qsznhks23z7629mms6s4cwef74vcwvy22gdx6a", None),
+            Err(AddressError::WrongPrefix { .. })
+        ));
+    }
+
+    #[test]
+    fn test_ethereum() {
+        let eth = Network::Ethereum;
+
+        assert_eq!(
+            eth.validate("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed", None),
+            Ok(())
+        );
+        assert_eq!(
+            eth.validate("0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359", None),
+            Ok(())
+        );
+        assert_eq!(
+            eth.validate("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed", None),
+            Ok(())
+        );
+
+        assert_eq!(
+            eth.validate("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD", None),
+            Err(AddressError::InvalidChecksum { network: eth })
+        );
+        assert!(matches!(
+            eth.validate("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA", None),
+            Err(AddressError::InvalidLength { .. })
+        ));
+        assert!(matches!(
+            eth.validate("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed", None),
+            Err(AddressError::WrongPrefix { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tagged_networks() {
+        let xrp = Network::Ripple;
+        assert_eq!(
+            xrp.validate("rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh", None),
+            Ok(())
+        );
+        assert_eq!(
+            xrp.validate("rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh", Some("12345")),
+            Ok(())
+        );
+        assert!(matches!(
+            xrp.validate("rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh", Some("memo")),
+            Err(AddressError::InvalidTag { .. })
+        ));
+        assert_eq!(
+            xrp.validate("rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTi", None),
+            Err(AddressError::InvalidChecksum { network: xrp })
+        );
+
+        let xlm = Network::Stellar;
+        let account = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+        assert_eq!(xlm.validate(account, Some("invoice 42")), Ok(()));
+        assert_eq!(
+            xlm.validate(
+                "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGA",
+                None
+            ),
+            Err(AddressError::InvalidChecksum { network: xlm })
+        );
+        assert!(matches!(
+            xlm.validate(account, Some("a memo that is longer than 28 bytes")),
+            Err(AddressError::InvalidTag { .. })
+        ));
+    }
+
+    #[test]
+    fn test_required_tag() {
+        let xrp = Network::Ripple;
+        let address = "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh";
+        assert_eq!(
+            xrp.validate_with(address, Some("12345"), TagPolicy::Required),
+            Ok(())
+        );
+        assert_eq!(
+            xrp.validate_with(address, Some(" "), TagPolicy::Required),
+            Err(AddressError::MissingTag { network: xrp })
+        );
+
+        // Networks without tags never require one.
+        let btc = Network::Bitcoin;
+        let address = "1NLqQmwkGxxQmzS9uwtCGXxbxrcNW4FpYp";
+        assert_eq!(
+            btc.validate_with(address, None, TagPolicy::Required),
+            Ok(())
+        );
+        assert_eq!(
+            btc.validate_with(address, Some("1"), TagPolicy::Required),
+            Err(AddressError::UnexpectedTag { network: btc })
+        );
+    }
+
+    #[test]
+    fn test_validate_address() {
+        let usdc = currency("usdc", Some("ethereum"), None);
+
+        assert_eq!(
+            validate_address(&usdc, " 0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed ", None),
+            Ok(Network::Ethereum)
+        );
+        assert_eq!(validate_address(&usdc, "", None), Err(AddressError::Empty));
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use config::{Config, Environment, File, Map, Source, Value};
+use serde_derive::Deserialize;
+use thiserror::Error;
+
+use crate::credentials::{
+    CredentialProvider, FileCredentials, SecretString, StaticCredentials, DEFAULT_API_KEY_ENV_VAR,
+    LEGACY_API_KEY_ENV_VAR,
+};
+use crate::http::{CANDLESTICK_SERVER_URL, DEFAULT_SERVER_URL};
+use crate::websocket::DEFAULT_WS_SERVER_URL;
+
+/// Path of the configuration file to load when none is given to [`ConfigLoader::file`].
+pub const CONFIG_FILE_ENV_VAR: &str = "SFOX_CONFIG";
+/// Name of the profile to load when none is given to [`ConfigLoader::profile`].
+pub const PROFILE_ENV_VAR: &str = "SFOX_PROFILE";
+pub const HTTP_SERVER_URL_ENV_VAR: &str = "SFOX_HTTP_SERVER_URL";
+pub const CANDLESTICK_SERVER_URL_ENV_VAR: &str = "SFOX_CANDLESTICK_SERVER_URL";
+pub const WS_SERVER_URL_ENV_VAR: &str = "SFOX_WS_SERVER_URL";
+
+/// The live sFOX API. Used when no profile is selected.
+pub const PRODUCTION_PROFILE: &str = "production";
+/// A mock server on the local machine, for development and integration tests.
+pub const LOCAL_MOCK_PROFILE: &str = "local-mock";
+/// Conventional name for a non-production sFOX environment. sFOX issues sandbox hosts per
+/// account, so this profile must be defined in the configuration file.
+pub const SANDBOX_PROFILE: &str = "sandbox";
+
+const LOCAL_MOCK_SERVER_URL: &str = "http://127.0.0.1:8080";
+const LOCAL_MOCK_WS_SERVER_URL: &str = "ws://127.0.0.1:8080/ws";
+
+const ENV_PREFIX: &str = "SFOX";
+
+/// Environment variables that were read before the names were settled, mapped to the variable
+/// that replaces them. The new name wins when both are set.
+const LEGACY_ENV_VARS: [(&str, &str); 3] = [
+    (LEGACY_API_KEY_ENV_VAR, DEFAULT_API_KEY_ENV_VAR),
+    ("SFOX_SERVER_URL", HTTP_SERVER_URL_ENV_VAR),
+    ("CANDLESTICK_SERVER_URL", CANDLESTICK_SERVER_URL_ENV_VAR),
+];
+
+#[derive(Clone, Debug, Error)]
+pub enum ConfigError {
+    #[error("could not load configuration: {0}")]
+    Load(String),
+    #[error("profile `{0}` is not built in and not defined in the configuration file")]
+    UnknownProfile(String),
+}
+
+impl From<config::ConfigError> for ConfigError {
+    fn from(e: config::ConfigError) -> Self {
+        ConfigError::Load(e.to_string())
+    }
+}
+
+/// Settings shared by the HTTP and WebSocket clients.
+///
+/// Loaded in layers, each overriding the one before it:
+///
+/// 1. the defaults of the selected built-in profile ([`PRODUCTION_PROFILE`], [`LOCAL_MOCK_PROFILE`]);
+/// 2. top-level keys of the configuration file;
+/// 3. the `[profiles.<name>]` table of the configuration file;
+/// 4. `SFOX_`-prefixed environment variables, e.g. `SFOX_API_KEY` or `SFOX_HTTP_SERVER_URL`;
+/// 5. overrides set on the [`ConfigLoader`].
+///
+/// # Example
+///
+/// With `SFOX_PROFILE=sandbox` and a `sfox.toml` such as:
+///
+/// ```toml
+/// timeout_secs = 10
+///
+/// [profiles.sandbox]
+/// http_server_url = "https://sandbox.example.com"
+/// ws_server_url = "wss://sandbox-ws.example.com/ws"
+/// api_key_file = "/run/secrets/sfox-sandbox"
+/// ```
+///
+/// ```no_run
+/// use sfox::config::SfoxConfig;
+/// use sfox::http;
+///
+/// let config = SfoxConfig::loader().file("sfox.toml").load().unwrap();
+/// let client = http::Client::from_config(&config).unwrap();
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct SfoxConfig {
+    /// Name of the profile the settings were loaded for.
+    pub profile: String,
+    /// API key. Takes precedence over `api_key_file`.
+    pub api_key: Option<SecretString>,
+    /// File holding the API key, re-read on every request so the key can be rotated in place.
+    pub api_key_file: Option<PathBuf>,
+    pub http_server_url: String,
+    pub candlestick_server_url: String,
+    pub ws_server_url: String,
+    pub timeout_secs: Option<u64>,
+    pub connect_timeout_secs: Option<u64>,
+    pub user_agent: Option<String>,
+}
+
+impl SfoxConfig {
+    /// Load the configuration selected by the environment: the file named by `SFOX_CONFIG`, if
+    /// any, and the profile named by `SFOX_PROFILE`, defaulting to production.
+    pub fn load() -> Result<SfoxConfig, ConfigError> {
+        ConfigLoader::new().load()
+    }
+
+    pub fn loader() -> ConfigLoader {
+        ConfigLoader::new()
+    }
+
+    /// The credential provider described by `api_key` or `api_key_file`, if either is set.
+    pub fn credentials(&self) -> Option<Arc<dyn CredentialProvider>> {
+        if let Some(api_key) = &self.api_key {
+            return Some(Arc::new(StaticCredentials::new(api_key.clone())));
+        }
+
+        self.api_key_file
+            .as_ref()
+            .map(|path| Arc::new(FileCredentials::new(path)) as Arc<dyn CredentialProvider>)
+    }
+}
+
+/// Selects the file, profile and overrides used to load an [`SfoxConfig`].
+#[derive(Clone, Debug, Default)]
+pub struct ConfigLoader {
+    env: Option<HashMap<String, String>>,
+    file: Option<PathBuf>,
+    overrides: Vec<(String, String)>,
+    profile: Option<String>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        ConfigLoader::default()
+    }
+
+    /// Configuration file to load. The format (TOML, YAML, JSON, ...) is taken from the
+    /// extension. Defaults to the file named by `SFOX_CONFIG`, if set.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Profile to load. Defaults to `SFOX_PROFILE`, then the file's top-level `profile` key,
+    /// then [`PRODUCTION_PROFILE`].
+    pub fn profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Override a setting, e.g. `set("http_server_url", url)`, above every other layer.
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.overrides.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn load(self) -> Result<SfoxConfig, ConfigError> {
+        let env = self.env.unwrap_or_else(|| env::vars().collect());
+
+        let file = match self
+            .file
+            .or_else(|| env.get(CONFIG_FILE_ENV_VAR).map(PathBuf::from))
+        {
+            Some(path) => Config::builder().add_source(File::from(path)).build()?,
+            None => Config::default(),
+        };
+
+        let profile = self
+            .profile
+            .or_else(|| env.get(PROFILE_ENV_VAR).cloned())
+            .or_else(|| file.get_string("profile").ok())
+            .unwrap_or_else(|| PRODUCTION_PROFILE.to_string());
+
+        let profile_table = file.get_table(&format!("profiles.{}", profile)).ok();
+        let defaults = builtin_profile(&profile);
+        if defaults.is_none() && profile_table.is_none() {
+            return Err(ConfigError::UnknownProfile(profile));
+        }
+
+        let mut builder = Config::builder();
+        for (key, value) in defaults.unwrap_or_default() {
+            builder = builder.set_default(key, value)?;
+        }
+        builder = builder
+            .add_source(file)
+            .add_source(Layer(profile_table.unwrap_or_default()))
+            .add_source(environment(legacy_env(&env)))
+            .add_source(environment(env));
+        for (key, value) in self.overrides {
+            builder = builder.set_override(key, value)?;
+        }
+
+        Ok(builder
+            .set_override("profile", profile)?
+            .build()?
+            .try_deserialize()?)
+    }
+
+    /// Read variables from the given map instead of the process environment.
+    #[cfg(test)]
+    fn env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = Some(env);
+        self
+    }
+}
+
+/// Read an environment variable, falling back to the legacy name it replaced.
+pub(crate) fn env_var(var: &str) -> Option<String> {
+    let legacy = LEGACY_ENV_VARS
+        .iter()
+        .find(|(_, current)| *current == var)
+        .map(|(legacy, _)| *legacy);
+
+    std::iter::once(var)
+        .chain(legacy)
+        .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+fn builtin_profile(profile: &str) -> Option<Vec<(&'static str, &'static str)>> {
+    match profile {
+        PRODUCTION_PROFILE => Some(vec![
+            ("http_server_url", DEFAULT_SERVER_URL),
+            ("candlestick_server_url", CANDLESTICK_SERVER_URL),
+            ("ws_server_url", DEFAULT_WS_SERVER_URL),
+        ]),
+        LOCAL_MOCK_PROFILE => Some(vec![
+            ("http_server_url", LOCAL_MOCK_SERVER_URL),
+            ("candlestick_server_url", LOCAL_MOCK_SERVER_URL),
+            ("ws_server_url", LOCAL_MOCK_WS_SERVER_URL),
+        ]),
+        _ => None,
+    }
+}
+
+fn environment(vars: HashMap<String, String>) -> Environment {
+    Environment::with_prefix(ENV_PREFIX)
+        .ignore_empty(true)
+        .source(Some(vars.into_iter().collect()))
+}
+
+/// Legacy variables renamed to the variable that replaces them.
+fn legacy_env(env: &HashMap<String, String>) -> HashMap<String, String> {
+    LEGACY_ENV_VARS
+        .iter()
+        .filter_map(|(legacy, current)| {
+            env.get(*legacy)
+                .map(|value| (current.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+/// A table of settings added as a configuration layer.
+#[derive(Clone, Debug)]
+struct Layer(Map<String, Value>);
+
+impl Source for Layer {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, config::ConfigError> {
+        Ok(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const CONFIG_FILE: &str = r#"
+timeout_secs = 10
+user_agent = "config-test"
+
+[profiles.sandbox]
+http_server_url = "https://sandbox.example.com"
+candlestick_server_url = "https://sandbox-chartdata.example.com"
+ws_server_url = "wss://sandbox-ws.example.com/ws"
+api_key = "sandbox-key"
+
+[profiles.local-mock]
+http_server_url = "http://127.0.0.1:4000"
+"#;
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn config_file(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("sfox-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, CONFIG_FILE).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_production_defaults() {
+        let config = ConfigLoader::new().env(env(&[])).load().unwrap();
+
+        assert_eq!(config.profile, PRODUCTION_PROFILE);
+        assert_eq!(config.http_server_url, DEFAULT_SERVER_URL);
+        assert_eq!(config.candlestick_server_url, CANDLESTICK_SERVER_URL);
+        assert_eq!(config.ws_server_url, DEFAULT_WS_SERVER_URL);
+        assert!(config.api_key.is_none());
+        assert!(config.credentials().is_none());
+    }
+
+    #[test]
+    fn test_profile_from_file() {
+        let path = config_file("profile");
+        let config = ConfigLoader::new()
+            .env(env(&[(PROFILE_ENV_VAR, SANDBOX_PROFILE)]))
+            .file(&path)
+            .load()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.profile, SANDBOX_PROFILE);
+        assert_eq!(config.http_server_url, "https://sandbox.example.com");
+        assert_eq!(config.ws_server_url, "wss://sandbox-ws.example.com/ws");
+        assert_eq!(config.timeout_secs, Some(10));
+        assert_eq!(config.user_agent.as_deref(), Some("config-test"));
+        assert_eq!(
+            config
+                .credentials()
+                .unwrap()
+                .api_key()
+                .unwrap()
+                .expose_secret(),
+            "sandbox-key"
+        );
+    }
+
+    #[test]
+    fn test_file_profile_extends_builtin_profile() {
+        let path = config_file("builtin");
+        let config = ConfigLoader::new()
+            .env(env(&[]))
+            .file(&path)
+            .profile(LOCAL_MOCK_PROFILE)
+            .load()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.http_server_url, "http://127.0.0.1:4000");
+        assert_eq!(config.ws_server_url, LOCAL_MOCK_WS_SERVER_URL);
+    }
+
+    #[test]
+    fn test_env_and_overrides_take_precedence() {
+        let path = config_file("precedence");
+        let config = ConfigLoader::new()
+            .env(env(&[
+                (CONFIG_FILE_ENV_VAR, path.to_str().unwrap()),
+                (PROFILE_ENV_VAR, SANDBOX_PROFILE),
+                ("SFOX_API_KEY", "env-key"),
+                ("SFOX_WS_SERVER_URL", "wss://env.example.com/ws"),
+            ]))
+            .set("ws_server_url", "wss://override.example.com/ws")
+            .set("timeout_secs", "30")
+            .load()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.http_server_url, "https://sandbox.example.com");
+        assert_eq!(config.api_key.unwrap().expose_secret(), "env-key");
+        assert_eq!(config.ws_server_url, "wss://override.example.com/ws");
+        assert_eq!(config.timeout_secs, Some(30));
+    }
+
+    #[test]
+    fn test_legacy_env_vars() {
+        let config = ConfigLoader::new()
+            .env(env(&[
+                ("SFOX_AUTH_TOKEN", "legacy-key"),
+                ("SFOX_SERVER_URL", "http://legacy.example.com"),
+                ("SFOX_HTTP_SERVER_URL", "http://current.example.com"),
+                (
+                    "CANDLESTICK_SERVER_URL",
+                    "http://legacy-chartdata.example.com",
+                ),
+            ]))
+            .load()
+            .unwrap();
+
+        assert_eq!(config.api_key.unwrap().expose_secret(), "legacy-key");
+        assert_eq!(config.http_server_url, "http://current.example.com");
+        assert_eq!(
+            config.candlestick_server_url,
+            "http://legacy-chartdata.example.com"
+        );
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        let result = ConfigLoader::new()
+            .env(env(&[]))
+            .profile(SANDBOX_PROFILE)
+            .load();
+
+        assert!(matches!(result, Err(ConfigError::UnknownProfile(_))));
+    }
+}
@@ -0,0 +1,284 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Deserializer};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+/// Environment variable read by [`EnvCredentials::default`].
+pub const DEFAULT_API_KEY_ENV_VAR: &str = "SFOX_API_KEY";
+/// Former name of [`DEFAULT_API_KEY_ENV_VAR`], still read when the new one is not set.
+pub const LEGACY_API_KEY_ENV_VAR: &str = "SFOX_AUTH_TOKEN";
+
+/// Error returned when an API key cannot be obtained from a provider.
+#[derive(Clone, Debug, Error)]
+pub enum CredentialError {
+    #[error("API key not found: {0}")]
+    Missing(String),
+    #[error("could not read API key: {0}")]
+    Unreadable(String),
+}
+
+/// An API key held in memory. It is redacted from `Debug` output and zeroed when dropped.
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        SecretString(secret.into())
+    }
+
+    /// Access the secret value. Avoid keeping copies of the returned string around.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        SecretString(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        SecretString(secret.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(SecretString)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Supplies the API key used to authenticate HTTP requests and WebSocket sessions.
+///
+/// Providers are queried every time a key is needed, so a provider whose source changes (a
+/// rewritten file, a rotated vault secret, [`StaticCredentials::rotate`]) rotates the key of every
+/// client that shares it without rebuilding the client.
+pub trait CredentialProvider: fmt::Debug + Send + Sync {
+    fn api_key(&self) -> Result<SecretString, CredentialError>;
+}
+
+/// Reads the API key from environment variables on every use. The first variable that is set
+/// wins.
+#[derive(Clone, Debug)]
+pub struct EnvCredentials {
+    pub vars: Vec<String>,
+}
+
+impl EnvCredentials {
+    pub fn new(var: impl Into<String>) -> Self {
+        EnvCredentials {
+            vars: vec![var.into()],
+        }
+    }
+}
+
+impl Default for EnvCredentials {
+    /// Reads `SFOX_API_KEY`, falling back to `SFOX_AUTH_TOKEN`.
+    fn default() -> Self {
+        EnvCredentials {
+            vars: vec![
+                DEFAULT_API_KEY_ENV_VAR.to_string(),
+                LEGACY_API_KEY_ENV_VAR.to_string(),
+            ],
+        }
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn api_key(&self) -> Result<SecretString, CredentialError> {
+        self.vars
+            .iter()
+            .find_map(|var| env::var(var).ok().filter(|key| !key.is_empty()))
+            .map(SecretString::from)
+            .ok_or_else(|| {
+                CredentialError::Missing(format!(
+                    "{} env variable not set.",
+                    self.vars.join(" or ")
+                ))
+            })
+    }
+}
+
+/// Reads the API key from a file on every use, e.g. a mounted Kubernetes secret.
+/// Surrounding whitespace is ignored.
+#[derive(Clone, Debug)]
+pub struct FileCredentials {
+    pub path: PathBuf,
+}
+
+impl FileCredentials {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileCredentials { path: path.into() }
+    }
+}
+
+impl CredentialProvider for FileCredentials {
+    fn api_key(&self) -> Result<SecretString, CredentialError> {
+        let mut contents = fs::read_to_string(&self.path)
+            .map_err(|e| CredentialError::Unreadable(format!("{}: {}", self.path.display(), e)))?;
+        let key = SecretString::new(contents.trim());
+        contents.zeroize();
+
+        if key.is_empty() {
+            return Err(CredentialError::Missing(format!(
+                "{} is empty",
+                self.path.display()
+            )));
+        }
+
+        Ok(key)
+    }
+}
+
+/// An API key held in memory that can be replaced at runtime with [`StaticCredentials::rotate`].
+#[derive(Debug, Default)]
+pub struct StaticCredentials {
+    key: RwLock<SecretString>,
+}
+
+impl StaticCredentials {
+    pub fn new(key: impl Into<SecretString>) -> Self {
+        StaticCredentials {
+            key: RwLock::new(key.into()),
+        }
+    }
+
+    /// Replace the key; subsequent requests use the new one.
+    pub fn rotate(&self, key: impl Into<SecretString>) {
+        let mut current = match self.key.write() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *current = key.into();
+    }
+}
+
+impl CredentialProvider for StaticCredentials {
+    fn api_key(&self) -> Result<SecretString, CredentialError> {
+        let key = match self.key.read() {
+            Ok(key) => key.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+
+        if key.is_empty() {
+            return Err(CredentialError::Missing("API key is empty".to_string()));
+        }
+
+        Ok(key)
+    }
+}
+
+/// Obtains the API key from a closure, e.g. one reading from a secrets manager's local cache.
+pub struct FnCredentials<F>(pub F);
+
+impl<F> fmt::Debug for FnCredentials<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FnCredentials")
+    }
+}
+
+impl<F> CredentialProvider for FnCredentials<F>
+where
+    F: Fn() -> Result<SecretString, CredentialError> + Send + Sync,
+{
+    fn api_key(&self) -> Result<SecretString, CredentialError> {
+        (self.0)()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let secret = SecretString::new("super-secret");
+
+        assert_eq!(format!("{:?}", secret), "SecretString([REDACTED])");
+        assert_eq!(secret.expose_secret(), "super-secret");
+
+        let credentials = StaticCredentials::new("super-secret");
+        assert!(!format!("{:?}", credentials).contains("super-secret"));
+    }
+
+    #[test]
+    fn test_env_credentials() {
+        env::set_var("SFOX_TEST_CREDENTIALS_KEY", "from-env");
+
+        let credentials = EnvCredentials::new("SFOX_TEST_CREDENTIALS_KEY");
+        assert_eq!(credentials.api_key().unwrap().expose_secret(), "from-env");
+
+        let fallback = EnvCredentials {
+            vars: vec![
+                "SFOX_TEST_CREDENTIALS_MISSING".to_string(),
+                "SFOX_TEST_CREDENTIALS_KEY".to_string(),
+            ],
+        };
+        assert_eq!(fallback.api_key().unwrap().expose_secret(), "from-env");
+
+        let missing = EnvCredentials::new("SFOX_TEST_CREDENTIALS_MISSING");
+        assert!(matches!(
+            missing.api_key(),
+            Err(CredentialError::Missing(_))
+        ));
+    }
+
+    #[test]
+    fn test_file_credentials() {
+        let path = env::temp_dir().join(format!("sfox-credentials-{}", std::process::id()));
+        let mut file = fs::File::create(&path).unwrap();
+        writeln!(file, "  from-file  ").unwrap();
+
+        let credentials = FileCredentials::new(&path);
+        assert_eq!(credentials.api_key().unwrap().expose_secret(), "from-file");
+
+        fs::write(&path, "rotated").unwrap();
+        assert_eq!(credentials.api_key().unwrap().expose_secret(), "rotated");
+
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            credentials.api_key(),
+            Err(CredentialError::Unreadable(_))
+        ));
+    }
+
+    #[test]
+    fn test_static_credentials_rotate() {
+        let credentials = StaticCredentials::new("first");
+        assert_eq!(credentials.api_key().unwrap().expose_secret(), "first");
+
+        credentials.rotate("second");
+        assert_eq!(credentials.api_key().unwrap().expose_secret(), "second");
+    }
+
+    #[test]
+    fn test_fn_credentials() {
+        let credentials = FnCredentials(|| Ok(SecretString::new("from-vault")));
+        assert_eq!(credentials.api_key().unwrap().expose_secret(), "from-vault");
+    }
+}
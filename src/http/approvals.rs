@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures_util::{stream, Stream, StreamExt};
+
+use super::v1::custody::ApprovalRequest;
+use super::{Client, HttpError};
+
+/// How often [`Client::watch_approvals`] polls when no interval is given.
+pub const DEFAULT_APPROVAL_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A change in the pending approval requests between two polls.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApprovalEvent {
+    /// A pending request seen for the first time.
+    New(ApprovalRequest),
+    /// A pending request whose status, approvals or responses changed.
+    Changed {
+        previous: Box<ApprovalRequest>,
+        current: ApprovalRequest,
+    },
+    /// A request that is no longer pending because it was approved, rejected or withdrawn, in
+    /// the state it was last seen.
+    Resolved(ApprovalRequest),
+}
+
+/// The pending requests seen by the last poll.
+#[derive(Debug, Default)]
+struct PendingApprovals {
+    seen: HashMap<usize, ApprovalRequest>,
+}
+
+impl PendingApprovals {
+    /// Replaces the pending requests, returning what changed: new and changed requests in id
+    /// order, then resolved ones.
+    fn update(&mut self, mut pending: Vec<ApprovalRequest>) -> Vec<ApprovalEvent> {
+        pending.sort_by_key(|request| request.approval_id);
+
+        let mut events = vec![];
+        let mut current = HashMap::with_capacity(pending.len());
+        for request in pending {
+            match self.seen.remove(&request.approval_id) {
+                None => events.push(ApprovalEvent::New(request.clone())),
+                Some(previous) if previous != request => events.push(ApprovalEvent::Changed {
+                    previous: Box::new(previous),
+                    current: request.clone(),
+                }),
+                Some(_) => {}
+            }
+            current.insert(request.approval_id, request);
+        }
+
+        let mut resolved: Vec<ApprovalRequest> = self.seen.drain().map(|(_, r)| r).collect();
+        resolved.sort_by_key(|request| request.approval_id);
+        events.extend(resolved.into_iter().map(ApprovalEvent::Resolved));
+
+        self.seen = current;
+        events
+    }
+}
+
+impl Client {
+    /// Polls the pending approval requests every `interval` and streams what changed. The first
+    /// poll reports every pending request as [`ApprovalEvent::New`].
+    ///
+    /// A failed poll yields its error and the stream carries on with the next poll, so approvers'
+    /// tooling can log failures without restarting the watch.
+    pub fn watch_approvals(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<ApprovalEvent, HttpError>> + Send + 'static {
+        let client = self.clone();
+        let state = (PendingApprovals::default(), true);
+
+        stream::unfold(state, move |(mut pending, first)| {
+            let client = client.clone();
+            async move {
+                if !first {
+                    tokio::time::sleep(interval).await;
+                }
+
+                let events: Vec<Result<ApprovalEvent, HttpError>> =
+                    match client.approval_requests(true).await {
+                        Ok(response) => pending.update(response.data).into_iter().map(Ok).collect(),
+                        Err(e) => vec![Err(e)],
+                    };
+
+                Some((stream::iter(events), (pending, false)))
+            }
+        })
+        .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mockito::Mock;
+    use serde_json::json;
+
+    use crate::util::server::new_test_server_and_client;
+
+    fn request(id: usize, received_approvals: usize) -> serde_json::Value {
+        json!({
+            "approval_id": id,
+            "requested_by_username": "example@email.com",
+            "requested_by_uaid": "6ea3fb9e-7797-11eb-aa51-0242ac120002",
+            "date_added": "2021-03-03T20:28:41.000Z",
+            "status": "Pending",
+            "approval_type": "WITHDRAW",
+            "required_approvals": 2,
+            "received_approvals": received_approvals,
+            "action_details": {
+                "atx_currency_code": "btc",
+                "atx_amount": 5,
+                "atx_dest_address": "0x1232131223",
+                "threshold": 1
+            },
+            "approval_responses": []
+        })
+    }
+
+    async fn mock_poll(server: &mut mockito::ServerGuard, data: serde_json::Value) -> Mock {
+        server
+            .mock("GET", "/v1/approvals?pending=true")
+            .with_body(json!({ "data": data }).to_string())
+            .expect(1)
+            .create_async()
+            .await
+    }
+
+    fn id(event: &ApprovalEvent) -> (&'static str, usize) {
+        match event {
+            ApprovalEvent::New(r) => ("new", r.approval_id),
+            ApprovalEvent::Changed { current, .. } => ("changed", current.approval_id),
+            ApprovalEvent::Resolved(r) => ("resolved", r.approval_id),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_approvals() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mocks = vec![
+            mock_poll(&mut server, json!([request(1, 0)])).await,
+            mock_poll(&mut server, json!([request(2, 0), request(1, 1)])).await,
+            mock_poll(&mut server, json!([request(2, 0)])).await,
+        ];
+
+        let events: Vec<ApprovalEvent> = client
+            .watch_approvals(Duration::ZERO)
+            .take(4)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(
+            events.iter().map(id).collect::<Vec<_>>(),
+            vec![("new", 1), ("changed", 1), ("new", 2), ("resolved", 1)]
+        );
+        match &events[1] {
+            ApprovalEvent::Changed { previous, current } => {
+                assert_eq!(previous.received_approvals, 0);
+                assert_eq!(current.received_approvals, 1);
+            }
+            other => panic!("expected a change, got {:?}", other),
+        }
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_approvals_continues_after_errors() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let failed = server
+            .mock("GET", "/v1/approvals?pending=true")
+            .with_status(401)
+            .with_body(r#"{"error": "invalid token"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = mock_poll(&mut server, json!([request(1, 0)])).await;
+
+        let events: Vec<Result<ApprovalEvent, HttpError>> = client
+            .watch_approvals(Duration::ZERO)
+            .take(2)
+            .collect()
+            .await;
+
+        assert!(events[0].is_err());
+        assert_eq!(id(events[1].as_ref().unwrap()), ("new", 1));
+        failed.assert_async().await;
+        ok.assert_async().await;
+    }
+}
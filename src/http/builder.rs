@@ -0,0 +1,318 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{self, SfoxConfig};
+use crate::credentials::{CredentialProvider, EnvCredentials, StaticCredentials};
+
+use super::rate_limit::RateLimiter;
+use super::retry::RetryPolicy;
+use super::{Client, HttpError, CANDLESTICK_SERVER_URL, DEFAULT_SERVER_URL};
+
+/// Configures and builds an HTTP [`Client`].
+///
+/// Settings that are not given explicitly fall back to the environment (`SFOX_API_KEY`,
+/// `SFOX_HTTP_SERVER_URL`, `SFOX_CANDLESTICK_SERVER_URL`) and then to the production defaults.
+/// The former names `SFOX_AUTH_TOKEN`, `SFOX_SERVER_URL` and `CANDLESTICK_SERVER_URL` are still
+/// read when the new ones are not set. See [`crate::config`] for profile-based configuration.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use sfox::http::ClientBuilder;
+///
+/// let client = ClientBuilder::new()
+///     .auth_token("my-api-key")
+///     .timeout(Duration::from_secs(10))
+///     .user_agent("my-trading-bot/1.0")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct ClientBuilder {
+    candlestick_server_url: Option<String>,
+    connect_timeout: Option<Duration>,
+    credentials: Option<Arc<dyn CredentialProvider>>,
+    proxy: Option<reqwest::Proxy>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    reqwest_client: Option<reqwest::Client>,
+    retry_policy: Option<RetryPolicy>,
+    server_url: Option<String>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        ClientBuilder::default()
+    }
+
+    /// A builder preset with the server URLs, credentials and transport settings of a loaded
+    /// [`SfoxConfig`]. Further settings, such as a retry policy, can be chained before `build`.
+    pub fn from_config(config: &SfoxConfig) -> Self {
+        let mut builder = ClientBuilder::new()
+            .server_url(config.http_server_url.clone())
+            .candlestick_server_url(config.candlestick_server_url.clone());
+
+        if let Some(credentials) = config.credentials() {
+            builder = builder.credentials(credentials);
+        }
+        builder.timeout = config.timeout_secs.map(Duration::from_secs);
+        builder.connect_timeout = config.connect_timeout_secs.map(Duration::from_secs);
+        builder.user_agent = config.user_agent.clone();
+
+        builder
+    }
+
+    /// API key used as the bearer token. Defaults to `SFOX_API_KEY`.
+    pub fn auth_token(self, auth_token: impl Into<String>) -> Self {
+        self.credentials(Arc::new(StaticCredentials::new(auth_token.into())))
+    }
+
+    /// Source of the API key, queried before every request. Share the `Arc` with other clients,
+    /// including the WebSocket client, to rotate the key for all of them at once.
+    pub fn credentials(mut self, credentials: Arc<dyn CredentialProvider>) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Base URL of the core API. Defaults to `SFOX_HTTP_SERVER_URL`, then [`DEFAULT_SERVER_URL`].
+    pub fn server_url(mut self, server_url: impl Into<String>) -> Self {
+        self.server_url = Some(server_url.into());
+        self
+    }
+
+    /// Base URL of the candlestick API. Defaults to `SFOX_CANDLESTICK_SERVER_URL`, then
+    /// [`CANDLESTICK_SERVER_URL`].
+    pub fn candlestick_server_url(mut self, candlestick_server_url: impl Into<String>) -> Self {
+        self.candlestick_server_url = Some(candlestick_server_url.into());
+        self
+    }
+
+    /// Total time allowed for a single request attempt, from connecting to reading the body.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Time allowed to establish a connection.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Route requests through a proxy.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Value of the `User-Agent` header sent with every request.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Use a preconfigured `reqwest` client. Cannot be combined with `timeout`,
+    /// `connect_timeout`, `proxy` or `user_agent`, which must then be set on that client.
+    pub fn reqwest_client(mut self, reqwest_client: reqwest::Client) -> Self {
+        self.reqwest_client = Some(reqwest_client);
+        self
+    }
+
+    /// Retry policy for transient failures. Defaults to [`RetryPolicy::default`].
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Limit request rates on the client side. Pass the same `Arc` to several builders to share
+    /// one budget between clients.
+    pub fn rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, HttpError> {
+        let http_client = match self.reqwest_client {
+            Some(client) => {
+                if self.timeout.is_some()
+                    || self.connect_timeout.is_some()
+                    || self.proxy.is_some()
+                    || self.user_agent.is_some()
+                {
+                    return Err(HttpError::InitializationError(
+                        "timeout, connect_timeout, proxy and user_agent must be configured on the provided reqwest client".to_string(),
+                    ));
+                }
+                client
+            }
+            None => {
+                let mut builder = reqwest::Client::builder();
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(connect_timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(connect_timeout);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+                if let Some(user_agent) = self.user_agent {
+                    builder = builder.user_agent(user_agent);
+                }
+                builder
+                    .build()
+                    .map_err(|e| HttpError::InitializationError(e.to_string()))?
+            }
+        };
+
+        let credentials = match self.credentials {
+            Some(credentials) => credentials,
+            None => {
+                let credentials = EnvCredentials::default();
+                // Fail at construction rather than on the first request when nothing is configured.
+                credentials
+                    .api_key()
+                    .map_err(|e| HttpError::InitializationError(e.to_string()))?;
+                Arc::new(credentials)
+            }
+        };
+
+        let server_url = self
+            .server_url
+            .or_else(|| config::env_var(config::HTTP_SERVER_URL_ENV_VAR))
+            .unwrap_or_else(|| DEFAULT_SERVER_URL.to_string());
+
+        let candlestick_server_url = self
+            .candlestick_server_url
+            .or_else(|| config::env_var(config::CANDLESTICK_SERVER_URL_ENV_VAR))
+            .unwrap_or_else(|| CANDLESTICK_SERVER_URL.to_string());
+
+        Ok(Client {
+            candlestick_server_url,
+            credentials,
+            http_client,
+            rate_limiter: self.rate_limiter,
+            retry_policy: self.retry_policy.unwrap_or_default(),
+            server_url,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::http::HttpVerb;
+    use crate::util::server::start_test_http_server;
+
+    #[test]
+    fn test_build_with_explicit_settings() {
+        let client = ClientBuilder::new()
+            .auth_token("account-two")
+            .server_url("http://localhost:4000")
+            .candlestick_server_url("http://localhost:4001")
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            client.credentials.api_key().unwrap().expose_secret(),
+            "account-two"
+        );
+        assert_eq!(client.server_url, "http://localhost:4000");
+        assert_eq!(client.candlestick_server_url, "http://localhost:4001");
+        assert_eq!(client.retry_policy.max_attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rotated_credentials_are_used() {
+        let (mut server, _mocks) = start_test_http_server(vec![]).await;
+        let first = server
+            .mock("GET", "/v1/user/balance")
+            .match_header("authorization", "Bearer first-key")
+            .with_body("[]")
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/v1/user/balance")
+            .match_header("authorization", "Bearer second-key")
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let credentials = Arc::new(StaticCredentials::new("first-key"));
+        let client = ClientBuilder::new()
+            .credentials(credentials.clone())
+            .server_url(server.url())
+            .build()
+            .unwrap();
+
+        assert!(client.account_balance().await.is_ok());
+        credentials.rotate("second-key");
+        assert!(client.account_balance().await.is_ok());
+
+        assert!(!format!("{:?}", client).contains("second-key"));
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[test]
+    fn test_reqwest_client_conflicts_with_transport_settings() {
+        let result = ClientBuilder::new()
+            .auth_token("secret")
+            .reqwest_client(reqwest::Client::new())
+            .timeout(Duration::from_secs(1))
+            .build();
+
+        assert!(matches!(result, Err(HttpError::InitializationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_user_agent_is_sent() {
+        let (mut server, _mocks) = start_test_http_server(vec![]).await;
+        let mock = server
+            .mock("GET", "/v1/user/balance")
+            .match_header("user-agent", "my-bot/1.0")
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let client = ClientBuilder::new()
+            .auth_token("secret")
+            .server_url(server.url())
+            .user_agent("my-bot/1.0")
+            .build()
+            .unwrap();
+
+        assert!(client.account_balance().await.is_ok());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // Accepts connections but never responds.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _handle = tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let client = ClientBuilder::new()
+            .auth_token("secret")
+            .server_url(format!("http://{}", addr))
+            .timeout(Duration::from_millis(100))
+            .retry_policy(RetryPolicy::none())
+            .build()
+            .unwrap();
+
+        let url = client.url_for_v1_resource("user/balance");
+        let result = client.request::<String>(HttpVerb::Get, &url, None).await;
+
+        assert!(matches!(result, Err(HttpError::TransportError(_))));
+    }
+}
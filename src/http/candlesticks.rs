@@ -1,46 +1,282 @@
-use futures_util::Future;
-use serde::Deserialize;
+use futures_util::{future, stream, Future, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use super::{Client, HttpError, HttpVerb};
+use crate::num::Number;
+
+/// Most candles the candlestick server returns for one request.
+pub const MAX_CANDLES_PER_REQUEST: usize = 500;
+
+/// Requests [`Client::candlesticks_range`] keeps in flight at once.
+pub const DEFAULT_CANDLESTICK_CONCURRENCY: usize = 4;
 
 /// A single element of candlestick chart data returned from the API.
 #[derive(Clone, Debug, Deserialize)]
 pub struct Candle {
-    pub open_price: f64,
-    pub high_price: f64,
-    pub low_price: f64,
-    pub close_price: f64,
-    pub volume: f64,
+    pub open_price: Number,
+    pub high_price: Number,
+    pub low_price: Number,
+    pub close_price: Number,
+    pub volume: Number,
     pub start_time: usize,
     pub pair: String,
     pub candle_period: usize,
-    pub vwap: f64,
+    pub vwap: Number,
     pub trades: usize,
 }
 
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandlesticksQuery {
+    pub pair: String,
+    pub start_time: usize,
+    pub end_time: usize,
+    /// Candle length in seconds.
+    pub period: usize,
+}
+
+/// What [`Client::candlesticks_range_with`] does about periods without a candle.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CandleGaps {
+    /// Yield a [`CandleRangeError::Gap`] for each run of missing periods and carry on.
+    #[default]
+    Report,
+    /// Yield flat, zero-volume candles at the previous close. Missing periods before the first
+    /// candle have no close to carry and are still reported.
+    Fill,
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum CandleRangeError {
+    #[error(transparent)]
+    Http(#[from] HttpError),
+    /// No candles start between `start_time` and `end_time`, inclusive.
+    #[error("No candles from {start_time} to {end_time}")]
+    Gap { start_time: usize, end_time: usize },
+}
+
+/// A window of candles longer than a single request may return.
+#[derive(Clone, Debug)]
+pub struct CandleRange {
+    pub pair: String,
+    pub start_time: usize,
+    pub end_time: usize,
+    /// Candle length in seconds.
+    pub period: usize,
+    /// Requests in flight at once; at least one is always sent.
+    pub concurrency: usize,
+    pub gaps: CandleGaps,
+}
+
+impl CandleRange {
+    pub fn new(pair: &str, start_time: usize, end_time: usize, period: usize) -> Self {
+        CandleRange {
+            pair: pair.to_string(),
+            start_time,
+            end_time,
+            period,
+            concurrency: DEFAULT_CANDLESTICK_CONCURRENCY,
+            gaps: CandleGaps::default(),
+        }
+    }
+
+    /// The `(start_time, end_time)` of every request. Each spans at most
+    /// [`MAX_CANDLES_PER_REQUEST`] candles and shares its boundary candle with the next, so
+    /// nothing is lost whether or not the server treats `endTime` as inclusive.
+    fn chunks(&self) -> Vec<(usize, usize)> {
+        let span = (MAX_CANDLES_PER_REQUEST - 1) * self.period;
+        let mut chunks = vec![];
+        let mut start = self.start_time;
+
+        loop {
+            let end = start.saturating_add(span).min(self.end_time);
+            chunks.push((start, end));
+
+            if end >= self.end_time {
+                return chunks;
+            }
+            start = end;
+        }
+    }
+}
+
+/// Puts the candles of consecutive chunks into one gapless sequence.
+struct CandleSequence {
+    gaps: CandleGaps,
+    period: usize,
+    /// Start time of the next candle expected.
+    next: usize,
+    /// Start time of the last candle in the range.
+    last: usize,
+    previous: Option<Candle>,
+    failed: bool,
+}
+
+impl CandleSequence {
+    fn new(range: &CandleRange) -> Self {
+        let period = range.period;
+
+        CandleSequence {
+            gaps: range.gaps,
+            period,
+            next: range.start_time + (period - range.start_time % period) % period,
+            last: range.end_time / period * period,
+            previous: None,
+            failed: false,
+        }
+    }
+
+    /// Sequences the candles of the next chunk, or closes the range on `None`. Returns `None`
+    /// once a request has failed, ending the stream.
+    fn push(
+        &mut self,
+        chunk: Option<Result<Vec<Candle>, HttpError>>,
+    ) -> Option<Vec<Result<Candle, CandleRangeError>>> {
+        if self.failed {
+            return None;
+        }
+
+        let mut items = vec![];
+        match chunk {
+            Some(Ok(mut candles)) => {
+                candles.sort_by_key(|candle| candle.start_time);
+
+                for candle in candles {
+                    // Boundary candles repeat at the start of the next chunk.
+                    if candle.start_time < self.next || candle.start_time > self.last {
+                        continue;
+                    }
+
+                    self.skip_to(candle.start_time, &mut items);
+                    self.next = candle.start_time + self.period;
+                    self.previous = Some(candle.clone());
+                    items.push(Ok(candle));
+                }
+            }
+            Some(Err(e)) => {
+                self.failed = true;
+                items.push(Err(e.into()));
+            }
+            None => self.skip_to(self.last + self.period, &mut items),
+        }
+
+        Some(items)
+    }
+
+    /// Fills or reports the missing periods from the next expected candle up to `until`.
+    fn skip_to(&mut self, until: usize, items: &mut Vec<Result<Candle, CandleRangeError>>) {
+        if until <= self.next {
+            return;
+        }
+
+        match (&self.previous, self.gaps) {
+            (Some(previous), CandleGaps::Fill) => items.extend(
+                (self.next..until)
+                    .step_by(self.period)
+                    .map(|start_time| Ok(flat_candle(previous, start_time))),
+            ),
+            _ => items.push(Err(CandleRangeError::Gap {
+                start_time: self.next,
+                end_time: (until - self.period).max(self.next),
+            })),
+        }
+
+        self.next = until;
+    }
+}
+
+/// A candle without trades, priced at the close of `previous`.
+fn flat_candle(previous: &Candle, start_time: usize) -> Candle {
+    let price = previous.close_price;
+
+    Candle {
+        open_price: price,
+        high_price: price,
+        low_price: price,
+        close_price: price,
+        volume: Number::default(),
+        start_time,
+        pair: previous.pair.clone(),
+        candle_period: previous.candle_period,
+        vwap: price,
+        trades: 0,
+    }
+}
+
 impl Client {
     /// Candlestick chart data from the SFox markets.
     /// Responses are limited to 500 candles from the server.
     pub fn candlesticks(
-        self,
+        &self,
         pair: &str,
         start_time: usize,
         end_time: usize,
         period_seconds: usize,
-    ) -> impl Future<Output = Result<Vec<Candle>, HttpError>> {
-        let query_str = format!(
-            "?pair={}&startTime={}&endTime={}&period={}",
-            pair, start_time, end_time, period_seconds
-        );
+    ) -> impl Future<Output = Result<Vec<Candle>, HttpError>> + Send + 'static {
+        let url = self.url_for_candlestick_resource("");
+        let query = CandlesticksQuery {
+            pair: pair.to_string(),
+            start_time,
+            end_time,
+            period: period_seconds,
+        };
+
+        self.request_with_query(HttpVerb::Get, &url, &query)
+    }
+
+    /// Every candle from `start_time` to `end_time`, however many requests that takes, sorted
+    /// by start time. Missing periods are reported; see [`Client::candlesticks_range_with`].
+    pub fn candlesticks_range(
+        &self,
+        pair: &str,
+        start_time: usize,
+        end_time: usize,
+        period_seconds: usize,
+    ) -> impl Stream<Item = Result<Candle, CandleRangeError>> + Send + 'static {
+        self.candlesticks_range_with(CandleRange::new(pair, start_time, end_time, period_seconds))
+    }
+
+    /// Splits `range` into requests of at most [`MAX_CANDLES_PER_REQUEST`] candles and
+    /// fetches up to `range.concurrency` of them at a time. Candles are yielded in order of
+    /// start time, without the duplicates of overlapping requests, and missing periods are
+    /// handled as `range.gaps` says.
+    ///
+    /// A [`CandleRangeError::Gap`] does not end the stream; a failed request does.
+    pub fn candlesticks_range_with(
+        &self,
+        range: CandleRange,
+    ) -> impl Stream<Item = Result<Candle, CandleRangeError>> + Send + 'static {
+        if range.period == 0 || range.start_time > range.end_time {
+            let error = HttpError::InvalidRequest(format!(
+                "Invalid candle range: {} to {} every {} seconds",
+                range.start_time, range.end_time, range.period
+            ));
+
+            return stream::iter(vec![Err(error.into())]).left_stream();
+        }
 
-        let url = self.url_for_candlestick_resource(&query_str);
+        let client = self.clone();
+        let pair = range.pair.clone();
+        let period = range.period;
 
-        self.request(HttpVerb::Get, &url, None)
+        stream::iter(range.chunks())
+            .map(move |(start, end)| client.candlesticks(&pair, start, end, period))
+            .buffered(range.concurrency.max(1))
+            .map(Some)
+            .chain(stream::once(future::ready(None)))
+            .scan(CandleSequence::new(&range), |sequence, chunk| {
+                future::ready(sequence.push(chunk))
+            })
+            .flat_map(stream::iter)
+            .right_stream()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use futures_util::TryStreamExt;
+
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
     use super::*;
@@ -96,4 +332,182 @@ mod tests {
             mock.assert_async().await;
         }
     }
+
+    const START: usize = 1592939280;
+
+    /// Minute candles starting `slots` minutes after `START`, closing at their slot number.
+    fn candles(slots: impl Iterator<Item = usize>) -> String {
+        let candles: Vec<serde_json::Value> = slots
+            .map(|slot| {
+                serde_json::json!({
+                    "open_price": 9654,
+                    "high_price": 9662.37,
+                    "low_price": 9653.66,
+                    "close_price": slot,
+                    "volume": 6.31945755,
+                    "start_time": START + slot * 60,
+                    "pair": "btcusd",
+                    "candle_period": 60,
+                    "vwap": 9655.70504211,
+                    "trades": 53
+                })
+            })
+            .collect();
+
+        serde_json::to_string(&candles).unwrap()
+    }
+
+    fn chunk_mock(start: usize, end: usize, body: String) -> ApiMock {
+        ApiMock {
+            action: HttpVerb::Get,
+            body,
+            path: format!(
+                "/candlesticks?pair=btcusd&startTime={}&endTime={}&period=60",
+                START + start * 60,
+                START + end * 60
+            ),
+            response_code: 200,
+        }
+    }
+
+    #[test]
+    fn test_chunks_stay_under_the_cap() {
+        let range = CandleRange::new("btcusd", 0, 1200 * 60, 60);
+
+        assert_eq!(
+            range.chunks(),
+            vec![(0, 499 * 60), (499 * 60, 998 * 60), (998 * 60, 1200 * 60)]
+        );
+        assert_eq!(
+            CandleRange::new("btcusd", 60, 60, 60).chunks(),
+            vec![(60, 60)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_candlesticks_range_splits_and_dedupes() {
+        let mocks = vec![
+            chunk_mock(0, 499, candles(0..500)),
+            chunk_mock(499, 600, candles((499..601).rev())),
+        ];
+
+        let (client, _server, mock_results) = new_test_server_and_client(mocks).await;
+
+        let starts: Vec<usize> = client
+            .candlesticks_range("btcusd", START, START + 600 * 60, 60)
+            .map_ok(|candle| candle.start_time)
+            .try_collect()
+            .await
+            .unwrap();
+
+        let expected: Vec<usize> = (0..601).map(|slot| START + slot * 60).collect();
+        assert_eq!(starts, expected);
+
+        for mock in mock_results {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_candlesticks_range_reports_gaps() {
+        let mocks = vec![chunk_mock(0, 5, candles([0, 1, 4].into_iter()))];
+
+        let (client, _server, _mock_results) = new_test_server_and_client(mocks).await;
+
+        let items: Vec<_> = client
+            .candlesticks_range("btcusd", START, START + 5 * 60, 60)
+            .collect()
+            .await;
+
+        let gaps: Vec<(usize, usize)> = items
+            .iter()
+            .filter_map(|item| match item {
+                Err(CandleRangeError::Gap {
+                    start_time,
+                    end_time,
+                }) => Some((*start_time, *end_time)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(items.len(), 5);
+        assert_eq!(
+            gaps,
+            vec![(START + 120, START + 180), (START + 300, START + 300)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_candlesticks_range_fills_gaps() {
+        let mocks = vec![chunk_mock(0, 5, candles([1, 3].into_iter()))];
+
+        let (client, _server, _mock_results) = new_test_server_and_client(mocks).await;
+
+        let range = CandleRange {
+            gaps: CandleGaps::Fill,
+            ..CandleRange::new("btcusd", START, START + 5 * 60, 60)
+        };
+        let items: Vec<_> = client.candlesticks_range_with(range).collect().await;
+
+        // Nothing to carry into the first minute.
+        assert!(matches!(
+            items[0],
+            Err(CandleRangeError::Gap { start_time, end_time }) if start_time == START && end_time == START
+        ));
+
+        let candles: Vec<Candle> = items.into_iter().skip(1).map(Result::unwrap).collect();
+        let starts: Vec<usize> = candles.iter().map(|c| c.start_time).collect();
+        assert_eq!(
+            starts,
+            (1..6).map(|slot| START + slot * 60).collect::<Vec<_>>()
+        );
+
+        let filled = &candles[1];
+        assert_eq!(filled.trades, 0);
+        assert_eq!(filled.volume, Number::default());
+        assert_eq!(filled.close_price, candles[0].close_price);
+        assert_eq!(candles[4].close_price, candles[2].close_price);
+    }
+
+    #[tokio::test]
+    async fn test_candlesticks_range_ends_on_failed_request() {
+        let mocks = vec![ApiMock {
+            action: HttpVerb::Get,
+            body: r#"{"error": "invalid pair"}"#.into(),
+            path: format!(
+                "/candlesticks?pair=btcusd&startTime={}&endTime={}&period=60",
+                START,
+                START + 60
+            ),
+            response_code: 400,
+        }];
+
+        let (client, _server, _mock_results) = new_test_server_and_client(mocks).await;
+
+        let items: Vec<_> = client
+            .candlesticks_range("btcusd", START, START + 60, 60)
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 1);
+        assert!(matches!(
+            items[0],
+            Err(CandleRangeError::Http(HttpError::Api { status: 400, .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_candlesticks_range_rejects_empty_period() {
+        let (client, _server, _mock_results) = new_test_server_and_client(vec![]).await;
+
+        let items: Vec<_> = client
+            .candlesticks_range("btcusd", START, START + 60, 0)
+            .collect()
+            .await;
+
+        assert!(matches!(
+            items[..],
+            [Err(CandleRangeError::Http(HttpError::InvalidRequest(_)))]
+        ));
+    }
 }
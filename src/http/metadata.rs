@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::v1::currency::{Currency, CurrencyPair};
+use super::v1::order::AssetPair;
+use super::v1::order_request::OrderRequest;
+use super::{Client, HttpError};
+use crate::num::{self, Number, Rounding};
+use crate::trading::{Pair, Side};
+
+/// How long [`MarketMetadata`] serves a snapshot before loading a new one.
+pub const DEFAULT_METADATA_TTL: Duration = Duration::from_secs(300);
+
+/// What [`MetadataSnapshot::validate_order`] does with values that are more precise than the
+/// currency allows.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PrecisionPolicy {
+    #[default]
+    Reject,
+    /// Round quantities down and prices in the order's favour: down for buys, up for sells.
+    /// Stop prices and trailing amounts are rounded to the nearest value.
+    Round,
+}
+
+/// Why an order failed pre-flight validation.
+#[derive(Clone, Debug, Error)]
+pub enum OrderValidationError {
+    #[error("Unknown pair: `{0}`")]
+    UnknownPair(String),
+    #[error("Unknown currency: `{0}`")]
+    UnknownCurrency(String),
+    #[error("Trading is disabled for `{0}`")]
+    TradingDisabled(String),
+    #[error("{field} {value} has more than {precision} decimal places")]
+    Precision {
+        field: &'static str,
+        value: Number,
+        precision: usize,
+    },
+    #[error("{field} {value} rounds to zero at {precision} decimal places")]
+    RoundsToZero {
+        field: &'static str,
+        value: Number,
+        precision: usize,
+    },
+    #[error("Could not load market metadata: {0}")]
+    Metadata(#[from] HttpError),
+}
+
+impl From<OrderValidationError> for HttpError {
+    fn from(e: OrderValidationError) -> Self {
+        match e {
+            OrderValidationError::Metadata(e) => e,
+            e => HttpError::InvalidRequest(e.to_string()),
+        }
+    }
+}
+
+/// Currencies and pairs as loaded at one point in time.
+#[derive(Clone, Debug)]
+pub struct MetadataSnapshot {
+    pub currencies: HashMap<String, Currency>,
+    pub currency_pairs: HashMap<String, CurrencyPair>,
+    pub asset_pairs: HashMap<String, AssetPair>,
+    pub loaded_at: Instant,
+}
+
+impl MetadataSnapshot {
+    /// Loads `currencies`, `currency_pairs` and `list_asset_pairs` concurrently.
+    pub async fn load(client: &Client) -> Result<Self, HttpError> {
+        let (currencies, currency_pairs, asset_pairs) = futures_util::try_join!(
+            client.currencies(),
+            client.currency_pairs(),
+            client.list_asset_pairs()
+        )?;
+
+        Ok(MetadataSnapshot {
+            currencies: currencies
+                .into_iter()
+                .map(|currency| (currency.symbol.to_lowercase(), currency))
+                .collect(),
+            currency_pairs,
+            asset_pairs,
+            loaded_at: Instant::now(),
+        })
+    }
+
+    pub fn currency(&self, symbol: &str) -> Option<&Currency> {
+        self.currencies.get(&symbol.to_lowercase())
+    }
+
+    /// The base and quote currency symbols of `pair`. Pairs only listed by `list_asset_pairs`
+    /// are split on the `/` of their formatted symbol.
+    pub fn pair_currencies(&self, pair: &Pair) -> Option<(String, String)> {
+        if let Some(pair) = self.currency_pairs.get(pair.as_str()) {
+            return Some((pair.base.to_lowercase(), pair.quote.to_lowercase()));
+        }
+
+        let asset_pair = self.asset_pairs.get(pair.as_str())?;
+        let (base, quote) = asset_pair.formatted_symbol.split_once('/')?;
+        Some((base.to_lowercase(), quote.to_lowercase()))
+    }
+
+    /// Checks that the order's pair is listed, both of its currencies can be traded and its
+    /// amounts fit the precision of their currency. Quantities use the precision of the base
+    /// currency and prices that of the quote currency.
+    ///
+    /// Returns the order as it should be sent, rounded when `policy` is
+    /// [`PrecisionPolicy::Round`].
+    pub fn validate_order(
+        &self,
+        order: &OrderRequest,
+        policy: PrecisionPolicy,
+    ) -> Result<OrderRequest, OrderValidationError> {
+        let pair = &order.body().currency_pair;
+        let (base, quote) = self
+            .pair_currencies(pair)
+            .ok_or_else(|| OrderValidationError::UnknownPair(pair.to_string()))?;
+        let base = self.tradable_currency(&base)?;
+        let quote = self.tradable_currency(&quote)?;
+
+        let price_rounding = match order.side() {
+            Side::Sell => Rounding::Up,
+            _ => Rounding::Down,
+        };
+
+        let mut order = order.clone();
+        let body = order.body_mut();
+        let fit = |field, value, currency: &Currency, rounding| {
+            fit_precision(field, value, currency.precision, rounding, policy)
+        };
+
+        body.quantity = fit("quantity", body.quantity, base, Rounding::Down)?;
+        body.display_quantity = body
+            .display_quantity
+            .map(|value| fit("display_quantity", value, base, Rounding::Down))
+            .transpose()?;
+        body.price = body
+            .price
+            .map(|value| fit("price", value, quote, price_rounding))
+            .transpose()?;
+        body.stop_amount = body
+            .stop_amount
+            .map(|value| fit("stop_amount", value, quote, Rounding::Nearest))
+            .transpose()?;
+        body.trailing_amount = body
+            .trailing_amount
+            .map(|value| fit("trailing_amount", value, quote, Rounding::Nearest))
+            .transpose()?;
+
+        Ok(order)
+    }
+
+    fn tradable_currency(&self, symbol: &str) -> Result<&Currency, OrderValidationError> {
+        let currency = self
+            .currency(symbol)
+            .ok_or_else(|| OrderValidationError::UnknownCurrency(symbol.to_string()))?;
+
+        if currency.trading_enabled {
+            Ok(currency)
+        } else {
+            Err(OrderValidationError::TradingDisabled(symbol.to_string()))
+        }
+    }
+}
+
+fn fit_precision(
+    field: &'static str,
+    value: Number,
+    precision: usize,
+    rounding: Rounding,
+    policy: PrecisionPolicy,
+) -> Result<Number, OrderValidationError> {
+    let places = precision as u32;
+    if num::has_precision(value, places) {
+        return Ok(value);
+    }
+
+    if policy == PrecisionPolicy::Reject {
+        return Err(OrderValidationError::Precision {
+            field,
+            value,
+            precision,
+        });
+    }
+
+    let rounded = num::round(value, places, rounding);
+    if rounded <= Number::default() {
+        return Err(OrderValidationError::RoundsToZero {
+            field,
+            value,
+            precision,
+        });
+    }
+
+    Ok(rounded)
+}
+
+/// Currency and pair metadata cached for a time to live, for validating orders before they are
+/// sent. Share it between tasks through an `Arc`; concurrent callers wait for a single reload.
+#[derive(Debug)]
+pub struct MarketMetadata {
+    client: Client,
+    ttl: Duration,
+    snapshot: Mutex<Option<Arc<MetadataSnapshot>>>,
+}
+
+impl MarketMetadata {
+    pub fn new(client: Client) -> Self {
+        MarketMetadata::with_ttl(client, DEFAULT_METADATA_TTL)
+    }
+
+    pub fn with_ttl(client: Client, ttl: Duration) -> Self {
+        MarketMetadata {
+            client,
+            ttl,
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// The cached snapshot, loading a new one first if there is none or it is older than the
+    /// time to live.
+    pub async fn snapshot(&self) -> Result<Arc<MetadataSnapshot>, HttpError> {
+        let mut snapshot = self.snapshot.lock().await;
+
+        match snapshot.as_ref() {
+            Some(current) if current.loaded_at.elapsed() < self.ttl => Ok(current.clone()),
+            _ => {
+                let loaded = Arc::new(MetadataSnapshot::load(&self.client).await?);
+                *snapshot = Some(loaded.clone());
+                Ok(loaded)
+            }
+        }
+    }
+
+    /// Loads a new snapshot regardless of the age of the cached one.
+    pub async fn refresh(&self) -> Result<Arc<MetadataSnapshot>, HttpError> {
+        let mut snapshot = self.snapshot.lock().await;
+
+        let loaded = Arc::new(MetadataSnapshot::load(&self.client).await?);
+        *snapshot = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    /// Validates `order` against the cached snapshot; see [`MetadataSnapshot::validate_order`].
+    pub async fn validate_order(
+        &self,
+        order: &OrderRequest,
+        policy: PrecisionPolicy,
+    ) -> Result<OrderRequest, OrderValidationError> {
+        self.snapshot().await?.validate_order(order, policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::num::number;
+    use crate::trading::Algorithm;
+    use crate::util::server::{new_test_server_and_client, ApiMock};
+
+    use super::super::HttpVerb;
+
+    const CURRENCIES_RESPONSE_BODY: &str = r#"
+        [
+            {
+                "id": 1, "symbol": "usd", "name": "US Dollar", "is_fiat": 1,
+                "is_lending_enabled": 0, "can_deposit": 1, "can_withdraw": 1,
+                "min_withdrawal": 10, "confirmations_needed": null, "precision": 2,
+                "ascii_sign": "$", "contract_address": null, "custody_enabled": 1,
+                "trading_enabled": 1, "primary_network": null, "code": "usd", "currency": "usd"
+            },
+            {
+                "id": 2, "symbol": "btc", "name": "Bitcoin", "is_fiat": 0,
+                "is_lending_enabled": 0, "can_deposit": 1, "can_withdraw": 1,
+                "min_withdrawal": 0.001, "confirmations_needed": 3, "precision": 8,
+                "ascii_sign": "B", "contract_address": null, "custody_enabled": 1,
+                "trading_enabled": 1, "primary_network": "Bitcoin", "code": "btc", "currency": "btc"
+            },
+            {
+                "id": 3, "symbol": "bch", "name": "Bitcoin Cash", "is_fiat": 0,
+                "is_lending_enabled": 0, "can_deposit": 1, "can_withdraw": 1,
+                "min_withdrawal": 0.001, "confirmations_needed": 3, "precision": 8,
+                "ascii_sign": "B", "contract_address": null, "custody_enabled": 1,
+                "trading_enabled": 0, "primary_network": null, "code": "bch", "currency": "bch"
+            }
+        ]
+    "#;
+
+    const CURRENCY_PAIRS_RESPONSE_BODY: &str = r#"
+        {
+            "btcusd": {"formatted_symbol": "BTC/USD", "symbol": "btcusd", "base": "btc", "quote": "usd"}
+        }
+    "#;
+
+    const ASSET_PAIRS_RESPONSE_BODY: &str = r#"
+        {
+            "btcusd": {"formatted_symbol": "BTC/USD", "symbol": "btcusd"},
+            "bchusd": {"formatted_symbol": "BCH/USD", "symbol": "bchusd"}
+        }
+    "#;
+
+    fn metadata_mocks() -> Vec<ApiMock> {
+        [
+            ("currency", CURRENCIES_RESPONSE_BODY),
+            ("markets/currency-pairs", CURRENCY_PAIRS_RESPONSE_BODY),
+            ("markets/currency_pairs", ASSET_PAIRS_RESPONSE_BODY),
+        ]
+        .into_iter()
+        .map(|(resource, body)| ApiMock {
+            action: HttpVerb::Get,
+            body: body.into(),
+            path: format!("/v1/{}", resource),
+            response_code: 200,
+        })
+        .collect()
+    }
+
+    fn limit_order(side: Side, pair: &str, quantity: &str, price: &str) -> OrderRequest {
+        OrderRequest::builder(side, pair, Algorithm::Limit, number(quantity))
+            .price(number(price))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_is_cached() {
+        let (client, _server, mock_results) = new_test_server_and_client(metadata_mocks()).await;
+        let metadata = MarketMetadata::new(client);
+
+        let first = metadata.snapshot().await.unwrap();
+        let second = metadata.snapshot().await.unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(
+            first.pair_currencies(&"bchusd".into()),
+            Some(("bch".into(), "usd".into()))
+        );
+
+        // Each resource was requested exactly once.
+        for mock in mock_results {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_expires() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mut mocks = vec![];
+        for mock in metadata_mocks() {
+            mocks.push(
+                server
+                    .mock("GET", mock.path.as_str())
+                    .with_body(mock.body)
+                    .expect(2)
+                    .create_async()
+                    .await,
+            );
+        }
+        let metadata = MarketMetadata::with_ttl(client, Duration::ZERO);
+
+        metadata.snapshot().await.unwrap();
+        metadata.snapshot().await.unwrap();
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_order() {
+        let (client, _server, _mocks) = new_test_server_and_client(metadata_mocks()).await;
+        let metadata = MarketMetadata::new(client);
+        let reject = PrecisionPolicy::Reject;
+
+        let order = limit_order(Side::Buy, "btcusd", "0.5", "25000.12");
+        let validated = metadata.validate_order(&order, reject).await.unwrap();
+        assert_eq!(validated.body().price, Some(number("25000.12")));
+
+        let unknown = limit_order(Side::Buy, "dogeusd", "1", "1");
+        assert!(matches!(
+            metadata.validate_order(&unknown, reject).await,
+            Err(OrderValidationError::UnknownPair(pair)) if pair == "dogeusd"
+        ));
+
+        let disabled = limit_order(Side::Buy, "bchusd", "1", "1");
+        assert!(matches!(
+            metadata.validate_order(&disabled, reject).await,
+            Err(OrderValidationError::TradingDisabled(symbol)) if symbol == "bch"
+        ));
+
+        let too_precise = limit_order(Side::Buy, "btcusd", "0.123456789", "25000");
+        assert!(matches!(
+            metadata.validate_order(&too_precise, reject).await,
+            Err(OrderValidationError::Precision {
+                field: "quantity",
+                precision: 8,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_order_rounds() {
+        let (client, _server, _mocks) = new_test_server_and_client(metadata_mocks()).await;
+        let metadata = MarketMetadata::new(client);
+        let round = PrecisionPolicy::Round;
+
+        let buy = limit_order(Side::Buy, "btcusd", "0.123456789", "25000.129");
+        let buy = metadata.validate_order(&buy, round).await.unwrap();
+        assert_eq!(buy.body().quantity, number("0.12345678"));
+        assert_eq!(buy.body().price, Some(number("25000.12")));
+
+        let sell = limit_order(Side::Sell, "btcusd", "1", "25000.121");
+        let sell = metadata.validate_order(&sell, round).await.unwrap();
+        assert_eq!(sell.body().price, Some(number("25000.13")));
+
+        let dust = limit_order(Side::Sell, "btcusd", "0.000000001", "25000");
+        assert!(matches!(
+            metadata.validate_order(&dust, round).await,
+            Err(OrderValidationError::RoundsToZero { .. })
+        ));
+    }
+}
@@ -1,17 +1,41 @@
-use std::collections::HashMap;
-use std::env;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 
 use futures_util::{Future, TryFutureExt};
-use reqwest::Response;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
 use serde::de::DeserializeOwned;
-use serde_derive::{Deserialize, Serialize};
-use serde_json::json;
+use serde::Serialize;
+use serde_derive::Deserialize;
+use serde_json::Value;
 use thiserror::Error;
 
+/// Streams changes to pending custody approval requests.
+pub mod approvals;
+mod builder;
 /// Candlestick chart data from the SFox markets.
 pub mod candlesticks;
+/// Cached currency and pair metadata for validating orders before they are sent.
+pub mod metadata;
+/// Client-side rate limiting shared between clones of the client.
+pub mod rate_limit;
+/// Retry policy for requests that fail with transient errors.
+pub mod retry;
+/// Request-for-quote lifecycle: refreshing quotes before they expire and checking them against
+/// the order book before executing them.
+pub mod rfq;
+/// Idempotent order submission that reconciles ambiguous failures by client order id.
+pub mod submission;
 /// Core API resources.
 pub mod v1;
+/// Guarded withdrawals: whitelist, minimum and fee checks producing a plan to confirm.
+pub mod withdrawal;
+
+pub use self::builder::ClientBuilder;
+use self::rate_limit::{RateLimitBucket, RateLimiter, DEFAULT_RATE_LIMIT_PAUSE};
+use self::retry::RetryPolicy;
+use crate::credentials::{CredentialProvider, EnvCredentials};
 
 /// Server URL for candlestick chart data.
 pub const DEFAULT_SERVER_URL: &str = "https://api.sfox.com";
@@ -19,34 +43,126 @@ pub const DEFAULT_SERVER_URL: &str = "https://api.sfox.com";
 pub const CANDLESTICK_SERVER_URL: &str = "https://chartdata.sfox.com";
 
 /// Error type for HTTP requests.
+///
+/// Non-2xx responses from the server are classified by status code: `401`/`403` become
+/// [`HttpError::Unauthorized`], `429` becomes [`HttpError::RateLimited`], `5xx` becomes
+/// [`HttpError::ServerError`] and every other status becomes [`HttpError::Api`]. Use
+/// [`HttpError::is_retryable`] to decide whether a failed call is worth repeating.
 #[derive(Clone, Error, Debug, Deserialize)]
 pub enum HttpError {
+    #[error("{method} {url} failed with status {status}: `{message}`")]
+    Api {
+        status: u16,
+        message: String,
+        body: String,
+        method: HttpVerb,
+        url: String,
+    },
     #[error("Authentication error: `{0}`")]
     AuthenticationError(String),
     #[error("Could not create http client: `{0}`")]
     InitializationError(String),
     #[error("Invalid request: `{0}`")]
     InvalidRequest(String),
+    #[error("{method} {url} was rate limited: `{message}`")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+        body: String,
+        method: HttpVerb,
+        url: String,
+    },
+    #[error("{method} {url} failed with server error {status}: `{message}`")]
+    ServerError {
+        status: u16,
+        message: String,
+        body: String,
+        method: HttpVerb,
+        url: String,
+    },
     #[error("Error while making request: `{0}`")]
     TransportError(String),
+    #[error("{method} {url} was not authorized ({status}): `{message}`")]
+    Unauthorized {
+        status: u16,
+        message: String,
+        body: String,
+        method: HttpVerb,
+        url: String,
+    },
     #[error("Could not deserialize response. Error: `{0}`, Response: `{1}`")]
     UnparseableResponse(String, String),
 }
 
+impl HttpError {
+    /// True when the failure is transient and the same request may succeed if sent again:
+    /// transport failures, rate limiting, server errors and request timeouts (`408`).
+    /// Validation and authentication failures are never retryable.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            HttpError::RateLimited { .. }
+            | HttpError::ServerError { .. }
+            | HttpError::TransportError(_) => true,
+            HttpError::Api { status, .. } => *status == StatusCode::REQUEST_TIMEOUT.as_u16(),
+            _ => false,
+        }
+    }
+
+    /// True when a failed request may still have been carried out by the server: the connection
+    /// broke, the server failed mid-request, the request timed out, or a successful response
+    /// could not be read. Use it to decide whether a failed order needs reconciling.
+    pub fn is_ambiguous(&self) -> bool {
+        match self {
+            HttpError::ServerError { .. }
+            | HttpError::TransportError(_)
+            | HttpError::UnparseableResponse(..) => true,
+            HttpError::Api { status, .. } => *status == StatusCode::REQUEST_TIMEOUT.as_u16(),
+            _ => false,
+        }
+    }
+
+    /// The HTTP status returned by the server, if the error came from a response.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            HttpError::Api { status, .. }
+            | HttpError::ServerError { status, .. }
+            | HttpError::Unauthorized { status, .. } => Some(*status),
+            HttpError::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS.as_u16()),
+            _ => None,
+        }
+    }
+
+    /// How long the server asked us to wait before retrying, from the `Retry-After` header.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            HttpError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
 /// Offers an asynchronous API that models the HTTP resources of the SFox API. Server and authentication are
 /// managed internally.
+///
+/// The API key is obtained from the [`CredentialProvider`] before every request and never appears
+/// in `Debug` output.
 #[derive(Clone, Debug, Deserialize)]
 pub struct Client {
-    #[serde(skip)]
-    pub auth_token: String,
+    #[serde(skip, default = "default_credentials")]
+    pub credentials: Arc<dyn CredentialProvider>,
     pub candlestick_server_url: String,
     #[serde(skip)]
     pub http_client: reqwest::Client,
+    /// Optional limiter shared by every clone of this client.
+    #[serde(skip)]
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    #[serde(skip)]
+    pub retry_policy: RetryPolicy,
     pub server_url: String,
 }
 
 /// GET, POST, PATCH, DELETE
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum HttpVerb {
     Get,
     Post,
@@ -65,6 +181,12 @@ impl From<HttpVerb> for &str {
     }
 }
 
+impl fmt::Display for HttpVerb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str((*self).into())
+    }
+}
+
 /// Builds a new client with the default server URL.
 pub fn new() -> Result<Client, HttpError> {
     Client::new()
@@ -73,13 +195,18 @@ pub fn new() -> Result<Client, HttpError> {
 impl Client {
     /// Returns a new client with the default server URL.
     pub fn new() -> Result<Client, HttpError> {
-        let candlestick_server_url = env::var("CANDLESTICK_SERVER_URL")
-            .unwrap_or_else(|_| CANDLESTICK_SERVER_URL.to_string());
+        ClientBuilder::new().build()
+    }
 
-        let server_url =
-            env::var("SFOX_SERVER_URL").unwrap_or_else(|_| DEFAULT_SERVER_URL.to_string());
+    /// Starts configuring a client with explicit credentials, servers and transport settings.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
 
-        build_server(candlestick_server_url, server_url)
+    /// Builds a client from a loaded [`SfoxConfig`](crate::config::SfoxConfig). Without an API key
+    /// in the config, the key is read from the environment as in [`Client::new`].
+    pub fn from_config(config: &crate::config::SfoxConfig) -> Result<Client, HttpError> {
+        ClientBuilder::from_config(config).build()
     }
 
     /// Builds a new client with the given server URL; useful for testing.
@@ -87,28 +214,81 @@ impl Client {
         server_url: String,
         candlestick_server_url: String,
     ) -> Result<Client, HttpError> {
-        build_server(candlestick_server_url, server_url)
+        ClientBuilder::new()
+            .server_url(server_url)
+            .candlestick_server_url(candlestick_server_url)
+            .build()
     }
 
     fn request<T>(
-        self,
+        &self,
         verb: HttpVerb,
         resource: &str,
-        req_body: Option<&HashMap<String, String>>,
-    ) -> impl Future<Output = Result<T, HttpError>>
+        req_body: Option<Value>,
+    ) -> impl Future<Output = Result<T, HttpError>> + Send + 'static
     where
         T: Clone + DeserializeOwned + Send + 'static,
     {
         self.send_request(verb, resource, req_body)
-            .and_then(|response| async move { parse_response(response).await })
+            .and_then(move |response| async move { parse_response(verb, response).await })
+    }
+
+    /// Sends `body` serialized as JSON. Serialization failures resolve to
+    /// [`HttpError::InvalidRequest`] without sending anything.
+    fn request_with_body<T, B>(
+        &self,
+        verb: HttpVerb,
+        resource: &str,
+        body: &B,
+    ) -> impl Future<Output = Result<T, HttpError>> + Send + 'static
+    where
+        T: Clone + DeserializeOwned + Send + 'static,
+        B: Serialize + ?Sized,
+    {
+        let request = serde_json::to_value(body)
+            .map(|body| self.request(verb, resource, Some(body)))
+            .map_err(|e| HttpError::InvalidRequest(e.to_string()));
+
+        async move { request?.await }
+    }
+
+    /// Sends the request with `query` appended to `resource` as a URL-encoded query string.
+    /// See [`with_query`] for how fields are encoded.
+    fn request_with_query<T, Q>(
+        &self,
+        verb: HttpVerb,
+        resource: &str,
+        query: &Q,
+    ) -> impl Future<Output = Result<T, HttpError>> + Send + 'static
+    where
+        T: Clone + DeserializeOwned + Send + 'static,
+        Q: Serialize + ?Sized,
+    {
+        let request = with_query(resource, query).map(|url| self.request(verb, &url, None));
+
+        async move { request?.await }
+    }
+
+    fn request_text_with_query<Q>(
+        &self,
+        verb: HttpVerb,
+        resource: &str,
+        query: &Q,
+    ) -> impl Future<Output = Result<String, HttpError>> + Send + 'static
+    where
+        Q: Serialize + ?Sized,
+    {
+        let request = with_query(resource, query).map(|url| self.request_text(verb, &url, None));
+
+        async move { request?.await }
     }
 
     fn request_text(
-        self,
+        &self,
         verb: HttpVerb,
         resource: &str,
-        req_body: Option<&HashMap<String, String>>,
-    ) -> impl Future<Output = Result<String, HttpError>> {
+        req_body: Option<Value>,
+    ) -> impl Future<Output = Result<String, HttpError>> + Send + 'static {
         self.send_request(verb, resource, req_body)
             .and_then(|response| async {
                 response
@@ -118,28 +298,89 @@ impl Client {
             })
     }
 
+    /// Sends the request, retrying transient failures according to the client's
+    /// [`RetryPolicy`] and waiting on the [`RateLimiter`], if any, before every attempt.
+    /// Resolves to a response with a successful status.
+    ///
+    /// The returned future owns a clone of the client, so endpoint methods can borrow `self`
+    /// and still hand out `Send + 'static` futures that outlive the borrow.
     fn send_request(
-        self,
+        &self,
         verb: HttpVerb,
         resource: &str,
-        req_body: Option<&HashMap<String, String>>,
-    ) -> impl Future<Output = Result<Response, HttpError>> {
-        let auth_token = self.auth_token.clone();
-
-        let base_response = self.action(verb.clone(), resource).bearer_auth(auth_token);
+        req_body: Option<Value>,
+    ) -> impl Future<Output = Result<Response, HttpError>> + Send + 'static {
+        let client = self.clone();
+        let url = resource.to_string();
+        let req_body = req_body.filter(|_| Self::has_request_body(verb));
+
+        async move {
+            let retry = client.retry_policy.allows(verb, &url);
+            let bucket = RateLimitBucket::for_url(&url, &client.candlestick_server_url);
+            let mut attempt = 1;
+
+            loop {
+                if let Some(limiter) = &client.rate_limiter {
+                    limiter.acquire(bucket).await;
+                }
+
+                let error = match client.send_once(verb, &url, req_body.as_ref()).await {
+                    Err(e) => e,
+                    result => return result,
+                };
+                let delay = match retry && client.retry_policy.should_retry(attempt, &error) {
+                    true => Some(client.retry_policy.delay(attempt, &error)),
+                    false => None,
+                };
+
+                match (&client.rate_limiter, &error) {
+                    // The retry waits out the pause in `acquire`, so the pause covers the
+                    // retry delay too instead of the two adding up.
+                    (Some(limiter), HttpError::RateLimited { retry_after, .. }) => {
+                        let pause = retry_after.unwrap_or(DEFAULT_RATE_LIMIT_PAUSE);
+                        limiter.pause(bucket, pause.max(delay.unwrap_or_default()));
+                    }
+                    _ => {
+                        if let Some(delay) = delay {
+                            tokio::time::sleep(delay).await;
+                        }
+                    }
+                }
+
+                match delay {
+                    Some(_) => attempt += 1,
+                    None => return Err(error),
+                }
+            }
+        }
+    }
 
-        let request = if Self::has_request_body(verb, &req_body) {
-            base_response.json(req_body.unwrap())
-        } else {
-            base_response
+    async fn send_once(
+        &self,
+        verb: HttpVerb,
+        url: &str,
+        req_body: Option<&Value>,
+    ) -> Result<Response, HttpError> {
+        let api_key = self
+            .credentials
+            .api_key()
+            .map_err(|e| HttpError::AuthenticationError(e.to_string()))?;
+        let base_request = self.action(verb, url).bearer_auth(api_key.expose_secret());
+
+        let request = match req_body {
+            Some(body) => base_request.json(body),
+            None => base_request,
         };
 
-        request
+        let response = request
             .send()
-            .map_err(|e| HttpError::TransportError(e.to_string()))
+            .await
+            .map_err(|e| HttpError::TransportError(e.to_string()))?;
+
+        check_status(verb, response).await
     }
 
-    fn action(self, verb: HttpVerb, resource_path: &str) -> reqwest::RequestBuilder {
+    fn action(&self, verb: HttpVerb, resource_path: &str) -> reqwest::RequestBuilder {
         let c = &self.http_client;
         match verb {
             HttpVerb::Get => c.get(resource_path),
@@ -149,10 +390,10 @@ impl Client {
         }
     }
 
-    fn has_request_body(verb: HttpVerb, req_body: &Option<&HashMap<String, String>>) -> bool {
+    fn has_request_body(verb: HttpVerb) -> bool {
         match verb {
             HttpVerb::Get | HttpVerb::Delete => false,
-            HttpVerb::Post | HttpVerb::Patch => req_body.is_some(),
+            HttpVerb::Post | HttpVerb::Patch => true,
         }
     }
 
@@ -165,43 +406,32 @@ impl Client {
     }
 }
 
-fn build_server(candlestick_server_url: String, server_url: String) -> Result<Client, HttpError> {
-    let http_client = reqwest::Client::builder()
-        .build()
-        .map_err(|e| HttpError::InitializationError(e.to_string()))?;
-
-    let auth_token = env::var("SFOX_AUTH_TOKEN").map_err(|_| {
-        HttpError::InitializationError("SFOX_AUTH_TOKEN env variable not set.".to_string())
-    })?;
-
-    Ok(Client {
-        auth_token,
-        candlestick_server_url,
-        http_client,
-        server_url,
-    })
+fn default_credentials() -> Arc<dyn CredentialProvider> {
+    Arc::new(EnvCredentials::default())
 }
 
-async fn parse_response<T>(response: reqwest::Response) -> Result<T, HttpError>
+/// Appends `query` to `url` as a URL-encoded query string, in field order. `None` fields are
+/// left out entirely and an empty query adds nothing, not even the `?`.
+fn with_query<Q>(url: &str, query: &Q) -> Result<String, HttpError>
 where
-    T: Clone + DeserializeOwned + Send + 'static,
+    Q: Serialize + ?Sized,
 {
-    if !response.status().is_success() {
-        let error_text = response
-            .json()
-            .await
-            .unwrap_or(json!("{\"error\": \"Could not parse response\"}"));
-        let error = error_text.get("error");
-        match error {
-            Some(error) => {
-                return Err(HttpError::TransportError(error.to_string()));
-            }
-            None => {
-                return Err(HttpError::TransportError(error_text.to_string()));
-            }
-        }
+    let query =
+        serde_urlencoded::to_string(query).map_err(|e| HttpError::InvalidRequest(e.to_string()))?;
+
+    if query.is_empty() {
+        return Ok(url.to_string());
     }
 
+    Ok(format!("{}?{}", url, query))
+}
+
+async fn parse_response<T>(verb: HttpVerb, response: Response) -> Result<T, HttpError>
+where
+    T: Clone + DeserializeOwned + Send + 'static,
+{
+    let response = check_status(verb, response).await?;
+
     let text: String = match response.text().await {
         Ok(text) => text,
         Err(e) => return Err(HttpError::TransportError(e.to_string())),
@@ -213,22 +443,149 @@ where
     }
 }
 
+/// Passes successful responses through and converts everything else into a classified error.
+async fn check_status(verb: HttpVerb, response: Response) -> Result<Response, HttpError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    Err(error_from_response(verb, response).await)
+}
+
+async fn error_from_response(method: HttpVerb, response: Response) -> HttpError {
+    let status = response.status();
+    let url = response.url().to_string();
+    let retry_after = parse_retry_after(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    let message = error_message(status, &body);
+
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => HttpError::Unauthorized {
+            status: status.as_u16(),
+            message,
+            body,
+            method,
+            url,
+        },
+        StatusCode::TOO_MANY_REQUESTS => HttpError::RateLimited {
+            retry_after,
+            message,
+            body,
+            method,
+            url,
+        },
+        s if s.is_server_error() => HttpError::ServerError {
+            status: status.as_u16(),
+            message,
+            body,
+            method,
+            url,
+        },
+        _ => HttpError::Api {
+            status: status.as_u16(),
+            message,
+            body,
+            method,
+            url,
+        },
+    }
+}
+
+/// SFox reports failures as `{"error": "..."}`; fall back to the raw body, then the status text.
+fn error_message(status: StatusCode, body: &str) -> String {
+    let message = match serde_json::from_str::<Value>(body) {
+        Ok(Value::Object(map)) => map
+            .get("error")
+            .or_else(|| map.get("message"))
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        Ok(Value::String(s)) => Some(s),
+        _ => None,
+    };
+
+    match message {
+        Some(message) => message,
+        None if !body.trim().is_empty() => body.trim().to_string(),
+        None => status
+            .canonical_reason()
+            .unwrap_or("Unknown error")
+            .to_string(),
+    }
+}
+
+/// Only the delay-seconds form of `Retry-After` is used by the SFox API.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
 #[cfg(test)]
 mod tests {
+    use futures_util::Future;
     use tokio_tungstenite::tungstenite::http;
 
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
     use crate::{
-        http::{parse_response, Client, HttpError, DEFAULT_SERVER_URL},
+        http::{
+            parse_response,
+            rate_limit::{Budget, RateLimiter},
+            retry::RetryPolicy,
+            Client, HttpError, HttpVerb, DEFAULT_SERVER_URL,
+        },
+        num::number,
+        trading::{Algorithm, RoutingType, Side},
+        util::server::{new_test_server_and_client, ApiMock},
         util::set_test_env,
     };
 
+    fn assert_send_static<F: Future + Send + 'static>(_future: F) {}
+
+    /// Endpoint futures outlive the borrow of the client, so a client held behind a reference
+    /// can hand them to `tokio::spawn`.
+    #[tokio::test]
+    async fn test_endpoint_futures_are_send_and_static() {
+        struct Holder<'a> {
+            client: &'a Client,
+        }
+
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("GET", "/v1/orders")
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let holder = Holder { client: &client };
+        let handle = tokio::spawn(holder.client.open_orders());
+        assert!(handle.await.unwrap().is_ok());
+        mock.assert_async().await;
+
+        let pair = String::from("btcusd");
+        assert_send_static(client.order_book(&pair));
+        assert_send_static(client.request_for_quote(&pair, Side::Buy, None, None, None));
+        assert_send_static(client.loan_positions(Some("active".into())));
+        assert_send_static(client.transaction_history(None, None, None, None, None));
+        assert_send_static(client.candlesticks(&pair, 0, 60, 60));
+    }
+
     #[test]
     fn test_client_initialization() {
         set_test_env();
         let client = Client::new().unwrap();
 
         // Assert auth token was set correctly
-        assert_eq!(client.auth_token, "secret");
+        assert_eq!(
+            client.credentials.api_key().unwrap().expose_secret(),
+            "secret"
+        );
         assert_eq!(client.server_url, DEFAULT_SERVER_URL);
     }
 
@@ -253,12 +610,12 @@ mod tests {
             .body("\"response body\"".to_string())
             .unwrap();
 
-        let parsed: String = parse_response(resp.into()).await.unwrap();
+        let parsed: String = parse_response(HttpVerb::Get, resp.into()).await.unwrap();
         assert!(!parsed.is_empty())
     }
 
     #[tokio::test]
-    async fn test_parse_response_transport_error() {
+    async fn test_parse_response_api_error() {
         // Create a failed response
         let resp = http::Response::builder()
             .status(400)
@@ -267,10 +624,335 @@ mod tests {
             .unwrap();
 
         // Try to parse failed response
-        let result = parse_response::<String>(resp.into()).await;
+        let result = parse_response::<String>(HttpVerb::Get, resp.into()).await;
 
         // Assert error returned
-        assert!(matches!(result, Err(HttpError::TransportError(_))));
+        match result {
+            Err(HttpError::Api {
+                status, message, ..
+            }) => {
+                assert_eq!(status, 400);
+                assert_eq!(message, "response body");
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_response_rate_limited() {
+        let resp = http::Response::builder()
+            .status(429)
+            .header("Retry-After", "3")
+            .body("{\"error\": \"too many requests\"}")
+            .unwrap();
+
+        let err = parse_response::<String>(HttpVerb::Get, resp.into())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, HttpError::RateLimited { .. }));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(3)));
+        assert_eq!(err.status(), Some(429));
+        assert!(err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_parse_response_server_error() {
+        let resp = http::Response::builder().status(503).body("").unwrap();
+
+        let err = parse_response::<String>(HttpVerb::Get, resp.into())
+            .await
+            .unwrap_err();
+
+        match &err {
+            HttpError::ServerError {
+                status, message, ..
+            } => {
+                assert_eq!(*status, 503);
+                assert_eq!(message, "Service Unavailable");
+            }
+            other => panic!("expected a server error, got {:?}", other),
+        }
+        assert!(err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_error_includes_method_and_url() {
+        let mock = ApiMock {
+            action: HttpVerb::Post,
+            body: r#"{"error": "invalid token. check authorization header."}"#.into(),
+            path: "/v1/orders/buy".into(),
+            response_code: 401,
+        };
+
+        let (client, _server, _mock_results) = new_test_server_and_client(vec![mock]).await;
+        let url = client.url_for_v1_resource("orders/buy");
+
+        let err = client
+            .request::<String>(HttpVerb::Post, &url, None)
+            .await
+            .unwrap_err();
+
+        match &err {
+            HttpError::Unauthorized {
+                status,
+                message,
+                method,
+                url: error_url,
+                ..
+            } => {
+                assert_eq!(*status, 401);
+                assert_eq!(message, "invalid token. check authorization header.");
+                assert_eq!(*method, HttpVerb::Post);
+                assert_eq!(error_url, &url);
+            }
+            other => panic!("expected an authorization error, got {:?}", other),
+        }
+        assert!(!err.is_retryable());
+    }
+
+    fn fast_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_retries_transient_failures() {
+        let (mut client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        client.retry_policy = fast_retry_policy();
+
+        let failures = server
+            .mock("GET", "/v1/user/balance")
+            .with_status(503)
+            .expect(2)
+            .create_async()
+            .await;
+        let success = server
+            .mock("GET", "/v1/user/balance")
+            .with_status(200)
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let result = client.account_balance().await;
+
+        assert!(result.is_ok());
+        failures.assert_async().await;
+        success.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_get_gives_up_after_max_attempts() {
+        let (mut client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        client.retry_policy = fast_retry_policy();
+
+        let failures = server
+            .mock("GET", "/v1/user/balance")
+            .with_status(502)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let result = client.account_balance().await;
+
+        assert!(matches!(
+            result,
+            Err(HttpError::ServerError { status: 502, .. })
+        ));
+        failures.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let (mut client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        client.retry_policy = fast_retry_policy();
+
+        let failure = server
+            .mock("GET", "/v1/user/balance")
+            .with_status(422)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let result = client.account_balance().await;
+
+        assert!(matches!(result, Err(HttpError::Api { status: 422, .. })));
+        failure.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_place_order_is_never_retried() {
+        let (mut client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        client.retry_policy = RetryPolicy {
+            verbs: vec![HttpVerb::Get, HttpVerb::Post],
+            ..fast_retry_policy()
+        };
+
+        let failure = server
+            .mock("POST", "/v1/orders/buy")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let result = client
+            .place_order(
+                Side::Buy,
+                "btcusd",
+                number("1.0"),
+                number("1.0"),
+                RoutingType::NetPrice,
+                Algorithm::Smart,
+                None,
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(HttpError::ServerError { status: 503, .. })
+        ));
+        failure.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_stake_is_never_retried() {
+        let (mut client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        client.retry_policy = RetryPolicy {
+            verbs: vec![HttpVerb::Get, HttpVerb::Post],
+            ..fast_retry_policy()
+        };
+
+        let failure = server
+            .mock("POST", "/v1/staking/stake")
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let result = client.stake("eth".to_string(), number("1.0")).await;
+
+        assert!(matches!(
+            result,
+            Err(HttpError::ServerError { status: 503, .. })
+        ));
+        failure.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_is_shared_between_clones() {
+        let (mut client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        client.rate_limiter = Some(Arc::new(RateLimiter::new(
+            Budget {
+                requests: 1,
+                per: Duration::from_millis(300),
+            },
+            Budget::per_second(100),
+        )));
+
+        let orders = server
+            .mock("GET", "/v1/orders")
+            .with_body("[]")
+            .expect(2)
+            .create_async()
+            .await;
+        let currencies = server
+            .mock("GET", "/v1/currency")
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let start = Instant::now();
+        client.clone().open_orders().await.unwrap();
+        client.clone().currencies().await.unwrap();
+        // The first clone took the only trading token, so this one waits for a refill.
+        client.clone().open_orders().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(250));
+
+        orders.assert_async().await;
+        currencies.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_response_pauses_bucket() {
+        let (mut client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        client.retry_policy = RetryPolicy::none();
+        client.rate_limiter = Some(Arc::new(RateLimiter::new(
+            Budget::per_second(100),
+            Budget::per_second(100),
+        )));
+
+        let limited = server
+            .mock("GET", "/v1/orders")
+            .with_status(429)
+            .with_header("Retry-After", "1")
+            .with_body(r#"{"error": "rate limit exceeded"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("GET", "/v1/orders")
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let result = client.open_orders().await;
+        assert!(matches!(result, Err(HttpError::RateLimited { .. })));
+
+        let start = Instant::now();
+        client.open_orders().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(900));
+
+        limited.assert_async().await;
+        ok.assert_async().await;
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let api_error = |status| HttpError::Api {
+            status,
+            message: String::new(),
+            body: String::new(),
+            method: HttpVerb::Get,
+            url: String::new(),
+        };
+
+        assert!(!api_error(400).is_retryable());
+        assert!(!api_error(422).is_retryable());
+        assert!(api_error(408).is_retryable());
+        assert!(HttpError::TransportError("connection reset".into()).is_retryable());
+        assert!(!HttpError::InvalidRequest("bad".into()).is_retryable());
+        assert!(!HttpError::UnparseableResponse("bad".into(), String::new()).is_retryable());
+    }
+
+    #[test]
+    fn test_is_ambiguous() {
+        let api_error = |status| HttpError::Api {
+            status,
+            message: String::new(),
+            body: String::new(),
+            method: HttpVerb::Post,
+            url: String::new(),
+        };
+
+        assert!(api_error(408).is_ambiguous());
+        assert!(!api_error(400).is_ambiguous());
+        assert!(HttpError::TransportError("timed out".into()).is_ambiguous());
+        assert!(HttpError::UnparseableResponse("bad".into(), String::new()).is_ambiguous());
+        assert!(!HttpError::InvalidRequest("bad".into()).is_ambiguous());
+        assert!(!HttpError::RateLimited {
+            retry_after: None,
+            message: String::new(),
+            body: String::new(),
+            method: HttpVerb::Post,
+            url: String::new(),
+        }
+        .is_ambiguous());
     }
 
     #[test]
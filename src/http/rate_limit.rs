@@ -0,0 +1,270 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// V1 resources that serve public market data rather than account or trading actions.
+const MARKET_DATA_RESOURCES: [&str; 3] = ["analytics/", "currency", "markets/"];
+
+/// How long to stop sending when the server answers `429` without a `Retry-After` header.
+pub const DEFAULT_RATE_LIMIT_PAUSE: Duration = Duration::from_secs(1);
+
+/// The budget a request draws its token from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RateLimitBucket {
+    /// Candlesticks, order books, currencies and volume analytics.
+    MarketData,
+    /// Orders, balances, transfers and every other account resource.
+    Trading,
+}
+
+impl RateLimitBucket {
+    /// Classifies a request URL built by the client.
+    pub fn for_url(url: &str, candlestick_server_url: &str) -> Self {
+        let is_candlestick = matches!(
+            url.strip_prefix(candlestick_server_url),
+            Some(path) if path.starts_with("/candlesticks")
+        );
+        if is_candlestick {
+            return RateLimitBucket::MarketData;
+        }
+
+        let resource = url.split("/v1/").nth(1).unwrap_or_default();
+        if MARKET_DATA_RESOURCES
+            .iter()
+            .any(|prefix| resource.starts_with(prefix))
+        {
+            RateLimitBucket::MarketData
+        } else {
+            RateLimitBucket::Trading
+        }
+    }
+}
+
+/// Number of requests allowed over a period. Requests may burst up to `requests` at once.
+#[derive(Clone, Copy, Debug)]
+pub struct Budget {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl Budget {
+    pub fn per_second(requests: u32) -> Self {
+        Budget {
+            requests,
+            per: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Client-side token bucket limiter with separate budgets for trading and market data requests.
+///
+/// The limiter is shared between clones of [`super::Client`] through an `Arc`, so every task
+/// using the same client draws from the same budgets. When the server responds with `429`, the
+/// affected bucket is paused for the duration of its `Retry-After` header.
+#[derive(Debug)]
+pub struct RateLimiter {
+    market_data: Mutex<TokenBucket>,
+    trading: Mutex<TokenBucket>,
+}
+
+impl Default for RateLimiter {
+    /// Conservative budgets; tune them to the limits of your account.
+    fn default() -> Self {
+        RateLimiter::new(Budget::per_second(5), Budget::per_second(10))
+    }
+}
+
+impl RateLimiter {
+    pub fn new(trading: Budget, market_data: Budget) -> Self {
+        RateLimiter {
+            market_data: Mutex::new(TokenBucket::new(market_data)),
+            trading: Mutex::new(TokenBucket::new(trading)),
+        }
+    }
+
+    /// Waits until a token is available in the bucket and takes it.
+    pub async fn acquire(&self, bucket: RateLimitBucket) {
+        loop {
+            let wait = match self.bucket(bucket).lock() {
+                Ok(mut b) => b.try_take(Instant::now()),
+                // A poisoned bucket only means another task panicked mid-update; don't block.
+                Err(_) => return,
+            };
+
+            match wait {
+                None => return,
+                Some(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Stops handing out tokens from the bucket for the given duration.
+    pub fn pause(&self, bucket: RateLimitBucket, duration: Duration) {
+        if let Ok(mut b) = self.bucket(bucket).lock() {
+            b.pause_until(Instant::now() + duration);
+        }
+    }
+
+    fn bucket(&self, bucket: RateLimitBucket) -> &Mutex<TokenBucket> {
+        match bucket {
+            RateLimitBucket::MarketData => &self.market_data,
+            RateLimitBucket::Trading => &self.trading,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new(budget: Budget) -> Self {
+        let capacity = f64::from(budget.requests.max(1));
+        let per = budget.per.as_secs_f64().max(f64::EPSILON);
+
+        TokenBucket {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / per,
+            last_refill: Instant::now(),
+            paused_until: None,
+        }
+    }
+
+    /// Takes a token, or returns how long to wait before trying again.
+    fn try_take(&mut self, now: Instant) -> Option<Duration> {
+        if let Some(until) = self.paused_until {
+            if until > now {
+                return Some(until - now);
+            }
+            self.paused_until = None;
+        }
+
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                (1.0 - self.tokens) / self.refill_per_sec,
+            ))
+        }
+    }
+
+    fn pause_until(&mut self, until: Instant) {
+        if !matches!(self.paused_until, Some(current) if current >= until) {
+            self.paused_until = Some(until);
+        }
+        // Restart with a single token, so the request that waited out the pause goes as it ends
+        // but isn't followed by a full burst.
+        self.tokens = 1.0;
+        self.last_refill = until;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_for_url() {
+        let candlesticks = "https://chartdata.sfox.com";
+
+        assert_eq!(
+            RateLimitBucket::for_url(
+                "https://chartdata.sfox.com/candlesticks?pair=btcusd",
+                candlesticks
+            ),
+            RateLimitBucket::MarketData
+        );
+        assert_eq!(
+            RateLimitBucket::for_url(
+                "https://api.sfox.com/v1/markets/orderbook/btcusd",
+                candlesticks
+            ),
+            RateLimitBucket::MarketData
+        );
+        assert_eq!(
+            RateLimitBucket::for_url("https://api.sfox.com/v1/currency", candlesticks),
+            RateLimitBucket::MarketData
+        );
+        assert_eq!(
+            RateLimitBucket::for_url("https://api.sfox.com/v1/orders/123", candlesticks),
+            RateLimitBucket::Trading
+        );
+        assert_eq!(
+            RateLimitBucket::for_url("https://api.sfox.com/v1/user/balance", candlesticks),
+            RateLimitBucket::Trading
+        );
+
+        // Both servers may share a host, e.g. behind a local mock.
+        assert_eq!(
+            RateLimitBucket::for_url("http://localhost:4000/v1/orders", "http://localhost:4000"),
+            RateLimitBucket::Trading
+        );
+    }
+
+    #[test]
+    fn test_buckets_are_separate() {
+        let limiter = RateLimiter::new(
+            Budget {
+                requests: 1,
+                per: Duration::from_secs(3600),
+            },
+            Budget::per_second(2),
+        );
+        let now = Instant::now();
+        let take = |bucket| limiter.bucket(bucket).lock().unwrap().try_take(now);
+
+        assert!(take(RateLimitBucket::Trading).is_none());
+        assert!(take(RateLimitBucket::Trading).is_some());
+        // Market data is not held up by the exhausted trading bucket.
+        assert!(take(RateLimitBucket::MarketData).is_none());
+        assert!(take(RateLimitBucket::MarketData).is_none());
+
+        limiter.pause(RateLimitBucket::MarketData, Duration::from_secs(60));
+        assert!(take(RateLimitBucket::MarketData).is_some());
+    }
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let mut bucket = TokenBucket::new(Budget {
+            requests: 2,
+            per: Duration::from_secs(1),
+        });
+        let start = bucket.last_refill;
+
+        assert!(bucket.try_take(start).is_none());
+        assert!(bucket.try_take(start).is_none());
+
+        let wait = bucket.try_take(start).unwrap();
+        assert_eq!(wait, Duration::from_millis(500));
+
+        assert!(bucket.try_take(start + wait).is_none());
+    }
+
+    #[test]
+    fn test_token_bucket_pause() {
+        let mut bucket = TokenBucket::new(Budget::per_second(10));
+        let start = bucket.last_refill;
+
+        bucket.pause_until(start + Duration::from_secs(2));
+
+        assert_eq!(bucket.try_take(start), Some(Duration::from_secs(2)));
+        // One request goes as soon as the pause ends, without waiting for a refill.
+        assert!(bucket.try_take(start + Duration::from_secs(2)).is_none());
+        let wait = bucket.try_take(start + Duration::from_secs(2)).unwrap();
+        assert_eq!(wait, Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::{HttpError, HttpVerb};
+
+/// Resources whose `POST`, `PATCH` and `DELETE` requests can safely be sent twice: requesting a
+/// quote has no side effect beyond the quote itself.
+pub const IDEMPOTENT_RESOURCES: [&str; 1] = ["quote"];
+
+/// Controls how the client retries requests that fail with a transient error
+/// (see [`HttpError::is_retryable`]).
+///
+/// Only verbs listed in `verbs` are retried. `GET` requests are retried for every resource, but
+/// `POST`, `PATCH` and `DELETE` requests only for resources listed in `retryable_resources`,
+/// because a timed-out order, withdrawal, transfer or stake may have gone through. The default
+/// policy retries `GET` requests up to three times with exponential backoff and full jitter.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled on every subsequent retry.
+    pub base_delay: Duration,
+    /// Upper bound for a single delay, including any `Retry-After` requested by the server.
+    pub max_delay: Duration,
+    /// Randomize each delay between zero and the computed backoff.
+    pub jitter: bool,
+    /// Verbs that are safe to send more than once.
+    pub verbs: Vec<HttpVerb>,
+    /// V1 resource paths (e.g. `"quote"`) whose `POST`, `PATCH` and `DELETE` requests may be
+    /// retried.
+    pub retryable_resources: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            verbs: vec![HttpVerb::Get],
+            retryable_resources: IDEMPOTENT_RESOURCES.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that sends every request exactly once.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a request with this verb and URL may be sent more than once.
+    pub fn allows(&self, verb: HttpVerb, url: &str) -> bool {
+        if self.max_attempts <= 1 || !self.verbs.contains(&verb) {
+            return false;
+        }
+        if verb == HttpVerb::Get {
+            return true;
+        }
+
+        let path = url.split('?').next().unwrap_or(url);
+        self.retryable_resources
+            .iter()
+            .any(|resource| path.ends_with(&format!("/v1/{}", resource)))
+    }
+
+    /// Whether a failed attempt should be followed by another one.
+    pub fn should_retry(&self, attempt: u32, error: &HttpError) -> bool {
+        attempt < self.max_attempts && error.is_retryable()
+    }
+
+    /// How long to wait after the given (1-based) failed attempt. A `Retry-After` sent by the
+    /// server takes precedence over the computed backoff.
+    pub fn delay(&self, attempt: u32, error: &HttpError) -> Duration {
+        if let Some(retry_after) = error.retry_after() {
+            return retry_after.min(self.max_delay);
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        if self.jitter && !backoff.is_zero() {
+            rand::thread_rng().gen_range(Duration::ZERO..=backoff)
+        } else {
+            backoff
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error() -> HttpError {
+        HttpError::ServerError {
+            status: 503,
+            message: String::new(),
+            body: String::new(),
+            method: HttpVerb::Get,
+            url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_allows() {
+        let policy = RetryPolicy::default();
+
+        assert!(policy.allows(HttpVerb::Get, "https://api.sfox.com/v1/orders"));
+        assert!(policy.allows(HttpVerb::Get, "https://api.sfox.com/v1/orders/123"));
+        assert!(!policy.allows(HttpVerb::Post, "https://api.sfox.com/v1/orders/buy"));
+        assert!(!policy.allows(HttpVerb::Delete, "https://api.sfox.com/v1/orders/open"));
+
+        let policy = RetryPolicy {
+            verbs: vec![HttpVerb::Get, HttpVerb::Post],
+            ..Default::default()
+        };
+
+        assert!(policy.allows(HttpVerb::Post, "https://api.sfox.com/v1/quote"));
+        assert!(policy.allows(HttpVerb::Get, "https://api.sfox.com/v1/orders/buy"));
+        for resource in [
+            "orders/buy",
+            "orders/sell",
+            "user/withdraw",
+            "staking/stake",
+            "staking/unstake",
+            "account/transfer",
+            "user/bank/deposit",
+            "user/deposit/address/btc",
+            "whitelisted-addresses",
+            "approval-rules",
+            "approvals/12",
+        ] {
+            let url = format!("https://api.sfox.com/v1/{}", resource);
+            assert!(!policy.allows(HttpVerb::Post, &url), "{}", resource);
+        }
+
+        let policy = RetryPolicy {
+            verbs: vec![HttpVerb::Get, HttpVerb::Patch, HttpVerb::Delete],
+            ..Default::default()
+        };
+
+        assert!(!policy.allows(HttpVerb::Patch, "https://api.sfox.com/v1/approval-rules/3"));
+        assert!(!policy.allows(HttpVerb::Delete, "https://api.sfox.com/v1/orders/open"));
+
+        assert!(!RetryPolicy::none().allows(HttpVerb::Get, "https://api.sfox.com/v1/orders"));
+    }
+
+    #[test]
+    fn test_delay_backoff() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(350),
+            jitter: false,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.delay(1, &server_error()), Duration::from_millis(100));
+        assert_eq!(policy.delay(2, &server_error()), Duration::from_millis(200));
+        assert_eq!(policy.delay(3, &server_error()), Duration::from_millis(350));
+        assert_eq!(
+            policy.delay(40, &server_error()),
+            Duration::from_millis(350)
+        );
+    }
+
+    #[test]
+    fn test_delay_jitter_is_bounded() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            assert!(policy.delay(2, &server_error()) <= Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn test_delay_honors_retry_after() {
+        let policy = RetryPolicy::default();
+        let error = HttpError::RateLimited {
+            retry_after: Some(Duration::from_secs(2)),
+            message: String::new(),
+            body: String::new(),
+            method: HttpVerb::Get,
+            url: String::new(),
+        };
+
+        assert_eq!(policy.delay(1, &error), Duration::from_secs(2));
+    }
+}
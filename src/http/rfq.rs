@@ -0,0 +1,389 @@
+use std::time::Duration;
+
+use futures_util::{stream, Stream};
+
+use super::v1::order::ExecutedQuote;
+use super::v1::quote::{Quote, QuoteError, QuoteRequest};
+use super::Client;
+use crate::num::Number;
+use crate::trading::Side;
+
+/// How long before expiry [`QuoteManager`] replaces the current quote by default.
+pub const DEFAULT_REFRESH_MARGIN: Duration = Duration::from_secs(2);
+
+/// Shortest wait between refreshes of [`QuoteManager::into_stream`] when a quote's expiry can't
+/// be read.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Drives a request for quote through its lifecycle: requesting the quote, replacing it before it
+/// expires and executing it, optionally only when it is in line with the order book.
+///
+/// ```no_run
+/// use sfox::http::rfq::QuoteManager;
+/// use sfox::http::v1::quote::QuoteRequest;
+/// use sfox::http::Client;
+/// use sfox::trading::Side;
+///
+/// tokio_test::block_on(async {
+///     let request = QuoteRequest {
+///         pair: "btcusd".into(),
+///         side: Side::Buy,
+///         quantity: "1".parse().ok(),
+///         amount: None,
+///         client_quote_id: None,
+///     };
+///     let mut manager = QuoteManager::new(Client::new().unwrap(), request)
+///         .max_slippage("0.005".parse().unwrap());
+///
+///     let quote = manager.quote().await.unwrap();
+///     println!("{:?} expires at {}", quote.price(), quote.date_expiry);
+///     let _executed = manager.accept().await.unwrap();
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct QuoteManager {
+    client: Client,
+    request: QuoteRequest,
+    refresh_margin: Duration,
+    max_slippage: Option<Number>,
+    current: Option<Quote>,
+}
+
+impl QuoteManager {
+    pub fn new(client: Client, request: QuoteRequest) -> Self {
+        QuoteManager {
+            client,
+            request,
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+            max_slippage: None,
+            current: None,
+        }
+    }
+
+    /// Replace the current quote once less than `margin` remains before it expires.
+    pub fn refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    /// Only accept quotes priced within `slippage` (a fraction, e.g. `0.005` for 0.5%) of what
+    /// filling the same quantity against the live order book would cost.
+    pub fn max_slippage(mut self, slippage: Number) -> Self {
+        self.max_slippage = Some(slippage);
+        self
+    }
+
+    /// The most recently received quote, which may have expired.
+    pub fn current(&self) -> Option<&Quote> {
+        self.current.as_ref()
+    }
+
+    /// The current quote, requesting a new one if there is none or it is about to expire.
+    pub async fn quote(&mut self) -> Result<&Quote, QuoteError> {
+        match &self.current {
+            Some(quote) if self.is_fresh(quote) => Ok(self.current.as_ref().unwrap()),
+            _ => self.refresh().await,
+        }
+    }
+
+    /// Requests a new quote, replacing the current one.
+    pub async fn refresh(&mut self) -> Result<&Quote, QuoteError> {
+        let request = &self.request;
+        let quote = self
+            .client
+            .request_for_quote(
+                &request.pair,
+                request.side.clone(),
+                request.quantity,
+                request.amount,
+                request.client_quote_id.as_deref(),
+            )
+            .await?;
+
+        Ok(self.current.insert(quote))
+    }
+
+    /// Compares a quote against an estimate from the order book, when a maximum slippage is
+    /// set. Buys may cost at most `1 + slippage` times the estimate and sells must fetch at least
+    /// `1 - slippage` times it.
+    pub async fn check(&self, quote: &Quote) -> Result<(), QuoteError> {
+        let slippage = match self.max_slippage {
+            Some(slippage) => slippage,
+            None => return Ok(()),
+        };
+
+        let quoted = quote.price().ok_or_else(|| QuoteError::MissingPrice {
+            quote_id: quote.quote_id.clone(),
+            side: quote.side.clone(),
+        })?;
+        let book = self.client.order_book(quote.pair.as_str()).await?;
+        let estimate = book.estimate(&quote.side, quote.quantity).ok_or_else(|| {
+            QuoteError::InsufficientDepth {
+                pair: quote.pair.clone(),
+                quantity: quote.quantity,
+            }
+        })?;
+
+        let one = Number::from(1u8);
+        let acceptable = match quote.side {
+            Side::Sell => quoted >= estimate * (one - slippage),
+            _ => quoted <= estimate * (one + slippage),
+        };
+        if acceptable {
+            Ok(())
+        } else {
+            Err(QuoteError::UnfavorablePrice { quoted, estimate })
+        }
+    }
+
+    /// Executes the current quote, refreshing it first if it is about to expire and checking it
+    /// against the order book if a maximum slippage is set. The executed quote is cleared, so the
+    /// next call requests a new one.
+    pub async fn accept(&mut self) -> Result<ExecutedQuote, QuoteError> {
+        let quote = self.quote().await?.clone();
+        self.check(&quote).await?;
+
+        let executed = self.client.accept_quote(&quote).await?;
+        self.current = None;
+
+        Ok(executed)
+    }
+
+    /// A stream of quotes, each requested shortly before the previous one expires. Ends after
+    /// the first failed request.
+    pub fn into_stream(self) -> impl Stream<Item = Result<Quote, QuoteError>> + Send + 'static {
+        stream::try_unfold(self, |mut manager| async move {
+            if let Some(quote) = &manager.current {
+                let wait = match quote.time_remaining() {
+                    Some(remaining) => remaining.saturating_sub(manager.refresh_margin),
+                    None => manager.refresh_margin.max(MIN_REFRESH_INTERVAL),
+                };
+                tokio::time::sleep(wait).await;
+            }
+
+            let quote = manager.refresh().await?.clone();
+            Ok(Some((quote, manager)))
+        })
+    }
+
+    fn is_fresh(&self, quote: &Quote) -> bool {
+        match quote.time_remaining() {
+            Some(remaining) => remaining > self.refresh_margin,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures_util::{StreamExt, TryStreamExt};
+    use mockito::{Matcher, Mock, ServerGuard};
+
+    use crate::num::number;
+    use crate::util::server::new_test_server_and_client;
+
+    const EXECUTE_QUOTE_RESPONSE_BODY: &str = r#"
+        {
+            "id": 1754344,
+            "side_id": 500,
+            "action": "Buy",
+            "algorithm_id": 150,
+            "algorithm": "Instant",
+            "type": "Instant",
+            "pair": "btcusd",
+            "quantity": 1,
+            "price": 27044.7156,
+            "amount": 0,
+            "net_market_amount": 0,
+            "filled": 1,
+            "vwap": 27044.7156,
+            "filled_amount": 27044.7156,
+            "fees": 0,
+            "net_proceeds": -27044.7156,
+            "status": "Done",
+            "status_code": 300,
+            "routing_option": "BestPrice",
+            "routing_type": "None",
+            "time_in_force": "FOK",
+            "expires": null,
+            "dateupdated": "2023-01-26T20:27:06.000Z",
+            "client_order_id": "",
+            "user_tx_id": "",
+            "o_action": "Buy",
+            "algo_id": 150,
+            "algorithm_options": null,
+            "destination": ""
+        }
+    "#;
+
+    fn quote_body(date_expiry: &str) -> String {
+        format!(
+            r#"{{
+                "quote_id": "165c404c-ffe9-11ed-b8ed-0a170e3de1bd",
+                "pair": "btcusd",
+                "side": "BUY",
+                "date_expiry": "{}",
+                "date_quote": "2023-05-31T19:26:48.595Z",
+                "amount": 27044.7156,
+                "quantity": 1,
+                "buy_price": 27044.7156
+            }}"#,
+            date_expiry
+        )
+    }
+
+    fn order_book_body(ask: &str) -> String {
+        format!(
+            r#"{{
+                "pair": "btcusd",
+                "currency": "usd",
+                "bids": [],
+                "asks": [[{}, 5, "market1"]],
+                "market_making": {{"bids": [], "asks": []}},
+                "lastupdated": 1572903458796,
+                "lastpublished": 1572903458798
+            }}"#,
+            ask
+        )
+    }
+
+    fn request() -> QuoteRequest {
+        QuoteRequest {
+            pair: "btcusd".into(),
+            side: Side::Buy,
+            quantity: Some(number("1")),
+            amount: None,
+            client_quote_id: None,
+        }
+    }
+
+    async fn mock_quote(server: &mut ServerGuard, date_expiry: &str, hits: usize) -> Mock {
+        server
+            .mock("POST", "/v1/quote")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"pair": "btcusd", "side": "buy"}"#.into(),
+            ))
+            .with_status(201)
+            .with_body(quote_body(date_expiry))
+            .expect(hits)
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_quote_is_reused_until_it_expires() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = mock_quote(&mut server, "2999-01-01T00:00:00.000Z", 1).await;
+        let mut manager = QuoteManager::new(client, request());
+
+        manager.quote().await.unwrap();
+        manager.quote().await.unwrap();
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_expired_quote_is_refreshed() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = mock_quote(&mut server, "2023-05-31T19:26:58.595Z", 2).await;
+        let mut manager = QuoteManager::new(client, request());
+
+        manager.quote().await.unwrap();
+        assert!(manager.current().unwrap().is_expired());
+        manager.quote().await.unwrap();
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_accept_checks_order_book() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let quote = mock_quote(&mut server, "2999-01-01T00:00:00.000Z", 1).await;
+        let book = server
+            .mock("GET", "/v1/markets/orderbook/btcusd")
+            .with_body(order_book_body("27000"))
+            .create_async()
+            .await;
+        let execute = server
+            .mock("POST", "/v1/orders/buy")
+            .expect(0)
+            .create_async()
+            .await;
+        let mut manager = QuoteManager::new(client, request()).max_slippage(number("0.001"));
+
+        // 27044.7156 is more than 0.1% above the 27000 the book would cost.
+        let result = manager.accept().await;
+        assert!(matches!(result, Err(QuoteError::UnfavorablePrice { .. })));
+        assert!(manager.current().is_some());
+
+        for mock in [quote, book, execute] {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accept_executes_on_quote_side() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let quote = mock_quote(&mut server, "2999-01-01T00:00:00.000Z", 1).await;
+        let book = server
+            .mock("GET", "/v1/markets/orderbook/btcusd")
+            .with_body(order_book_body("27040"))
+            .create_async()
+            .await;
+        let execute = server
+            .mock("POST", "/v1/orders/buy")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"quote_id": "165c404c-ffe9-11ed-b8ed-0a170e3de1bd"}"#.into(),
+            ))
+            .with_status(201)
+            .with_body(EXECUTE_QUOTE_RESPONSE_BODY)
+            .create_async()
+            .await;
+        let mut manager = QuoteManager::new(client, request()).max_slippage(number("0.001"));
+
+        let executed = manager.accept().await.unwrap();
+        assert_eq!(executed.id, 1754344);
+        assert!(manager.current().is_none());
+
+        for mock in [quote, book, execute] {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accept_rejects_quote_that_arrives_expired() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let quote = mock_quote(&mut server, "2023-05-31T19:26:58.595Z", 1).await;
+        let execute = server
+            .mock("POST", "/v1/orders/buy")
+            .expect(0)
+            .create_async()
+            .await;
+        let mut manager = QuoteManager::new(client, request());
+
+        let result = manager.accept().await;
+        assert!(matches!(result, Err(QuoteError::Expired { .. })));
+
+        for mock in [quote, execute] {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_into_stream_refreshes() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = mock_quote(&mut server, "2023-05-31T19:26:58.595Z", 3).await;
+
+        let quotes: Vec<Quote> = QuoteManager::new(client, request())
+            .into_stream()
+            .take(3)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(quotes.len(), 3);
+        mock.assert_async().await;
+    }
+}
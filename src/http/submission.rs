@@ -0,0 +1,398 @@
+use std::time::Duration;
+
+use futures_util::Future;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::v1::order::{ClientOrder, Order};
+use super::v1::order_request::OrderRequest;
+use super::{Client, HttpError};
+
+/// Controls how [`Client::submit_order_idempotent_with`] resolves ambiguous failures.
+///
+/// After a submission fails in a way that may have reached the exchange (see
+/// [`HttpError::is_ambiguous`]), the client looks the order up by its client order id up to
+/// `reconcile_lookups` times, waiting `reconcile_delay` before the first lookup and twice as long
+/// before each next one, and only submits it again when none found it. The default waits 2, 4
+/// and 8 seconds, so an order the exchange lists late is not placed twice.
+#[derive(Clone, Debug)]
+pub struct SubmissionPolicy {
+    /// Total number of submissions, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first lookup of an order whose submission failed ambiguously, giving
+    /// the exchange time to list it.
+    pub reconcile_delay: Duration,
+    /// Lookups that must all miss before an order is submitted again. At least one is made.
+    pub reconcile_lookups: u32,
+}
+
+impl Default for SubmissionPolicy {
+    fn default() -> Self {
+        SubmissionPolicy {
+            max_attempts: 3,
+            reconcile_delay: Duration::from_secs(2),
+            reconcile_lookups: 3,
+        }
+    }
+}
+
+/// How an idempotent submission ended.
+#[derive(Clone, Debug)]
+pub enum SubmissionOutcome {
+    /// sFOX accepted the order and returned it.
+    Placed(Order),
+    /// A submission failed ambiguously and the order was then found by its client order id.
+    Reconciled(ClientOrder),
+}
+
+impl SubmissionOutcome {
+    /// The server id of the order.
+    pub fn id(&self) -> usize {
+        match self {
+            SubmissionOutcome::Placed(order) => order.id,
+            SubmissionOutcome::Reconciled(order) => order.id(),
+        }
+    }
+}
+
+/// Why an idempotent submission did not place an order.
+#[derive(Debug, Error)]
+pub enum SubmissionError {
+    /// The order was refused outright and does not exist.
+    #[error("Order `{client_order_id}` was rejected: {source}")]
+    Rejected {
+        client_order_id: String,
+        source: HttpError,
+    },
+    /// Every attempt failed ambiguously and the order was not found after any of them.
+    #[error("Order `{client_order_id}` was not placed after {attempts} attempts: {source}")]
+    NotPlaced {
+        client_order_id: String,
+        attempts: u32,
+        source: HttpError,
+    },
+    /// A submission failed ambiguously and the lookup that would settle it failed too. The
+    /// order may or may not exist; look it up again with [`Client::order_by_client_id`].
+    #[error("Could not tell whether order `{client_order_id}` was placed: {source}")]
+    Unresolved {
+        client_order_id: String,
+        source: HttpError,
+    },
+}
+
+impl SubmissionError {
+    /// The client order id the order was submitted with.
+    pub fn client_order_id(&self) -> &str {
+        match self {
+            SubmissionError::Rejected {
+                client_order_id, ..
+            }
+            | SubmissionError::NotPlaced {
+                client_order_id, ..
+            }
+            | SubmissionError::Unresolved {
+                client_order_id, ..
+            } => client_order_id,
+        }
+    }
+}
+
+/// A new, random client order id.
+pub fn new_client_order_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+impl Client {
+    /// Submits an order with the default [`SubmissionPolicy`]. See
+    /// [`Client::submit_order_idempotent_with`].
+    pub fn submit_order_idempotent(
+        &self,
+        order: &OrderRequest,
+    ) -> impl Future<Output = Result<SubmissionOutcome, SubmissionError>> + Send + 'static {
+        self.submit_order_idempotent_with(order, SubmissionPolicy::default())
+    }
+
+    /// Submits an order so that it is placed at most once, even when a request times out.
+    ///
+    /// The order is given a random `client_order_id` unless it already has one. When a
+    /// submission fails ambiguously the order is looked up by that id in the open and done
+    /// orders; if found it is returned, otherwise, once `policy.reconcile_lookups` lookups have
+    /// missed, it is submitted again with the same id, up to `policy.max_attempts` times. Any other failure is returned as
+    /// [`SubmissionError::Rejected`] without a lookup.
+    pub fn submit_order_idempotent_with(
+        &self,
+        order: &OrderRequest,
+        policy: SubmissionPolicy,
+    ) -> impl Future<Output = Result<SubmissionOutcome, SubmissionError>> + Send + 'static {
+        let client = self.clone();
+        let mut order = order.clone();
+        let client_order_id = order
+            .body_mut()
+            .client_order_id
+            .get_or_insert_with(new_client_order_id)
+            .clone();
+
+        async move {
+            let mut attempt = 0;
+            loop {
+                attempt += 1;
+                let error = match client.submit_order(&order).await {
+                    Ok(placed) => return Ok(SubmissionOutcome::Placed(placed)),
+                    Err(e) if !e.is_ambiguous() => {
+                        return Err(SubmissionError::Rejected {
+                            client_order_id,
+                            source: e,
+                        })
+                    }
+                    Err(e) => e,
+                };
+
+                match reconcile(&client, &client_order_id, &policy).await {
+                    Ok(Some(found)) => return Ok(SubmissionOutcome::Reconciled(found)),
+                    Ok(None) if attempt < policy.max_attempts => continue,
+                    Ok(None) => {
+                        return Err(SubmissionError::NotPlaced {
+                            client_order_id,
+                            attempts: attempt,
+                            source: error,
+                        })
+                    }
+                    Err(source) => {
+                        return Err(SubmissionError::Unresolved {
+                            client_order_id,
+                            source,
+                        })
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Looks an order up until it is found or the policy's lookups are used up.
+async fn reconcile(
+    client: &Client,
+    client_order_id: &str,
+    policy: &SubmissionPolicy,
+) -> Result<Option<ClientOrder>, HttpError> {
+    let mut delay = policy.reconcile_delay;
+    for _ in 0..policy.reconcile_lookups.max(1) {
+        tokio::time::sleep(delay).await;
+        delay = delay.saturating_mul(2);
+
+        if let Some(order) = client.order_by_client_id(client_order_id).await? {
+            return Ok(Some(order));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mockito::{Matcher, Mock, ServerGuard};
+
+    use crate::num::number;
+    use crate::trading::{Algorithm, Side};
+    use crate::util::server::new_test_server_and_client;
+
+    const ORDER_RESPONSE_BODY: &str = r#"
+        {
+            "id": 123,
+            "quantity": 1,
+            "price": 10,
+            "o_action": "Buy",
+            "pair": "btcusd",
+            "type": "Limit",
+            "vwap": 0,
+            "filled": 0,
+            "status": "Started",
+            "client_order_id": "abc"
+        }
+    "#;
+
+    fn order() -> OrderRequest {
+        OrderRequest::builder(Side::Buy, "btcusd", Algorithm::Limit, number("1"))
+            .price(number("10"))
+            .client_order_id("abc")
+            .build()
+            .unwrap()
+    }
+
+    fn policy() -> SubmissionPolicy {
+        SubmissionPolicy {
+            max_attempts: 2,
+            reconcile_delay: Duration::ZERO,
+            reconcile_lookups: 1,
+        }
+    }
+
+    async fn mock_post(server: &mut ServerGuard, status: usize, body: &str, hits: usize) -> Mock {
+        server
+            .mock("POST", "/v1/orders/buy")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"client_order_id": "abc"}"#.into(),
+            ))
+            .with_status(status)
+            .with_body(body)
+            .expect(hits)
+            .create_async()
+            .await
+    }
+
+    async fn mock_get(server: &mut ServerGuard, path: &str, body: &str, hits: usize) -> Mock {
+        server
+            .mock("GET", path)
+            .with_body(body)
+            .expect(hits)
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_assigns_client_order_id() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", "/v1/orders/buy")
+            .match_body(Matcher::Regex(
+                r#""client_order_id":"[0-9a-f]{8}-[0-9a-f]{4}-4"#.into(),
+            ))
+            .with_body(ORDER_RESPONSE_BODY)
+            .create_async()
+            .await;
+
+        let order = OrderRequest::builder(Side::Buy, "btcusd", Algorithm::Market, number("1"))
+            .build()
+            .unwrap();
+        let outcome = client.submit_order_idempotent(&order).await.unwrap();
+
+        assert!(matches!(outcome, SubmissionOutcome::Placed(_)));
+        assert_eq!(outcome.id(), 123);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_reconciles_ambiguous_failure() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let post = mock_post(&mut server, 503, "", 1).await;
+        let open = mock_get(
+            &mut server,
+            "/v1/orders",
+            &format!("[{}]", ORDER_RESPONSE_BODY),
+            1,
+        )
+        .await;
+        let done = mock_get(&mut server, "/v1/orders/done", "[]", 0).await;
+
+        let outcome = client
+            .submit_order_idempotent_with(&order(), policy())
+            .await
+            .unwrap();
+
+        match outcome {
+            SubmissionOutcome::Reconciled(ClientOrder::Open(order)) => assert_eq!(order.id, 123),
+            other => panic!("expected a reconciled open order, got {:?}", other),
+        }
+        for mock in [post, open, done] {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconciles_order_listed_late() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let post = mock_post(&mut server, 503, "", 1).await;
+        // The first lookup misses the order, the second finds it.
+        let missing = mock_get(&mut server, "/v1/orders", "[]", 1).await;
+        let done = mock_get(&mut server, "/v1/orders/done", "[]", 1).await;
+        let listed = mock_get(
+            &mut server,
+            "/v1/orders",
+            &format!("[{}]", ORDER_RESPONSE_BODY),
+            1,
+        )
+        .await;
+
+        let policy = SubmissionPolicy {
+            reconcile_lookups: 2,
+            ..policy()
+        };
+        let outcome = client
+            .submit_order_idempotent_with(&order(), policy)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            outcome,
+            SubmissionOutcome::Reconciled(ClientOrder::Open(_))
+        ));
+        for mock in [post, missing, done, listed] {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resubmits_when_not_found() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let failed = mock_post(&mut server, 503, "", 1).await;
+        let placed = mock_post(&mut server, 200, ORDER_RESPONSE_BODY, 1).await;
+        let open = mock_get(&mut server, "/v1/orders", "[]", 1).await;
+        let done = mock_get(&mut server, "/v1/orders/done", "[]", 1).await;
+
+        let outcome = client
+            .submit_order_idempotent_with(&order(), policy())
+            .await
+            .unwrap();
+
+        assert!(matches!(outcome, SubmissionOutcome::Placed(_)));
+        for mock in [failed, placed, open, done] {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let post = mock_post(&mut server, 503, "", 2).await;
+        let open = mock_get(&mut server, "/v1/orders", "[]", 2).await;
+        let done = mock_get(&mut server, "/v1/orders/done", "[]", 2).await;
+
+        let result = client
+            .submit_order_idempotent_with(&order(), policy())
+            .await;
+
+        match result.unwrap_err() {
+            SubmissionError::NotPlaced {
+                client_order_id,
+                attempts,
+                source,
+            } => {
+                assert_eq!(client_order_id, "abc");
+                assert_eq!(attempts, 2);
+                assert_eq!(source.status(), Some(503));
+            }
+            other => panic!("expected NotPlaced, got {:?}", other),
+        }
+        for mock in [post, open, done] {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejection_is_not_reconciled() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let post = mock_post(&mut server, 400, r#"{"error": "insufficient funds"}"#, 1).await;
+        let open = mock_get(&mut server, "/v1/orders", "[]", 0).await;
+
+        let result = client
+            .submit_order_idempotent_with(&order(), policy())
+            .await;
+
+        assert!(matches!(result, Err(SubmissionError::Rejected { .. })));
+        for mock in [post, open] {
+            mock.assert_async().await;
+        }
+    }
+}
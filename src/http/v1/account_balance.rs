@@ -2,23 +2,26 @@ use futures_util::Future;
 use serde::Deserialize;
 
 use super::super::{Client, HttpError, HttpVerb};
+use crate::num::Number;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct AccountBalance {
     pub currency: String,
-    pub balance: f64,
-    pub available: f64,
-    pub held: f64,
-    pub borrow_wallet: f64,
-    pub collateral_wallet: f64,
-    pub lending_wallet: f64,
-    pub trading_wallet: f64,
+    pub balance: Number,
+    pub available: Number,
+    pub held: Number,
+    pub borrow_wallet: Number,
+    pub collateral_wallet: Number,
+    pub lending_wallet: Number,
+    pub trading_wallet: Number,
 }
 
 static RESOURCE: &str = "user/balance";
 
 impl Client {
-    pub fn account_balance(self) -> impl Future<Output = Result<Vec<AccountBalance>, HttpError>> {
+    pub fn account_balance(
+        &self,
+    ) -> impl Future<Output = Result<Vec<AccountBalance>, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(RESOURCE);
         self.request::<Vec<AccountBalance>>(HttpVerb::Get, &url, None)
     }
@@ -1,8 +1,8 @@
 use futures_util::Future;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
 use super::super::{Client, HttpError, HttpVerb};
+use crate::num::{self, Number};
 
 static ACH_BANK_TRANSFER_RESOURCE: &str = "user/bank/deposit";
 
@@ -12,16 +12,22 @@ pub struct AchBankTransfer {
     pub success: bool,
 }
 
+/// Body of a `POST /v1/user/bank/deposit` request.
+#[derive(Clone, Debug, Serialize)]
+pub struct AchBankTransferRequest {
+    #[serde(serialize_with = "num::serialize")]
+    pub amount: Number,
+}
+
 impl Client {
     pub fn ach_bank_transfer(
-        self,
-        amount: f64,
-    ) -> impl Future<Output = Result<AchBankTransfer, HttpError>> {
-        let mut params = HashMap::new();
-        params.insert("amount".into(), amount.to_string());
+        &self,
+        amount: Number,
+    ) -> impl Future<Output = Result<AchBankTransfer, HttpError>> + Send + 'static {
+        let body = AchBankTransferRequest { amount };
 
         let url = self.url_for_v1_resource(ACH_BANK_TRANSFER_RESOURCE);
-        self.request::<AchBankTransfer>(HttpVerb::Post, &url, Some(&params))
+        self.request_with_body::<AchBankTransfer, _>(HttpVerb::Post, &url, &body)
     }
 }
 
@@ -29,6 +35,7 @@ impl Client {
 mod tests {
     use super::*;
 
+    use crate::num::number;
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
     const RESPONSE_BODY: &str = r#"
@@ -49,7 +56,7 @@ mod tests {
 
         let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
 
-        let result = client.ach_bank_transfer(100.0).await;
+        let result = client.ach_bank_transfer(number("100.0")).await;
 
         assert!(result.is_ok());
 
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use futures_util::Future;
 use serde::Deserialize;
 
@@ -15,19 +13,19 @@ static CRYPTO_DEPOSIT_ADDRESS_RESOURCE: &str = "user/deposit/address";
 
 impl Client {
     pub fn crypto_deposit_address(
-        self,
+        &self,
         currency: &str,
-    ) -> impl Future<Output = Result<Vec<CryptoDepositAddress>, HttpError>> {
+    ) -> impl Future<Output = Result<Vec<CryptoDepositAddress>, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(&currency_path(currency));
         self.request::<Vec<CryptoDepositAddress>>(HttpVerb::Get, &url, None)
     }
 
     pub fn new_crypto_deposit_address(
-        self,
+        &self,
         currency: &str,
-    ) -> impl Future<Output = Result<CryptoDepositAddress, HttpError>> {
+    ) -> impl Future<Output = Result<CryptoDepositAddress, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(&currency_path(currency));
-        self.request(HttpVerb::Post, &url, Some(&HashMap::new()))
+        self.request(HttpVerb::Post, &url, Some(serde_json::json!({})))
     }
 }
 
@@ -4,6 +4,7 @@ use std::collections::HashMap;
 
 use super::super::{Client, HttpError, HttpVerb};
 use super::bool_from_int;
+use crate::num::Number;
 
 static CURRENCIES_RESOURCE: &str = "currency";
 static CURRENCY_PAIRS_RESOURCE: &str = "markets/currency-pairs";
@@ -21,7 +22,7 @@ pub struct Currency {
     pub can_deposit: bool,
     #[serde(deserialize_with = "bool_from_int")]
     pub can_withdraw: bool,
-    pub min_withdrawal: f64,
+    pub min_withdrawal: Number,
     pub confirmations_needed: Option<usize>,
     pub precision: usize,
     pub ascii_sign: String,
@@ -44,14 +45,17 @@ pub struct CurrencyPair {
 }
 
 impl Client {
-    pub fn currencies(self) -> impl Future<Output = Result<Vec<Currency>, HttpError>> {
+    pub fn currencies(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Currency>, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(CURRENCIES_RESOURCE);
         self.request(HttpVerb::Get, &url, None)
     }
 
     pub fn currency_pairs(
-        self,
-    ) -> impl Future<Output = Result<HashMap<String, CurrencyPair>, HttpError>> {
+        &self,
+    ) -> impl Future<Output = Result<HashMap<String, CurrencyPair>, HttpError>> + Send + 'static
+    {
         let url = self.url_for_v1_resource(CURRENCY_PAIRS_RESOURCE);
         self.request(HttpVerb::Get, &url, None)
     }
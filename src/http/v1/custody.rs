@@ -1,23 +1,28 @@
 use futures_util::Future;
-use serde_derive::Deserialize;
-use std::collections::HashMap;
+use serde::de::{Deserializer, Error as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
 
+use super::currency::Currency;
+use crate::address::{self, AddressError};
 use crate::http::{Client, HttpError, HttpVerb};
+use crate::num::{self, Number};
+use crate::trading::vocabulary;
 
 static APPROVAL_RULES_RESOURCE: &str = "approval-rules";
 static APPROVAL_RESOURCE: &str = "approvals";
 static CUSTODY_RESOURCE: &str = "whitelisted-addresses";
 
-#[derive(Clone, Debug, Deserialize)]
-pub enum ApprovalRuleType {
-    #[serde(rename = "ADD_ALTER_COLL")]
-    AddAlterColl,
-
-    #[serde(rename = "ALTER_SAFE")]
-    AlterSafe,
-
-    #[serde(rename = "WITHDRAW")]
-    Withdraw,
+vocabulary! {
+    /// The action an approval rule guards.
+    pub enum ApprovalRuleType {
+        /// Adding or changing a collaborator on the account.
+        AddAlterColl => "ADD_ALTER_COLL",
+        /// Changing the account's safe mode settings.
+        AlterSafe => "ALTER_SAFE",
+        Withdraw => "WITHDRAW",
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -50,12 +55,22 @@ pub struct CustodyAddress {
     pub tag: Option<String>,
 }
 
+/// Why [`Client::add_checked_custody_address`] failed.
+#[derive(Debug, Error)]
+pub enum CustodyAddressError {
+    #[error(transparent)]
+    InvalidAddress(#[from] AddressError),
+    #[error(transparent)]
+    Http(#[from] HttpError),
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct ApprovalRequestResponse {
     pub data: Vec<ApprovalRequest>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "RawApprovalRequest")]
 pub struct ApprovalRequest {
     pub approval_id: usize,
     pub requested_by_username: String,
@@ -66,18 +81,132 @@ pub struct ApprovalRequest {
     pub required_approvals: usize,
     pub received_approvals: usize,
     pub action_details: ActionDetails,
-    pub approval_responses: ApprovalResponse,
+    pub approval_responses: Vec<ApprovalResponse>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct ActionDetails {
+/// What an approval request would do, shaped by its `approval_type`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActionDetails {
+    Withdraw(WithdrawalAction),
+    AddAlterColl(CollaboratorAction),
+    AlterSafe(SafeModeAction),
+    /// Details of an approval type unknown to this version of the crate.
+    Unknown(Value),
+}
+
+/// The withdrawal a `WITHDRAW` approval request would send.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct WithdrawalAction {
     pub atx_currency_code: String,
-    pub atx_amount: f64,
+    pub atx_amount: Number,
     pub atx_dest_address: String,
-    pub threshold: usize,
+    pub threshold: Option<Number>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// The collaborator an `ADD_ALTER_COLL` approval request would add or change. Fields this
+/// version of the crate does not know are kept in `other`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct CollaboratorAction {
+    #[serde(default, alias = "collaborator_email")]
+    pub email: Option<String>,
+    /// The collaborator's role once the request is approved.
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// The safe mode change an `ALTER_SAFE` approval request would make. Fields this version of
+/// the crate does not know are kept in `other`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct SafeModeAction {
+    /// Whether safe mode is turned on (`true`) or off.
+    #[serde(default)]
+    pub safe_mode: Option<bool>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// An approval request as sent by sFOX, before its action details are typed.
+#[derive(Deserialize)]
+struct RawApprovalRequest {
+    approval_id: usize,
+    requested_by_username: String,
+    requested_by_uaid: String,
+    date_added: String,
+    status: String,
+    approval_type: ApprovalRuleType,
+    required_approvals: usize,
+    received_approvals: usize,
+    #[serde(default)]
+    action_details: Value,
+    #[serde(default, deserialize_with = "one_or_many")]
+    approval_responses: Vec<ApprovalResponse>,
+}
+
+impl TryFrom<RawApprovalRequest> for ApprovalRequest {
+    type Error = serde_json::Error;
+
+    fn try_from(raw: RawApprovalRequest) -> Result<Self, Self::Error> {
+        // Missing details are read as an empty object.
+        let as_object = |value: Value| match value {
+            Value::Object(map) => Ok(Value::Object(map)),
+            Value::Null => Ok(Value::Object(Map::new())),
+            other => Err(serde_json::Error::custom(format!(
+                "expected action details object, got {}",
+                other
+            ))),
+        };
+
+        let action_details = match raw.approval_type {
+            ApprovalRuleType::Withdraw => {
+                ActionDetails::Withdraw(serde_json::from_value(raw.action_details)?)
+            }
+            ApprovalRuleType::AddAlterColl => {
+                ActionDetails::AddAlterColl(serde_json::from_value(as_object(raw.action_details)?)?)
+            }
+            ApprovalRuleType::AlterSafe => {
+                ActionDetails::AlterSafe(serde_json::from_value(as_object(raw.action_details)?)?)
+            }
+            ApprovalRuleType::Unknown(_) => ActionDetails::Unknown(raw.action_details),
+        };
+
+        Ok(ApprovalRequest {
+            approval_id: raw.approval_id,
+            requested_by_username: raw.requested_by_username,
+            requested_by_uaid: raw.requested_by_uaid,
+            date_added: raw.date_added,
+            status: raw.status,
+            approval_type: raw.approval_type,
+            required_approvals: raw.required_approvals,
+            received_approvals: raw.received_approvals,
+            action_details,
+            approval_responses: raw.approval_responses,
+        })
+    }
+}
+
+/// Accepts a list, a single object or `null`, which sFOX sends when there is at most one
+/// response.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<ApprovalResponse>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        Many(Vec<ApprovalResponse>),
+        One(ApprovalResponse),
+    }
+
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        Some(OneOrMany::Many(responses)) => responses,
+        Some(OneOrMany::One(response)) => vec![response],
+        None => vec![],
+    })
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct ApprovalResponse {
     pub ua_display_id: String,
     pub username: String,
@@ -97,98 +226,174 @@ pub struct ApprovalRule {
     pub status: String,
     pub available_approver_count: usize,
     pub required_approvals: usize,
-    pub threshold: usize,
+    pub threshold: Number,
+}
+
+/// Body of a request whitelisting a withdrawal address.
+#[derive(Clone, Debug, Serialize)]
+pub struct AddCustodyAddressRequest {
+    pub alias: String,
+    pub currency_symbol: String,
+    pub address: String,
+    /// Memo or destination tag, for networks that take one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
+/// Body of a request creating an approval rule.
+#[derive(Clone, Debug, Serialize)]
+pub struct AddApprovalRuleRequest {
+    pub rule_type: ApprovalRuleType,
+    pub required_approvals: usize,
+    #[serde(serialize_with = "num::serialize")]
+    pub threshold: Number,
+}
+
+/// Body of a request changing an approval rule.
+#[derive(Clone, Debug, Serialize)]
+pub struct EditApprovalRuleRequest {
+    pub required_approvals: usize,
+    #[serde(serialize_with = "num::serialize")]
+    pub threshold: Number,
+}
+
+/// Body of a request approving or rejecting a pending approval request.
+#[derive(Clone, Debug, Serialize)]
+pub struct ApprovalDecisionRequest {
+    pub approve: bool,
+}
+
+/// Query listing approval requests. Leaving `pending` unset lists every request.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ApprovalRequestsQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending: Option<bool>,
 }
 
 impl Client {
     pub fn custody_addresses(
-        self,
-    ) -> impl Future<Output = Result<CustodyAddressesResponse, HttpError>> {
+        &self,
+    ) -> impl Future<Output = Result<CustodyAddressesResponse, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(CUSTODY_RESOURCE);
 
         self.request(HttpVerb::Get, &query_str, None)
     }
 
     pub fn add_custody_address(
-        self,
+        &self,
         alias: String,
         currency_symbol: String,
         address: String,
-    ) -> impl Future<Output = Result<CustodyAddress, HttpError>> {
+    ) -> impl Future<Output = Result<CustodyAddress, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(CUSTODY_RESOURCE);
 
-        let mut params = HashMap::new();
-        params.insert("alias".into(), alias);
-        params.insert("currency_symbol".into(), currency_symbol);
-        params.insert("address".into(), address);
+        let body = AddCustodyAddressRequest {
+            alias,
+            currency_symbol,
+            address,
+            tag: None,
+        };
+
+        self.request_with_body(HttpVerb::Post, &query_str, &body)
+    }
+
+    /// Whitelists an address after checking its format, checksum and tag for the currency's
+    /// network (see [`crate::address`]). Addresses on networks that can't be checked are
+    /// rejected; use [`Client::add_custody_address`] for those.
+    pub fn add_checked_custody_address(
+        &self,
+        alias: String,
+        currency: &Currency,
+        address: String,
+        tag: Option<String>,
+    ) -> impl Future<Output = Result<CustodyAddress, CustodyAddressError>> + Send + 'static {
+        let query_str = self.url_for_v1_resource(CUSTODY_RESOURCE);
 
-        self.request(HttpVerb::Post, &query_str, Some(&params))
+        let request = address::validate_address(currency, &address, tag.as_deref()).map(|_| {
+            let body = AddCustodyAddressRequest {
+                alias,
+                currency_symbol: currency.symbol.clone(),
+                address: address.trim().to_string(),
+                tag: tag.filter(|tag| !tag.trim().is_empty()),
+            };
+
+            self.request_with_body(HttpVerb::Post, &query_str, &body)
+        });
+
+        async move { Ok(request?.await?) }
     }
 
-    pub fn approval_rules(self) -> impl Future<Output = Result<ApprovalRulesResponse, HttpError>> {
+    pub fn approval_rules(
+        &self,
+    ) -> impl Future<Output = Result<ApprovalRulesResponse, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(APPROVAL_RULES_RESOURCE);
 
         self.request(HttpVerb::Get, &query_str, None)
     }
 
     pub fn add_approval_rule(
-        self,
-        rule_type: String,
+        &self,
+        rule_type: ApprovalRuleType,
         required_approvals: usize,
-        threshold: usize,
-    ) -> impl Future<Output = Result<ApprovalRule, HttpError>> {
+        threshold: Number,
+    ) -> impl Future<Output = Result<ApprovalRule, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(APPROVAL_RULES_RESOURCE);
 
-        let mut params = HashMap::new();
-        params.insert("rule_type".to_string(), rule_type);
-        params.insert(
-            "required_approvals".to_string(),
-            required_approvals.to_string(),
-        );
-        params.insert("threshold".to_string(), threshold.to_string());
+        let request = if rule_type.is_unknown() {
+            Err(HttpError::InvalidRequest(format!(
+                "Unknown approval rule type: {}",
+                rule_type
+            )))
+        } else {
+            let body = AddApprovalRuleRequest {
+                rule_type,
+                required_approvals,
+                threshold,
+            };
+            Ok(self.request_with_body(HttpVerb::Post, &query_str, &body))
+        };
 
-        self.request(HttpVerb::Post, &query_str, Some(&params))
+        async move { request?.await }
     }
 
     pub fn edit_approval_rule(
-        self,
+        &self,
         id: usize,
         required_approvals: usize,
-        threshold: f64,
-    ) -> impl Future<Output = Result<ApprovalRule, HttpError>> {
+        threshold: Number,
+    ) -> impl Future<Output = Result<ApprovalRule, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(&format!("{}/{}", APPROVAL_RULES_RESOURCE, id));
 
-        let mut params = HashMap::new();
-        params.insert("required_approvals".into(), required_approvals.to_string());
-        params.insert("threshold".into(), threshold.to_string());
+        let body = EditApprovalRuleRequest {
+            required_approvals,
+            threshold,
+        };
 
-        self.request(HttpVerb::Patch, &query_str, Some(&params))
+        self.request_with_body(HttpVerb::Patch, &query_str, &body)
     }
 
     pub fn approval_requests(
-        self,
+        &self,
         pending: bool,
-    ) -> impl Future<Output = Result<ApprovalRequestResponse, HttpError>> {
-        let mut query_str = self.url_for_v1_resource(APPROVAL_RESOURCE);
-        if pending {
-            query_str = format!("{}?pending=true", query_str);
-        }
+    ) -> impl Future<Output = Result<ApprovalRequestResponse, HttpError>> + Send + 'static {
+        let url = self.url_for_v1_resource(APPROVAL_RESOURCE);
+        let query = ApprovalRequestsQuery {
+            pending: pending.then_some(true),
+        };
 
-        self.request(HttpVerb::Get, &query_str, None)
+        self.request_with_query(HttpVerb::Get, &url, &query)
     }
 
     pub fn respond_to_approval_request(
-        self,
+        &self,
         id: usize,
         approve: bool,
-    ) -> impl Future<Output = Result<(), HttpError>> {
+    ) -> impl Future<Output = Result<(), HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(&format!("{}/{}", APPROVAL_RESOURCE, id));
 
-        // TODO: handle polymorphic params
-        let mut params = HashMap::new();
-        params.insert("approve".to_string(), approve.to_string());
+        let body = ApprovalDecisionRequest { approve };
 
-        self.request(HttpVerb::Post, &query_str, Some(&params))
+        self.request_with_body(HttpVerb::Post, &query_str, &body)
     }
 }
 
@@ -196,6 +401,7 @@ impl Client {
 mod tests {
     use super::*;
 
+    use crate::num::number;
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
     const CUSTODY_ADDRESSES_RESPONSE_BODY: &str = r#"
@@ -282,6 +488,101 @@ mod tests {
         }
     "#;
 
+    #[test]
+    fn test_approval_request_action_details() {
+        let requests: ApprovalRequestResponse =
+            serde_json::from_str(APPROVALS_RESPONSE_BODY).unwrap();
+        let request = &requests.data[0];
+
+        assert_eq!(request.approval_type, ApprovalRuleType::Withdraw);
+        match &request.action_details {
+            ActionDetails::Withdraw(withdrawal) => {
+                assert_eq!(withdrawal.atx_amount, number("5"));
+                assert_eq!(withdrawal.atx_dest_address, "0x1232131223");
+            }
+            other => panic!("expected withdrawal details, got {:?}", other),
+        }
+        // A single response object is read as a list of one.
+        assert_eq!(request.approval_responses.len(), 1);
+        assert!(request.approval_responses[0].approved);
+
+        let request: ApprovalRequest = serde_json::from_value(serde_json::json!({
+            "approval_id": 2,
+            "requested_by_username": "example@email.com",
+            "requested_by_uaid": "6ea3fb9e-7797-11eb-aa51-0242ac120002",
+            "date_added": "2021-03-03T20:28:41.000Z",
+            "status": "Pending",
+            "approval_type": "ALTER_SAFE",
+            "required_approvals": 2,
+            "received_approvals": 2,
+            "action_details": {"safe_mode": true, "reason": "travel"},
+            "approval_responses": [
+                {"ua_display_id": "a", "username": "one@email.com", "approved": true},
+                {"ua_display_id": "b", "username": "two@email.com", "approved": false}
+            ]
+        }))
+        .unwrap();
+
+        match &request.action_details {
+            ActionDetails::AlterSafe(details) => {
+                assert_eq!(details.safe_mode, Some(true));
+                assert_eq!(details.other["reason"], "travel");
+            }
+            other => panic!("expected safe mode details, got {:?}", other),
+        }
+        assert_eq!(request.approval_responses.len(), 2);
+
+        let request: ApprovalRequest = serde_json::from_value(serde_json::json!({
+            "approval_id": 3,
+            "requested_by_username": "example@email.com",
+            "requested_by_uaid": "6ea3fb9e-7797-11eb-aa51-0242ac120002",
+            "date_added": "2021-03-03T20:28:41.000Z",
+            "status": "Pending",
+            "approval_type": "ADD_ALTER_COLL",
+            "required_approvals": 1,
+            "received_approvals": 0,
+            "action_details": {"collaborator_email": "new@email.com", "role": "trader"},
+            "approval_responses": null
+        }))
+        .unwrap();
+
+        assert_eq!(
+            request.action_details,
+            ActionDetails::AddAlterColl(CollaboratorAction {
+                email: Some("new@email.com".into()),
+                role: Some("trader".into()),
+                other: Map::new(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_approval_rule_sends_typed_body() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", format!("/v1/{}", APPROVAL_RULES_RESOURCE).as_str())
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "rule_type": "ALTER_SAFE",
+                "required_approvals": 2,
+                "threshold": "0"
+            })))
+            .with_body(APPROVAL_RULE_RESPONSE_BODY)
+            .create_async()
+            .await;
+
+        let result = client
+            .add_approval_rule(ApprovalRuleType::AlterSafe, 2, number("0"))
+            .await;
+        assert!(result.is_ok());
+
+        let result = client
+            .add_approval_rule("SOMETHING_ELSE".into(), 2, number("0"))
+            .await;
+        assert!(matches!(result, Err(HttpError::InvalidRequest(_))));
+
+        mock.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_custody_addresses() {
         let mock = ApiMock {
@@ -323,6 +624,58 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_add_checked_custody_address() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", format!("/v1/{}", CUSTODY_RESOURCE).as_str())
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "alias": "Satoshis Fund",
+                "currency_symbol": "btc",
+                "address": "1NLqQmwkGxxQmzS9uwtCGXxbxrcNW4FpYp"
+            })))
+            .with_body(CREATE_CUSTODY_ADDRESS_RESPONSE_BODY)
+            .expect(1)
+            .create_async()
+            .await;
+        let btc: Currency = serde_json::from_value(serde_json::json!({
+            "id": 1, "symbol": "btc", "name": "Bitcoin", "is_fiat": 0, "is_lending_enabled": 0,
+            "can_deposit": 1, "can_withdraw": 1, "min_withdrawal": 0.001,
+            "confirmations_needed": 3, "precision": 8, "ascii_sign": "", "contract_address": null,
+            "custody_enabled": 1, "trading_enabled": 1, "primary_network": "bitcoin",
+            "code": "btc", "currency": "btc"
+        }))
+        .unwrap();
+
+        let result = client
+            .add_checked_custody_address(
+                "Satoshis Fund".into(),
+                &btc,
+                "1NLqQmwkGxxQmzS9uwtCGXxbxrcNW4FpYp".into(),
+                None,
+            )
+            .await;
+        assert!(result.is_ok());
+
+        // A mistyped address is rejected without a request.
+        let result = client
+            .add_checked_custody_address(
+                "Satoshis Fund".into(),
+                &btc,
+                "1NLqQmwkGxxQmzS9uwtCGXxbxrcNW4FpYq".into(),
+                None,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(CustodyAddressError::InvalidAddress(
+                AddressError::InvalidChecksum { .. }
+            ))
+        ));
+
+        mock.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_approval_rules() {
         let mock = ApiMock {
@@ -354,7 +707,7 @@ mod tests {
         let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
 
         let result = client
-            .add_approval_rule("test rule type".into(), 1, 1)
+            .add_approval_rule(ApprovalRuleType::Withdraw, 1, number("1"))
             .await;
 
         assert!(result.is_ok());
@@ -377,7 +730,7 @@ mod tests {
 
         let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
 
-        let result = client.edit_approval_rule(rule_id, 2, 2.0).await;
+        let result = client.edit_approval_rule(rule_id, 2, number("2.5")).await;
 
         assert!(result.is_ok());
 
@@ -406,6 +759,26 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_pending_approval_requests() {
+        let mock = ApiMock {
+            action: HttpVerb::Get,
+            body: APPROVALS_RESPONSE_BODY.into(),
+            path: format!("/v1/{}?pending=true", APPROVAL_RESOURCE),
+            response_code: 200,
+        };
+
+        let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
+
+        let result = client.approval_requests(true).await;
+
+        assert!(result.is_ok());
+
+        for mock in mock_results {
+            mock.assert_async().await;
+        }
+    }
+
     #[tokio::test]
     async fn test_approval_request_response() {
         let request_id = 1;
@@ -427,4 +800,20 @@ mod tests {
             mock.assert_async().await;
         }
     }
+
+    #[tokio::test]
+    async fn test_approval_request_response_sends_boolean() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", format!("/v1/{}/{}", APPROVAL_RESOURCE, 1).as_str())
+            .match_body(mockito::Matcher::JsonString(r#"{"approve": false}"#.into()))
+            .with_body("null")
+            .create_async()
+            .await;
+
+        let result = client.respond_to_approval_request(1, false).await;
+
+        assert!(result.is_ok());
+        mock.assert_async().await;
+    }
 }
@@ -2,6 +2,7 @@ use futures_util::Future;
 use serde::Deserialize;
 
 use super::super::{Client, HttpError, HttpVerb};
+use crate::num::Number;
 
 static FEE_RESOURCE: &str = "account/fee-rates";
 static WITHDRAW_FEE_RESOURCE: &str = "withdraw-fee";
@@ -19,19 +20,19 @@ pub struct Fees {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct WithdrawFee {
-    pub fee: f64,
+    pub fee: Number,
 }
 
 impl Client {
-    pub fn fees(self) -> impl Future<Output = Result<Fees, HttpError>> {
+    pub fn fees(&self) -> impl Future<Output = Result<Fees, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(FEE_RESOURCE);
         self.request(HttpVerb::Get, &url, None)
     }
 
     pub fn withdraw_fee(
-        self,
+        &self,
         currency: &str,
-    ) -> impl Future<Output = Result<WithdrawFee, HttpError>> {
+    ) -> impl Future<Output = Result<WithdrawFee, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(&format!("{}/{}", WITHDRAW_FEE_RESOURCE, currency));
         self.request(HttpVerb::Get, &url, None)
     }
@@ -97,11 +98,9 @@ mod tests {
 
         let result = client.fees().await;
 
-        assert!(result.is_err());
-        assert!(
-            result.unwrap_err().to_string()
-                == "Error while making request: `\"invalid token. check authorization header.\"`"
-        );
+        let err = result.unwrap_err();
+        assert!(matches!(err, HttpError::Unauthorized { status: 401, .. }));
+        assert!(!err.is_retryable());
 
         for mock in mock_results {
             mock.assert_async().await;
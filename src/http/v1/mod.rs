@@ -21,6 +21,8 @@ pub mod fee;
 pub mod order;
 /// Order book data.
 pub mod order_book;
+/// Validated orders with algorithm-specific options.
+pub mod order_request;
 /// If enabled, post-trade settlement features are available here including position, settlement,
 /// and risk information.
 pub mod post_trade_settlement;
@@ -60,11 +62,11 @@ mod tests {
     fn test_bool_from_int() {
         // Test 0 -> false
         let result = bool_from_int(json!(0)).unwrap();
-        assert_eq!(result, false);
+        assert!(!result);
 
         // Test 1 -> true
         let result = bool_from_int(json!(1)).unwrap();
-        assert_eq!(result, true);
+        assert!(result);
 
         // Test invalid value errors
         let result = bool_from_int(json!(2));
@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
 use futures_util::Future;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::super::{Client, HttpError, HttpVerb};
+use crate::num::{self, Number};
+use crate::trading::{Algorithm, Pair, RoutingType, Side, TimeInForce};
 
 static DONE_ORDERS_RESOURCE: &str = "orders/done";
 static LIST_ASSET_PAIRS_RESOURCE: &str = "markets/currency_pairs";
@@ -47,141 +49,291 @@ pub struct CryptoDepositAddress {
 pub struct ExecutedQuote {
     pub id: usize,
     pub side_id: usize,
-    pub action: String,
+    pub action: Side,
     pub algorithm_id: usize,
-    pub algorithm: String,
+    pub algorithm: Algorithm,
     #[serde(rename = "type")]
-    pub execution_type: String,
-    pub pair: String,
-    pub quantity: f64,
-    pub price: f64,
-    pub amount: f64,
-    pub net_market_amount: f64,
-    pub filled: f64,
-    pub vwap: f64,
-    pub filled_amount: f64,
-    pub fees: f64,
-    pub net_proceeds: f64,
+    pub execution_type: Algorithm,
+    pub pair: Pair,
+    pub quantity: Number,
+    pub price: Number,
+    pub amount: Number,
+    pub net_market_amount: Number,
+    pub filled: Number,
+    pub vwap: Number,
+    pub filled_amount: Number,
+    pub fees: Number,
+    pub net_proceeds: Number,
     pub status: String,
     pub status_code: usize,
-    pub routing_option: String,
-    pub routing_type: String,
-    pub time_in_force: String,
+    pub routing_option: RoutingType,
+    pub routing_type: RoutingType,
+    pub time_in_force: TimeInForce,
     pub expires: Option<String>,
     pub dateupdated: String,
     pub client_order_id: Option<String>,
     pub user_tx_id: Option<String>,
-    pub o_action: String,
+    pub o_action: Side,
     pub algo_id: usize,
     pub algorithm_options: Option<String>,
     pub destination: Option<String>,
 }
 
+/// Query of a `DELETE /v1/orders` request.
+#[derive(Clone, Debug, Serialize)]
+pub struct CancelOrdersQuery {
+    /// Comma separated order ids.
+    pub ids: String,
+}
+
+impl CancelOrdersQuery {
+    pub fn new(order_ids: &[usize]) -> Self {
+        let ids = order_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        CancelOrdersQuery { ids }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Order {
     pub id: usize,
-    pub quantity: f64,
-    pub price: f64,
-    pub o_action: String,
-    pub pair: String,
+    pub quantity: Number,
+    pub price: Number,
+    pub o_action: Side,
+    pub pair: Pair,
     #[serde(rename = "type")]
-    pub order_type: String,
-    pub vwap: f64,
-    pub filled: f64,
+    pub order_type: Algorithm,
+    pub vwap: Number,
+    pub filled: Number,
     pub status: OrderStatus,
+    pub client_order_id: Option<String>,
+}
+
+/// An order found by its client order id, either still open or done.
+#[derive(Clone, Debug)]
+pub enum ClientOrder {
+    Open(Order),
+    Done(Box<ExecutedQuote>),
+}
+
+impl ClientOrder {
+    /// The server id of the order.
+    pub fn id(&self) -> usize {
+        match self {
+            ClientOrder::Open(order) => order.id,
+            ClientOrder::Done(order) => order.id,
+        }
+    }
+}
+
+/// Body of a `POST /v1/orders/{side}` request. Build a validated one with
+/// [`OrderRequest::builder`](super::order_request::OrderRequest::builder).
+#[derive(Clone, Debug, Serialize)]
+pub struct PlaceOrderRequest {
+    pub currency_pair: Pair,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "num::serialize_option"
+    )]
+    pub price: Option<Number>,
+    #[serde(serialize_with = "num::serialize")]
+    pub quantity: Number,
+    pub routing_type: RoutingType,
+    pub algorithm_id: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_order_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_in_force: Option<TimeInForce>,
+    /// When a good-til-date order expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+    /// Stop price of a stop order.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "num::serialize_option"
+    )]
+    pub stop_amount: Option<Number>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "num::serialize_option"
+    )]
+    pub trailing_amount: Option<Number>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "num::serialize_option"
+    )]
+    pub trailing_percent: Option<Number>,
+    /// Seconds between the slices of a TWAP order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    /// Seconds a TWAP order runs for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_time: Option<u64>,
+    /// Quantity shown on the book by an iceberg order.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "num::serialize_option"
+    )]
+    pub display_quantity: Option<Number>,
 }
 
 impl Client {
     pub fn cancel_all_orders(
-        self,
-    ) -> impl Future<Output = Result<CancelledOrderResponse, HttpError>> {
+        &self,
+    ) -> impl Future<Output = Result<CancelledOrderResponse, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(OPEN_ORDERS_RESOURCE);
         self.request(HttpVerb::Delete, &url, None)
     }
 
     pub fn cancel_order(
-        self,
+        &self,
         order_id: usize,
-    ) -> impl Future<Output = Result<CancelledOrder, HttpError>> {
+    ) -> impl Future<Output = Result<CancelledOrder, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(&format!("{}/{}", ORDERS_RESOURCE, order_id));
         self.request(HttpVerb::Delete, &url, None)
     }
 
     pub fn cancel_orders(
-        self,
+        &self,
         order_ids: Vec<usize>,
-    ) -> impl Future<Output = Result<CancelledOrderResponse, HttpError>> {
-        // Create a comma separated list of order ids from the vector
-        let order_ids_query_param = order_ids
-            .iter()
-            .map(|id| id.to_string())
-            .collect::<Vec<String>>()
-            .join(",");
-
-        let url = self.url_for_v1_resource(&format!(
-            "{}?ids={}",
-            ORDERS_RESOURCE, order_ids_query_param
-        ));
+    ) -> impl Future<Output = Result<CancelledOrderResponse, HttpError>> + Send + 'static {
+        let url = self.url_for_v1_resource(ORDERS_RESOURCE);
+        let query = CancelOrdersQuery::new(&order_ids);
 
-        self.request(HttpVerb::Delete, &url, None)
+        self.request_with_query(HttpVerb::Delete, &url, &query)
     }
 
-    pub fn open_orders(self) -> impl Future<Output = Result<Vec<Order>, HttpError>> {
+    pub fn open_orders(
+        &self,
+    ) -> impl Future<Output = Result<Vec<Order>, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(ORDERS_RESOURCE);
         self.request(HttpVerb::Get, &url, None)
     }
 
+    /// Places an order without algorithm options, sending the price and quantity as given (e.g.
+    /// a zero price for a market order). Prefer [`Client::submit_order`], which validates the
+    /// options of every algorithm.
     #[allow(clippy::too_many_arguments)]
     pub fn place_order(
-        self,
-        side: &str,
-        currency_pair: &str,
-        price: f64,
-        quantity: f64,
-        routing_type: &str,
-        algorithm_id: usize,
+        &self,
+        side: Side,
+        currency_pair: impl Into<Pair>,
+        price: Number,
+        quantity: Number,
+        routing_type: RoutingType,
+        algorithm: Algorithm,
         client_order_id: Option<&str>,
-    ) -> impl Future<Output = Result<Order, HttpError>> {
-        let mut params = HashMap::new();
-        params.insert("currency_pair".to_string(), currency_pair.to_string());
-        params.insert("price".to_string(), price.to_string());
-        params.insert("quantity".to_string(), quantity.to_string());
-        params.insert("routing_type".to_string(), routing_type.to_string());
-        params.insert("algorithm_id".to_string(), algorithm_id.to_string());
-        if let Some(client_order_id) = client_order_id {
-            params.insert("client_order_id".to_string(), client_order_id.to_string());
-        }
-
-        let url = self.url_for_v1_resource(&format!("{}/{}", ORDERS_RESOURCE, side));
+    ) -> impl Future<Output = Result<Order, HttpError>> + Send + 'static {
+        let request = match (order_path(&side), algorithm.id()) {
+            (Err(e), _) => Err(e),
+            (_, None) => Err(HttpError::InvalidRequest(format!(
+                "Unknown algorithm: {}",
+                algorithm
+            ))),
+            (Ok(path), Some(algorithm_id)) => {
+                let body = PlaceOrderRequest {
+                    currency_pair: currency_pair.into(),
+                    price: Some(price),
+                    quantity,
+                    routing_type,
+                    algorithm_id,
+                    client_order_id: client_order_id.map(str::to_string),
+                    time_in_force: None,
+                    expires: None,
+                    stop_amount: None,
+                    trailing_amount: None,
+                    trailing_percent: None,
+                    interval: None,
+                    total_time: None,
+                    display_quantity: None,
+                };
+
+                let url = self.url_for_v1_resource(&path);
+                Ok(self.request_with_body(HttpVerb::Post, &url, &body))
+            }
+        };
 
-        self.request(HttpVerb::Post, &url, Some(&params))
+        async move { request?.await }
     }
 
-    pub fn order_status(self, order_id: &str) -> impl Future<Output = Result<Order, HttpError>> {
+    pub fn order_status(
+        &self,
+        order_id: &str,
+    ) -> impl Future<Output = Result<Order, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(&format!("{}/{}", ORDERS_RESOURCE, order_id));
 
         self.request(HttpVerb::Get, &url, None)
     }
 
-    pub fn done_orders(self) -> impl Future<Output = Result<Vec<ExecutedQuote>, HttpError>> {
+    /// Finds an order by the `client_order_id` it was placed with, looking through the open
+    /// orders and then the done orders. `None` when neither list holds it.
+    ///
+    /// Only the done orders sFOX returns by default, its most recent ones, are searched, so
+    /// this is meant for orders placed shortly before.
+    pub fn order_by_client_id(
+        &self,
+        client_order_id: &str,
+    ) -> impl Future<Output = Result<Option<ClientOrder>, HttpError>> + Send + 'static {
+        let open_orders = self.open_orders();
+        let done_orders = self.done_orders();
+        let client_order_id = client_order_id.to_string();
+
+        async move {
+            // sFOX reports orders placed without a client order id with an empty one.
+            let matches = |id: &Option<String>| {
+                id.as_deref().filter(|id| !id.is_empty()) == Some(client_order_id.as_str())
+            };
+
+            if let Some(order) = open_orders
+                .await?
+                .into_iter()
+                .find(|order| matches(&order.client_order_id))
+            {
+                return Ok(Some(ClientOrder::Open(order)));
+            }
+
+            Ok(done_orders
+                .await?
+                .into_iter()
+                .find(|order| matches(&order.client_order_id))
+                .map(|order| ClientOrder::Done(Box::new(order))))
+        }
+    }
+
+    pub fn done_orders(
+        &self,
+    ) -> impl Future<Output = Result<Vec<ExecutedQuote>, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(DONE_ORDERS_RESOURCE);
 
         self.request(HttpVerb::Get, &url, None)
     }
 
     pub fn list_asset_pairs(
-        self,
-    ) -> impl Future<Output = Result<HashMap<String, AssetPair>, HttpError>> {
+        &self,
+    ) -> impl Future<Output = Result<HashMap<String, AssetPair>, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(LIST_ASSET_PAIRS_RESOURCE);
 
         self.request(HttpVerb::Get, &url, None)
     }
 }
 
+/// The `orders/{side}` resource orders and quotes are executed on.
+pub(crate) fn order_path(side: &Side) -> Result<String, HttpError> {
+    match side {
+        Side::Unknown(side) => Err(HttpError::InvalidRequest(format!("Invalid side: {}", side))),
+        side => Ok(format!("{}/{}", ORDERS_RESOURCE, side)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::num::number;
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
     const CANCEL_PENDING_ORDER_RESPONSE_BODY: &str = r#"
@@ -349,17 +501,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_cancel_multiple_orders() {
-        let ids = vec![2, 3];
-        let ids_param = ids
-            .iter()
-            .map(|id| id.to_string())
-            .collect::<Vec<String>>()
-            .join(",");
-
         let mock = ApiMock {
             action: HttpVerb::Delete,
             body: ORDERS_RESPONSE_BODY.into(),
-            path: format!("/v1/{}?ids={}", ORDERS_RESOURCE, ids_param),
+            path: format!("/v1/{}?ids=2%2C3", ORDERS_RESOURCE),
             response_code: 200,
         };
 
@@ -376,17 +521,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_cancel_multiple_orders_failed() {
-        let ids = vec![2, 3];
-        let ids_param = ids
-            .iter()
-            .map(|id| id.to_string())
-            .collect::<Vec<String>>()
-            .join(",");
-
         let mock = ApiMock {
             action: HttpVerb::Delete,
             body: CANCEL_MULTIPLE_ORDERS_FAILED_RESPONSE_BODY.into(),
-            path: format!("/v1/{}?ids={}", ORDERS_RESOURCE, ids_param),
+            path: format!("/v1/{}?ids=2%2C3", ORDERS_RESOURCE),
             response_code: 400,
         };
 
@@ -394,12 +532,18 @@ mod tests {
 
         let result = client.cancel_orders(vec![2, 3]).await;
 
-        assert!(result.is_err());
-        let err = result.unwrap_err();
-        assert!(
-            err.to_string()
-                == "Error while making request: `\"the order ids provided were invalid or the orders were already done/canceled\"`"
-        );
+        match result.unwrap_err() {
+            HttpError::Api {
+                status, message, ..
+            } => {
+                assert_eq!(status, 400);
+                assert_eq!(
+                    message,
+                    "the order ids provided were invalid or the orders were already done/canceled"
+                );
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
 
         for mock in mock_results {
             mock.assert_async().await;
@@ -429,26 +573,127 @@ mod tests {
 
     #[tokio::test]
     async fn test_place_order() {
-        let side = "sell";
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", "/v1/orders/sell")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"currency_pair": "ethusd", "routing_type": "NetPrice", "algorithm_id": 100, "client_order_id": "123A"}"#.into(),
+            ))
+            .with_body(ORDER_RESPONSE_BODY)
+            .create_async()
+            .await;
 
-        let mock = ApiMock {
-            action: HttpVerb::Post,
-            body: ORDER_RESPONSE_BODY.into(),
-            path: format!("/v1/{}/{}", ORDERS_RESOURCE, side),
-            response_code: 200,
-        };
+        let result = client
+            .place_order(
+                Side::Sell,
+                "ETH/USD",
+                number("0.123"),
+                number("0.456"),
+                RoutingType::NetPrice,
+                Algorithm::Market,
+                "123A".into(),
+            )
+            .await;
 
-        let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
+        let order = result.unwrap();
+        assert_eq!(order.o_action, Side::Buy);
+        assert_eq!(order.order_type, Algorithm::Limit);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_place_order_rejects_unknown_side_and_algorithm() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
 
         let result = client
-            .place_order(side, "ethusd", 0.123, 0.456, "NetPrice", 100, "123A".into())
+            .place_order(
+                "sel".parse().unwrap(),
+                "ethusd",
+                number("1"),
+                number("1"),
+                RoutingType::NetPrice,
+                Algorithm::Limit,
+                None,
+            )
+            .await;
+        assert!(matches!(result, Err(HttpError::InvalidRequest(_))));
+
+        let result = client
+            .place_order(
+                Side::Buy,
+                "ethusd",
+                number("1"),
+                number("1"),
+                RoutingType::NetPrice,
+                Algorithm::Unknown("Moonshot".into()),
+                None,
+            )
+            .await;
+        assert!(matches!(result, Err(HttpError::InvalidRequest(_))));
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_place_order_sends_zero_price_market_order() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", "/v1/orders/buy")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"price": "0", "quantity": "1", "algorithm_id": 100}"#.into(),
+            ))
+            .with_body(ORDER_RESPONSE_BODY)
+            .create_async()
+            .await;
+
+        let result = client
+            .place_order(
+                Side::Buy,
+                "btcusd",
+                number("0"),
+                number("1"),
+                RoutingType::NetPrice,
+                Algorithm::Market,
+                None,
+            )
             .await;
 
         assert!(result.is_ok());
+        mock.assert_async().await;
+    }
 
-        for mock in mock_results {
-            mock.assert_async().await;
-        }
+    #[cfg(feature = "decimal")]
+    #[tokio::test]
+    async fn test_place_order_sends_exact_decimals() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", "/v1/orders/buy")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"price": "0.3", "quantity": "0.456"}"#.into(),
+            ))
+            .with_body(ORDER_RESPONSE_BODY)
+            .create_async()
+            .await;
+
+        let result = client
+            .place_order(
+                Side::Buy,
+                "ethusd",
+                number("0.1") + number("0.2"),
+                number("0.456"),
+                RoutingType::NetPrice,
+                Algorithm::Market,
+                None,
+            )
+            .await;
+
+        assert!(result.is_ok());
+        mock.assert_async().await;
     }
 
     #[tokio::test]
@@ -512,6 +757,42 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_order_by_client_id() {
+        let open_mock = ApiMock {
+            action: HttpVerb::Get,
+            body: OPEN_ORDERS_RESPONSE_BODY.into(),
+            path: format!("/v1/{}", ORDERS_RESOURCE),
+            response_code: 200,
+        };
+        let done_mock = ApiMock {
+            action: HttpVerb::Get,
+            body: DONE_ORDERS_RESPONSE_BODY.into(),
+            path: format!("/v1/{}", DONE_ORDERS_RESOURCE),
+            response_code: 200,
+        };
+
+        let (client, _server, _mocks) =
+            new_test_server_and_client(vec![open_mock, done_mock]).await;
+
+        let found = client
+            .order_by_client_id("94b0e7c4-0fa7-403d-a0d0-6c4ccec76630")
+            .await
+            .unwrap();
+        match found {
+            Some(ClientOrder::Done(order)) => assert_eq!(order.id, 701968334),
+            other => panic!("expected a done order, got {:?}", other),
+        }
+
+        // Empty client order ids never match.
+        assert!(client.order_by_client_id("").await.unwrap().is_none());
+        assert!(client
+            .order_by_client_id("missing")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
     #[tokio::test]
     async fn test_list_asset_pairs() {
         let mock = ApiMock {
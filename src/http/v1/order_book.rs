@@ -1,9 +1,12 @@
+use std::cmp::Ordering;
+
 use futures_util::Future;
-use serde::{Deserialize, Deserializer};
-use serde_json::Value;
+use serde::Deserialize;
 
 use super::Client;
 use crate::http::{HttpError, HttpVerb};
+use crate::num::{self, Number};
+use crate::trading::Side;
 
 static ORDER_BOOK_RESOURCE: &str = "markets/orderbook";
 
@@ -27,30 +30,56 @@ pub struct MarketMaking {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct OpenOrder {
-    pub price: f64,
-    #[serde(deserialize_with = "maybe_sci_notation_to_f64")]
-    pub volume: f64,
+    pub price: Number,
+    #[serde(deserialize_with = "num::from_str_or_number")]
+    pub volume: Number,
     pub exchange: String,
 }
 
-fn maybe_sci_notation_to_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let value = Value::deserialize(deserializer)?;
+impl OrderBook {
+    /// The average price of filling `quantity` against the book: buys walk the asks from the
+    /// lowest price up and sells walk the bids from the highest price down. `None` when the book
+    /// is too thin to fill the quantity, the quantity is not positive or the side is unknown.
+    pub fn estimate(&self, side: &Side, quantity: Number) -> Option<Number> {
+        let zero = Number::default();
+        let mut levels: Vec<&OpenOrder> = match side {
+            Side::Buy => self.asks.iter().collect(),
+            Side::Sell => self.bids.iter().collect(),
+            Side::Unknown(_) => return None,
+        };
+        if quantity <= zero {
+            return None;
+        }
+
+        levels.sort_by(|a, b| a.price.partial_cmp(&b.price).unwrap_or(Ordering::Equal));
+        if *side == Side::Sell {
+            levels.reverse();
+        }
 
-    match value {
-        Value::String(s) => {
-            let reformatted = s.replace('e', "E");
-            reformatted.parse::<f64>().map_err(serde::de::Error::custom)
+        let mut remaining = quantity;
+        let mut cost = zero;
+        for level in levels {
+            let filled = if level.volume < remaining {
+                level.volume
+            } else {
+                remaining
+            };
+            cost += filled * level.price;
+            remaining -= filled;
+            if remaining <= zero {
+                return Some(cost / quantity);
+            }
         }
-        Value::Number(n) => Ok(n.as_f64().unwrap()),
-        _ => Err(serde::de::Error::custom("Expected string or number")),
+
+        None
     }
 }
 
 impl Client {
-    pub fn order_book(self, pair: &str) -> impl Future<Output = Result<OrderBook, HttpError>> {
+    pub fn order_book(
+        &self,
+        pair: &str,
+    ) -> impl Future<Output = Result<OrderBook, HttpError>> + Send + 'static {
         let query_str = format!("{}/{}", ORDER_BOOK_RESOURCE, pair);
         let url = self.url_for_v1_resource(&query_str);
 
@@ -62,6 +91,7 @@ impl Client {
 mod tests {
     use super::*;
 
+    use crate::num::number;
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
     const ORDER_BOOK_RESPONSE_BODY: &str = r#"
@@ -185,6 +215,24 @@ mod tests {
         }
     "#;
 
+    #[test]
+    fn test_estimate() {
+        let book: OrderBook = serde_json::from_str(ORDER_BOOK_RESPONSE_BODY).unwrap();
+
+        // Filled entirely at the best ask.
+        assert_eq!(
+            book.estimate(&Side::Buy, number("1")),
+            Some(number("9455.55"))
+        );
+        // Walks from the best bid down: 1 at 9458.12 is only 1e-8, the rest at 9456.
+        let sell = book.estimate(&Side::Sell, number("1")).unwrap();
+        assert!(sell > number("9456") && sell < number("9456.0001"));
+
+        assert_eq!(book.estimate(&Side::Buy, number("1000000")), None);
+        assert_eq!(book.estimate(&Side::Buy, number("0")), None);
+        assert_eq!(book.estimate(&Side::Unknown("x".into()), number("1")), None);
+    }
+
     #[tokio::test]
     async fn test_order_book() {
         let pair = "ethusd";
@@ -0,0 +1,556 @@
+use std::time::Duration;
+
+use futures_util::Future;
+use thiserror::Error;
+
+use super::order::{order_path, Order, PlaceOrderRequest};
+use crate::http::{Client, HttpError, HttpVerb};
+use crate::num::Number;
+use crate::trading::{Algorithm, Pair, RoutingType, Side, TimeInForce};
+
+/// Why an [`OrderRequestBuilder`] could not build an order.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum OrderRequestError {
+    #[error("Invalid side: `{0}`")]
+    InvalidSide(String),
+    #[error("Unknown algorithm: `{0}`")]
+    UnknownAlgorithm(String),
+    #[error("{algorithm} orders require {option}")]
+    MissingOption {
+        algorithm: Algorithm,
+        option: &'static str,
+    },
+    #[error("{algorithm} orders do not accept {option}")]
+    UnsupportedOption {
+        algorithm: Algorithm,
+        option: &'static str,
+    },
+    #[error("Invalid {option}: {reason}")]
+    InvalidOption {
+        option: &'static str,
+        reason: String,
+    },
+}
+
+impl From<OrderRequestError> for HttpError {
+    fn from(e: OrderRequestError) -> Self {
+        HttpError::InvalidRequest(e.to_string())
+    }
+}
+
+/// An order whose options have been checked against its algorithm. Submit it with
+/// [`Client::submit_order`].
+#[derive(Clone, Debug)]
+pub struct OrderRequest {
+    side: Side,
+    algorithm: Algorithm,
+    body: PlaceOrderRequest,
+}
+
+impl OrderRequest {
+    /// Starts an order of `quantity` on `pair`, executed by `algorithm`. Routing defaults to
+    /// [`RoutingType::NetPrice`].
+    pub fn builder(
+        side: Side,
+        pair: impl Into<Pair>,
+        algorithm: Algorithm,
+        quantity: Number,
+    ) -> OrderRequestBuilder {
+        OrderRequestBuilder {
+            side,
+            pair: pair.into(),
+            algorithm,
+            quantity,
+            price: None,
+            routing_type: RoutingType::NetPrice,
+            client_order_id: None,
+            time_in_force: None,
+            expires: None,
+            stop_price: None,
+            trailing_amount: None,
+            trailing_percent: None,
+            twap_interval: None,
+            twap_duration: None,
+            display_quantity: None,
+        }
+    }
+
+    pub fn side(&self) -> &Side {
+        &self.side
+    }
+
+    pub fn algorithm(&self) -> &Algorithm {
+        &self.algorithm
+    }
+
+    /// The body sent to the server.
+    pub fn body(&self) -> &PlaceOrderRequest {
+        &self.body
+    }
+
+    pub(crate) fn body_mut(&mut self) -> &mut PlaceOrderRequest {
+        &mut self.body
+    }
+}
+
+/// Collects the options of an [`OrderRequest`] and validates them in
+/// [`OrderRequestBuilder::build`].
+///
+/// | Algorithm         | Requires                                     |
+/// |-------------------|----------------------------------------------|
+/// | `Limit`, `Hidden` | `price`                                      |
+/// | `Iceberg`         | `price`, `display_quantity`                  |
+/// | `Stop`            | `stop_price`                                 |
+/// | `TrailingStop`    | one of `trailing_amount`, `trailing_percent` |
+/// | `Twap`            | `twap_interval`, `twap_duration`             |
+///
+/// Those options are rejected on any other algorithm. `expires` goes with
+/// [`TimeInForce::GoodTilDate`] and nothing else. Algorithms only known by a raw id
+/// (`Algorithm::Unknown("309")`) are sent with whatever options are set.
+#[derive(Clone, Debug)]
+pub struct OrderRequestBuilder {
+    side: Side,
+    pair: Pair,
+    algorithm: Algorithm,
+    quantity: Number,
+    price: Option<Number>,
+    routing_type: RoutingType,
+    client_order_id: Option<String>,
+    time_in_force: Option<TimeInForce>,
+    expires: Option<String>,
+    stop_price: Option<Number>,
+    trailing_amount: Option<Number>,
+    trailing_percent: Option<Number>,
+    twap_interval: Option<Duration>,
+    twap_duration: Option<Duration>,
+    display_quantity: Option<Number>,
+}
+
+impl OrderRequestBuilder {
+    /// Limit price.
+    pub fn price(mut self, price: Number) -> Self {
+        self.price = Some(price);
+        self
+    }
+
+    pub fn routing_type(mut self, routing_type: RoutingType) -> Self {
+        self.routing_type = routing_type;
+        self
+    }
+
+    pub fn client_order_id(mut self, client_order_id: impl Into<String>) -> Self {
+        self.client_order_id = Some(client_order_id.into());
+        self
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = Some(time_in_force);
+        self
+    }
+
+    /// Expiry of a [`TimeInForce::GoodTilDate`] order, e.g. `2024-01-31T00:00:00Z`.
+    pub fn expires(mut self, expires: impl Into<String>) -> Self {
+        self.expires = Some(expires.into());
+        self
+    }
+
+    /// Price that triggers a [`Algorithm::Stop`] order.
+    pub fn stop_price(mut self, stop_price: Number) -> Self {
+        self.stop_price = Some(stop_price);
+        self
+    }
+
+    /// Distance, in the quote currency, a [`Algorithm::TrailingStop`] order trails the market.
+    pub fn trailing_amount(mut self, trailing_amount: Number) -> Self {
+        self.trailing_amount = Some(trailing_amount);
+        self
+    }
+
+    /// Distance, in percent, a [`Algorithm::TrailingStop`] order trails the market.
+    pub fn trailing_percent(mut self, trailing_percent: Number) -> Self {
+        self.trailing_percent = Some(trailing_percent);
+        self
+    }
+
+    /// Time between the slices of a [`Algorithm::Twap`] order, in whole seconds.
+    pub fn twap_interval(mut self, interval: Duration) -> Self {
+        self.twap_interval = Some(interval);
+        self
+    }
+
+    /// How long a [`Algorithm::Twap`] order runs, in whole seconds.
+    pub fn twap_duration(mut self, duration: Duration) -> Self {
+        self.twap_duration = Some(duration);
+        self
+    }
+
+    /// Quantity an [`Algorithm::Iceberg`] order shows on the book at a time.
+    pub fn display_quantity(mut self, display_quantity: Number) -> Self {
+        self.display_quantity = Some(display_quantity);
+        self
+    }
+
+    pub fn build(self) -> Result<OrderRequest, OrderRequestError> {
+        if self.side.is_unknown() {
+            return Err(OrderRequestError::InvalidSide(self.side.to_string()));
+        }
+        let algorithm_id = self
+            .algorithm
+            .id()
+            .ok_or_else(|| OrderRequestError::UnknownAlgorithm(self.algorithm.to_string()))?;
+
+        positive("quantity", Some(self.quantity))?;
+        positive("price", self.price)?;
+        positive("stop_price", self.stop_price)?;
+        positive("trailing_amount", self.trailing_amount)?;
+        positive("trailing_percent", self.trailing_percent)?;
+        positive("display_quantity", self.display_quantity)?;
+        self.check_algorithm_options()?;
+        self.check_time_in_force()?;
+
+        let body = PlaceOrderRequest {
+            currency_pair: self.pair,
+            price: self.price,
+            quantity: self.quantity,
+            routing_type: self.routing_type,
+            algorithm_id,
+            client_order_id: self.client_order_id,
+            time_in_force: self.time_in_force,
+            expires: self.expires,
+            stop_amount: self.stop_price,
+            trailing_amount: self.trailing_amount,
+            trailing_percent: self.trailing_percent,
+            interval: self.twap_interval.map(|interval| interval.as_secs()),
+            total_time: self.twap_duration.map(|duration| duration.as_secs()),
+            display_quantity: self.display_quantity,
+        };
+
+        Ok(OrderRequest {
+            side: self.side,
+            algorithm: self.algorithm,
+            body,
+        })
+    }
+
+    fn check_algorithm_options(&self) -> Result<(), OrderRequestError> {
+        if self.algorithm.is_unknown() {
+            return Ok(());
+        }
+
+        let options = [
+            (
+                "stop_price",
+                self.stop_price.is_some(),
+                &[Algorithm::Stop][..],
+            ),
+            (
+                "trailing_amount",
+                self.trailing_amount.is_some(),
+                &[Algorithm::TrailingStop][..],
+            ),
+            (
+                "trailing_percent",
+                self.trailing_percent.is_some(),
+                &[Algorithm::TrailingStop][..],
+            ),
+            (
+                "twap_interval",
+                self.twap_interval.is_some(),
+                &[Algorithm::Twap][..],
+            ),
+            (
+                "twap_duration",
+                self.twap_duration.is_some(),
+                &[Algorithm::Twap][..],
+            ),
+            (
+                "display_quantity",
+                self.display_quantity.is_some(),
+                &[Algorithm::Iceberg][..],
+            ),
+        ];
+        for (option, is_set, algorithms) in options {
+            if is_set && !algorithms.contains(&self.algorithm) {
+                return Err(self.unsupported(option));
+            }
+        }
+
+        match self.algorithm {
+            Algorithm::Limit | Algorithm::Hidden => self.require("price", self.price.is_some()),
+            Algorithm::Iceberg => {
+                self.require("price", self.price.is_some())?;
+                self.require("display_quantity", self.display_quantity.is_some())?;
+                if self.display_quantity > Some(self.quantity) {
+                    return Err(OrderRequestError::InvalidOption {
+                        option: "display_quantity",
+                        reason: "must not exceed the order quantity".into(),
+                    });
+                }
+                Ok(())
+            }
+            Algorithm::Stop => self.require("stop_price", self.stop_price.is_some()),
+            Algorithm::TrailingStop => match (self.trailing_amount, self.trailing_percent) {
+                (Some(_), Some(_)) => Err(OrderRequestError::InvalidOption {
+                    option: "trailing_percent",
+                    reason: "trailing_amount is already set".into(),
+                }),
+                (None, None) => Err(self.missing("trailing_amount or trailing_percent")),
+                _ => Ok(()),
+            },
+            Algorithm::Twap => {
+                let interval = self
+                    .twap_interval
+                    .ok_or_else(|| self.missing("twap_interval"))?;
+                let duration = self
+                    .twap_duration
+                    .ok_or_else(|| self.missing("twap_duration"))?;
+                if interval.as_secs() == 0 || interval > duration {
+                    return Err(OrderRequestError::InvalidOption {
+                        option: "twap_interval",
+                        reason: "must be at least a second and no longer than twap_duration".into(),
+                    });
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_time_in_force(&self) -> Result<(), OrderRequestError> {
+        match (&self.time_in_force, &self.expires) {
+            (Some(TimeInForce::GoodTilDate), None) => Err(OrderRequestError::InvalidOption {
+                option: "time_in_force",
+                reason: "GTD orders require expires".into(),
+            }),
+            (Some(TimeInForce::GoodTilDate), Some(_)) | (_, None) => Ok(()),
+            (_, Some(_)) => Err(OrderRequestError::InvalidOption {
+                option: "expires",
+                reason: "only GTD orders expire".into(),
+            }),
+        }
+    }
+
+    fn require(&self, option: &'static str, is_set: bool) -> Result<(), OrderRequestError> {
+        if is_set {
+            Ok(())
+        } else {
+            Err(self.missing(option))
+        }
+    }
+
+    fn missing(&self, option: &'static str) -> OrderRequestError {
+        OrderRequestError::MissingOption {
+            algorithm: self.algorithm.clone(),
+            option,
+        }
+    }
+
+    fn unsupported(&self, option: &'static str) -> OrderRequestError {
+        OrderRequestError::UnsupportedOption {
+            algorithm: self.algorithm.clone(),
+            option,
+        }
+    }
+}
+
+fn positive(option: &'static str, value: Option<Number>) -> Result<(), OrderRequestError> {
+    match value {
+        Some(value) if value <= Number::default() => Err(OrderRequestError::InvalidOption {
+            option,
+            reason: format!("must be positive, got {}", value),
+        }),
+        _ => Ok(()),
+    }
+}
+
+impl Client {
+    /// Submits a validated order to `POST /v1/orders/{side}`.
+    pub fn submit_order(
+        &self,
+        order: &OrderRequest,
+    ) -> impl Future<Output = Result<Order, HttpError>> + Send + 'static {
+        let request = order_path(&order.side).map(|path| {
+            let url = self.url_for_v1_resource(&path);
+            self.request_with_body(HttpVerb::Post, &url, &order.body)
+        });
+
+        async move { request?.await }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::num::number;
+    use crate::util::server::new_test_server_and_client;
+
+    const ORDER_RESPONSE_BODY: &str = r#"
+        {
+            "id": 123,
+            "quantity": 1,
+            "price": 10,
+            "o_action": "Sell",
+            "pair": "btcusd",
+            "type": "Trailing Stop",
+            "vwap": 0,
+            "filled": 0,
+            "status": "Started"
+        }
+    "#;
+
+    fn builder(algorithm: Algorithm) -> OrderRequestBuilder {
+        OrderRequest::builder(Side::Sell, "btcusd", algorithm, number("1"))
+    }
+
+    #[test]
+    fn test_required_options() {
+        let missing =
+            |algorithm: Algorithm, option| OrderRequestError::MissingOption { algorithm, option };
+
+        assert_eq!(
+            builder(Algorithm::Stop).build().unwrap_err(),
+            missing(Algorithm::Stop, "stop_price")
+        );
+        assert_eq!(
+            builder(Algorithm::Limit).build().unwrap_err(),
+            missing(Algorithm::Limit, "price")
+        );
+        assert_eq!(
+            builder(Algorithm::TrailingStop).build().unwrap_err(),
+            missing(
+                Algorithm::TrailingStop,
+                "trailing_amount or trailing_percent"
+            )
+        );
+        assert_eq!(
+            builder(Algorithm::Twap)
+                .twap_interval(Duration::from_secs(60))
+                .build()
+                .unwrap_err(),
+            missing(Algorithm::Twap, "twap_duration")
+        );
+
+        assert!(builder(Algorithm::Stop)
+            .stop_price(number("25000"))
+            .build()
+            .is_ok());
+        assert!(builder(Algorithm::Market).build().is_ok());
+    }
+
+    #[test]
+    fn test_options_of_other_algorithms_are_rejected() {
+        assert_eq!(
+            builder(Algorithm::Limit)
+                .price(number("1"))
+                .stop_price(number("1"))
+                .build()
+                .unwrap_err(),
+            OrderRequestError::UnsupportedOption {
+                algorithm: Algorithm::Limit,
+                option: "stop_price"
+            }
+        );
+
+        // Nothing is known about the options of raw algorithm ids.
+        assert!(builder(Algorithm::Unknown("309".into()))
+            .stop_price(number("1"))
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert!(matches!(
+            OrderRequest::builder(Side::Buy, "btcusd", Algorithm::Market, number("0")).build(),
+            Err(OrderRequestError::InvalidOption {
+                option: "quantity",
+                ..
+            })
+        ));
+        assert!(matches!(
+            builder(Algorithm::TrailingStop)
+                .trailing_amount(number("100"))
+                .trailing_percent(number("1"))
+                .build(),
+            Err(OrderRequestError::InvalidOption { .. })
+        ));
+        assert!(matches!(
+            builder(Algorithm::Twap)
+                .twap_interval(Duration::from_secs(600))
+                .twap_duration(Duration::from_secs(60))
+                .build(),
+            Err(OrderRequestError::InvalidOption {
+                option: "twap_interval",
+                ..
+            })
+        ));
+        assert!(matches!(
+            builder(Algorithm::Iceberg)
+                .price(number("1"))
+                .display_quantity(number("2"))
+                .build(),
+            Err(OrderRequestError::InvalidOption {
+                option: "display_quantity",
+                ..
+            })
+        ));
+        assert!(matches!(
+            builder(Algorithm::Market)
+                .time_in_force(TimeInForce::GoodTilDate)
+                .build(),
+            Err(OrderRequestError::InvalidOption { .. })
+        ));
+        assert!(matches!(
+            builder(Algorithm::Market)
+                .expires("2024-01-31T00:00:00Z")
+                .build(),
+            Err(OrderRequestError::InvalidOption {
+                option: "expires",
+                ..
+            })
+        ));
+        assert_eq!(
+            OrderRequest::builder(
+                "sel".parse().unwrap(),
+                "btcusd",
+                Algorithm::Market,
+                number("1")
+            )
+            .build()
+            .unwrap_err(),
+            OrderRequestError::InvalidSide("sel".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_submit_order_sends_algorithm_options() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", "/v1/orders/sell")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "currency_pair": "btcusd",
+                "quantity": "1",
+                "routing_type": "NetPrice",
+                "algorithm_id": 308,
+                "time_in_force": "GTD",
+                "expires": "2024-01-31T00:00:00Z",
+                "trailing_percent": "2.5"
+            })))
+            .with_body(ORDER_RESPONSE_BODY)
+            .create_async()
+            .await;
+
+        let order = builder(Algorithm::TrailingStop)
+            .trailing_percent(number("2.5"))
+            .time_in_force(TimeInForce::GoodTilDate)
+            .expires("2024-01-31T00:00:00Z")
+            .build()
+            .unwrap();
+
+        let result = client.submit_order(&order).await;
+
+        assert_eq!(result.unwrap().order_type, Algorithm::TrailingStop);
+        mock.assert_async().await;
+    }
+}
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 
 use futures_util::Future;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::Client;
 use crate::http::{HttpError, HttpVerb};
+use crate::num::{self, Number};
 
 static POST_TRADE_SETTLEMENT_RESOURCE: &str = "post-trade-settlement";
 static POST_TRADE_SETTLEMENT_INTEREST_RESOURCE: &str = "post-trade-settlement/interest";
@@ -55,53 +56,67 @@ pub struct WalletTransfer {
     pub to_wallet: String,
 }
 
+/// Body of a `POST /v1/account/transfer` request moving funds between wallets.
+#[derive(Clone, Debug, Serialize)]
+pub struct WalletTransferRequest {
+    pub currency: String,
+    #[serde(serialize_with = "num::serialize")]
+    pub quantity: Number,
+    pub from_wallet: String,
+    pub to_wallet: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PostTradeSettlementPositionsQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
 impl Client {
     pub fn post_trade_settlement(
-        self,
-    ) -> impl Future<Output = Result<PostTradeSettlement, HttpError>> {
+        &self,
+    ) -> impl Future<Output = Result<PostTradeSettlement, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(POST_TRADE_SETTLEMENT_RESOURCE);
 
         self.request(HttpVerb::Get, &url, None)
     }
 
     pub fn post_trade_settlement_interest(
-        self,
-    ) -> impl Future<Output = Result<HashMap<String, PostTradeSettlementInterest>, HttpError>> {
+        &self,
+    ) -> impl Future<Output = Result<HashMap<String, PostTradeSettlementInterest>, HttpError>>
+           + Send
+           + 'static {
         let url = self.url_for_v1_resource(POST_TRADE_SETTLEMENT_INTEREST_RESOURCE);
         self.request(HttpVerb::Get, &url, None)
     }
 
     pub fn post_trade_settlement_positions(
-        self,
+        &self,
         status: Option<String>,
-    ) -> impl Future<Output = Result<PostTradeSettlementPositions, HttpError>> {
-        let resource = POST_TRADE_SETTLEMENT_POSITIONS_RESOURCE;
+    ) -> impl Future<Output = Result<PostTradeSettlementPositions, HttpError>> + Send + 'static
+    {
+        let url = self.url_for_v1_resource(POST_TRADE_SETTLEMENT_POSITIONS_RESOURCE);
+        let query = PostTradeSettlementPositionsQuery { status };
 
-        let query_str = match status {
-            Some(s) => format!("{}?status={}", resource, s),
-            None => resource.into(),
-        };
-
-        let url = self.url_for_v1_resource(&query_str);
-
-        self.request(HttpVerb::Get, &url, None)
+        self.request_with_query(HttpVerb::Get, &url, &query)
     }
 
     pub fn wallet_transfer(
-        self,
+        &self,
         currency: String,
-        quantity: f64,
+        quantity: Number,
         from_wallet: String,
         to_wallet: String,
-    ) -> impl Future<Output = Result<WalletTransfer, HttpError>> {
-        let mut params = HashMap::new();
-        params.insert("currency", currency);
-        params.insert("quantity", quantity.to_string());
-        params.insert("from_wallet", from_wallet);
-        params.insert("to_wallet", to_wallet);
+    ) -> impl Future<Output = Result<WalletTransfer, HttpError>> + Send + 'static {
+        let body = WalletTransferRequest {
+            currency,
+            quantity,
+            from_wallet,
+            to_wallet,
+        };
 
         let url = self.url_for_v1_resource("account/transfer");
-        self.request(HttpVerb::Post, &url, None)
+        self.request_with_body(HttpVerb::Post, &url, &body)
     }
 }
 
@@ -109,8 +124,20 @@ impl Client {
 mod tests {
     use super::*;
 
+    use crate::num::number;
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
+    const WALLET_TRANSFER_RESPONSE_BODY: &str = r#"
+        {
+            "from_transaction_id": 1,
+            "to_transaction_id": 2,
+            "currency": "btc",
+            "quantity": "0.5",
+            "from_wallet": "trading",
+            "to_wallet": "collateral"
+        }
+    "#;
+
     const POST_TRADE_SETTLEMENT_RESPONSE_BODY: &str = r#"
         {
             "exposure": 1000378.13,
@@ -220,4 +247,30 @@ mod tests {
             mock.assert_async().await;
         }
     }
+
+    #[tokio::test]
+    async fn test_wallet_transfer() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", "/v1/account/transfer")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"currency": "btc", "from_wallet": "trading", "to_wallet": "collateral"}"#
+                    .into(),
+            ))
+            .with_body(WALLET_TRANSFER_RESPONSE_BODY)
+            .create_async()
+            .await;
+
+        let result = client
+            .wallet_transfer(
+                "btc".into(),
+                number("0.5"),
+                "trading".into(),
+                "collateral".into(),
+            )
+            .await;
+
+        assert!(result.is_ok());
+        mock.assert_async().await;
+    }
 }
@@ -1,87 +1,258 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use futures_util::Future;
-use serde_derive::Deserialize;
-use std::collections::HashMap;
-use std::pin::Pin;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::http::{Client, HttpError, HttpVerb};
+use crate::num::{self, Number};
+use crate::trading::{Pair, Side};
 
-use super::order::ExecutedQuote;
+use super::order::{order_path, ExecutedQuote};
 
 static REQUEST_FOR_QUOTE_RESOURCE: &str = "quote";
-static EXECUTE_QUOTE_RESOURCE: &str = "orders/buy";
+
+/// Why a quote could not be executed.
+#[derive(Debug, Error)]
+pub enum QuoteError {
+    #[error("Quote `{quote_id}` expired at {date_expiry}")]
+    Expired {
+        quote_id: String,
+        date_expiry: String,
+    },
+    #[error("Quote `{quote_id}` has no {side} price")]
+    MissingPrice { quote_id: String, side: Side },
+    #[error("The order book cannot fill {quantity} {pair}")]
+    InsufficientDepth { pair: Pair, quantity: Number },
+    #[error("Quoted price {quoted} is worse than the order book estimate {estimate}")]
+    UnfavorablePrice { quoted: Number, estimate: Number },
+    #[error(transparent)]
+    Http(#[from] HttpError),
+}
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Quote {
     pub quote_id: String,
-    pub quantity: f64,
-    pub amount: f64,
-    pub pair: String,
-    pub side: String,
+    pub quantity: Number,
+    pub amount: Number,
+    pub pair: Pair,
+    pub side: Side,
     pub date_expiry: String,
     pub date_quote: String,
-    pub buy_price: Option<f64>,
-    pub sell_price: Option<f64>,
+    pub buy_price: Option<Number>,
+    pub sell_price: Option<Number>,
 }
 
-impl Client {
-    pub fn request_for_quote(
-        self,
-        pair: &str,
-        side: &str,
-        quantity: Option<f64>,
-        amount: Option<f64>,
-        client_quote_id: Option<&str>,
-    ) -> Pin<Box<dyn Future<Output = Result<Quote, HttpError>> + Send>> {
-        let mut params = HashMap::new();
-        params.insert("pair".into(), pair.to_string());
-        params.insert("side".into(), side.to_string());
-
-        if quantity.is_none() && amount.is_none() {
-            return Box::pin(async {
-                Err(HttpError::InvalidRequest(
-                    "Either quantity or amount must be provided".into(),
-                ))
-            });
-        }
+impl Quote {
+    /// When the quote expires, parsed from `date_expiry`. `None` if the timestamp is not in the
+    /// RFC 3339 form sFOX sends.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        parse_timestamp(&self.date_expiry)
+    }
 
-        if let Some(quantity) = quantity {
-            params.insert("quantity".into(), quantity.to_string());
-        }
+    /// How long until the quote expires; zero once it has. `None` if the expiry can't be parsed.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        self.expires_at().map(|expiry| {
+            expiry
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO)
+        })
+    }
 
-        if let Some(amount) = amount {
-            params.insert("amount".into(), amount.to_string());
-        }
+    /// Whether the quote has expired. A quote whose expiry can't be parsed is left for the server
+    /// to judge and is not considered expired.
+    pub fn is_expired(&self) -> bool {
+        self.time_remaining() == Some(Duration::ZERO)
+    }
 
-        if let Some(client_quote_id) = client_quote_id {
-            params.insert("client_quote_id".into(), client_quote_id.to_string());
+    /// The quoted price for the quote's side: `buy_price` for buys and `sell_price` for sells.
+    pub fn price(&self) -> Option<Number> {
+        match self.side {
+            Side::Buy => self.buy_price,
+            Side::Sell => self.sell_price,
+            Side::Unknown(_) => None,
         }
+    }
+}
+
+/// Body of a `POST /v1/quote` request. At least one of `quantity` and `amount` is required.
+#[derive(Clone, Debug, Serialize)]
+pub struct QuoteRequest {
+    pub pair: Pair,
+    pub side: Side,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "num::serialize_option"
+    )]
+    pub quantity: Option<Number>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "num::serialize_option"
+    )]
+    pub amount: Option<Number>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_quote_id: Option<String>,
+}
+
+/// Body of a request executing a quote.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExecuteQuoteRequest {
+    pub currency_pair: Pair,
+    #[serde(serialize_with = "num::serialize")]
+    pub quantity: Number,
+    pub quote_id: String,
+}
+
+impl Client {
+    pub fn request_for_quote(
+        &self,
+        pair: impl Into<Pair>,
+        side: Side,
+        quantity: Option<Number>,
+        amount: Option<Number>,
+        client_quote_id: Option<&str>,
+    ) -> impl Future<Output = Result<Quote, HttpError>> + Send + 'static {
+        let body = QuoteRequest {
+            pair: pair.into(),
+            side,
+            quantity,
+            amount,
+            client_quote_id: client_quote_id.map(str::to_string),
+        };
 
         let url = self.url_for_v1_resource(REQUEST_FOR_QUOTE_RESOURCE);
+        let request = if body.quantity.is_none() && body.amount.is_none() {
+            Err(HttpError::InvalidRequest(
+                "Either quantity or amount must be provided".into(),
+            ))
+        } else if body.side.is_unknown() {
+            Err(HttpError::InvalidRequest(format!(
+                "Invalid side: {}",
+                body.side
+            )))
+        } else {
+            Ok(self.request_with_body(HttpVerb::Post, &url, &body))
+        };
 
-        Box::pin(self.request(HttpVerb::Post, &url, Some(&params)))
+        async move { request?.await }
     }
 
+    /// Executes a quote on `POST /v1/orders/{side}`. The side must be the one the quote was
+    /// requested for. Prefer [`Client::accept_quote`], which takes it from the quote and checks
+    /// the expiry first.
     pub fn execute_quote(
-        self,
-        currency_pair: &str,
-        quantity: f64,
+        &self,
+        side: Side,
+        currency_pair: impl Into<Pair>,
+        quantity: Number,
         quote_id: &str,
-    ) -> impl Future<Output = Result<ExecutedQuote, HttpError>> {
-        let mut params = HashMap::new();
-        params.insert("currency_pair".into(), currency_pair.to_string());
-        params.insert("quantity".into(), quantity.to_string());
-        params.insert("quote_id".into(), quote_id.to_string());
+    ) -> impl Future<Output = Result<ExecutedQuote, HttpError>> + Send + 'static {
+        let body = ExecuteQuoteRequest {
+            currency_pair: currency_pair.into(),
+            quantity,
+            quote_id: quote_id.to_string(),
+        };
+
+        let request = order_path(&side).map(|path| {
+            let url = self.url_for_v1_resource(&path);
+            self.request_with_body(HttpVerb::Post, &url, &body)
+        });
 
-        let url = self.url_for_v1_resource(EXECUTE_QUOTE_RESOURCE);
+        async move { request?.await }
+    }
+
+    /// Executes a quote on the side it was requested for. Expired quotes are rejected with
+    /// [`QuoteError::Expired`] without sending a request.
+    pub fn accept_quote(
+        &self,
+        quote: &Quote,
+    ) -> impl Future<Output = Result<ExecutedQuote, QuoteError>> + Send + 'static {
+        let request = if quote.is_expired() {
+            Err(QuoteError::Expired {
+                quote_id: quote.quote_id.clone(),
+                date_expiry: quote.date_expiry.clone(),
+            })
+        } else {
+            Ok(self.execute_quote(
+                quote.side.clone(),
+                quote.pair.clone(),
+                quote.quantity,
+                &quote.quote_id,
+            ))
+        };
 
-        self.request(HttpVerb::Post, &url, Some(&params))
+        async move { Ok(request?.await?) }
     }
 }
 
+/// Parses an RFC 3339 timestamp such as `2023-05-31T19:26:58.595Z`.
+pub(crate) fn parse_timestamp(s: &str) -> Option<SystemTime> {
+    let (date, time) = s.split_once(['T', 't', ' '])?;
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+
+    let (time, offset) = if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        (time, 0)
+    } else {
+        let at = time.rfind(['+', '-'])?;
+        let (time, offset) = time.split_at(at);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (hours, minutes) = offset[1..].split_once(':')?;
+        let offset = hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60;
+        (time, sign * offset)
+    };
+
+    let (clock, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut clock_parts = clock.splitn(3, ':');
+    let hour: i64 = clock_parts.next()?.parse().ok()?;
+    let minute: i64 = clock_parts.next()?.parse().ok()?;
+    let second: i64 = clock_parts.next()?.parse().ok()?;
+
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let nanos = fraction
+        .chars()
+        .chain(std::iter::repeat('0'))
+        .take(9)
+        .collect::<String>()
+        .parse::<u32>()
+        .ok()?;
+
+    let seconds =
+        days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second - offset;
+
+    u64::try_from(seconds)
+        .ok()
+        .map(|seconds| UNIX_EPOCH + Duration::new(seconds, nanos))
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::num::number;
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
     const REQUEST_FOR_QUOTE_RESPONSE_BODY: &str = r#"
@@ -143,7 +314,13 @@ mod tests {
         let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
 
         let result = client
-            .request_for_quote("btcusd", "sell", Some(1.0), Some(1.0), Some("123"))
+            .request_for_quote(
+                "btcusd",
+                Side::Sell,
+                Some(number("1.0")),
+                Some(number("1.0")),
+                Some("123"),
+            )
             .await;
 
         assert!(result.is_ok());
@@ -153,18 +330,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_request_sends_numbers_as_strings() {
+        // The same body with and without the `decimal` feature.
+        let request = QuoteRequest {
+            pair: "btcusd".into(),
+            side: Side::Buy,
+            quantity: Some(number("1.50")),
+            amount: None,
+            client_quote_id: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"pair":"btcusd","side":"buy","quantity":"1.5"}"#
+        );
+
+        let request = ExecuteQuoteRequest {
+            currency_pair: "btcusd".into(),
+            quantity: number("0.00000015"),
+            quote_id: "123".into(),
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"currency_pair":"btcusd","quantity":"0.00000015","quote_id":"123"}"#
+        );
+    }
+
+    fn quote(side: Side, date_expiry: &str) -> Quote {
+        let mut quote: Quote = serde_json::from_str(REQUEST_FOR_QUOTE_RESPONSE_BODY).unwrap();
+        quote.side = side;
+        quote.date_expiry = date_expiry.into();
+        quote
+    }
+
     #[tokio::test]
     async fn test_execute_quote() {
         let mock = ApiMock {
             action: HttpVerb::Post,
             body: EXECUTE_QUOTE_RESPONSE_BODY.into(),
-            path: format!("/v1/{}", EXECUTE_QUOTE_RESOURCE),
+            path: "/v1/orders/buy".into(),
             response_code: 201,
         };
 
         let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
 
-        let result = client.execute_quote("btcusd", 1.0, "123").await;
+        let result = client
+            .execute_quote(Side::Buy, "btcusd", number("1.0"), "123")
+            .await;
 
         assert!(result.is_ok());
 
@@ -172,4 +384,76 @@ mod tests {
             mock.assert_async().await;
         }
     }
+
+    #[tokio::test]
+    async fn test_accept_quote_routes_on_side() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let sell = server
+            .mock("POST", "/v1/orders/sell")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"currency_pair": "btcusd", "quote_id": "165c404c-ffe9-11ed-b8ed-0a170e3de1bd"}"#
+                    .into(),
+            ))
+            .with_status(201)
+            .with_body(EXECUTE_QUOTE_RESPONSE_BODY)
+            .create_async()
+            .await;
+        let buy = server
+            .mock("POST", "/v1/orders/buy")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let quote = quote(Side::Sell, "2999-01-01T00:00:00.000Z");
+        assert!(client.accept_quote(&quote).await.is_ok());
+
+        sell.assert_async().await;
+        buy.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_accept_quote_rejects_expired_quote() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let quote: Quote = serde_json::from_str(REQUEST_FOR_QUOTE_RESPONSE_BODY).unwrap();
+        assert!(quote.is_expired());
+
+        let result = client.accept_quote(&quote).await;
+        assert!(matches!(result, Err(QuoteError::Expired { .. })));
+
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_quote_price() {
+        assert_eq!(quote(Side::Buy, "").price(), Some(number("27044.7156")));
+        assert_eq!(quote(Side::Sell, "").price(), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let at = |seconds: u64, nanos: u32| Some(UNIX_EPOCH + Duration::new(seconds, nanos));
+
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), at(0, 0));
+        assert_eq!(
+            parse_timestamp("2023-05-31T19:26:58.595Z"),
+            at(1_685_561_218, 595_000_000)
+        );
+        assert_eq!(
+            parse_timestamp("2023-05-31T21:26:58.595+02:00"),
+            at(1_685_561_218, 595_000_000)
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-29T12:00:00-05:30"),
+            at(1_709_227_800, 0)
+        );
+        assert_eq!(parse_timestamp("2023-13-01T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2023-05-31"), None);
+        assert_eq!(parse_timestamp("not a date"), None);
+    }
 }
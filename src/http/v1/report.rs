@@ -1,7 +1,5 @@
-use std::collections::HashMap;
-
-use futures_util::Future;
-use serde_derive::Deserialize;
+use futures_util::{stream, Future, Stream, TryStreamExt};
+use serde_derive::{Deserialize, Serialize};
 
 use crate::http::{Client, HttpError, HttpVerb};
 
@@ -9,6 +7,9 @@ static TRANSACTION_HISTORY_RESOURCE: &str = "account/transactions";
 static ORDERS_REPORT_RESOURCE: &str = "orders/buy";
 static MONTHLY_SUMMARY_BY_ASSET_RESOURCE: &str = "users/reports/tax-currency-summary";
 
+/// Transactions requested per page by [`Client::transaction_history_stream`].
+pub const DEFAULT_TRANSACTION_PAGE_SIZE: usize = 100;
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct TransactionHistory {
     pub id: usize,
@@ -48,75 +49,249 @@ pub enum TransactionStatus {
     AdminHoldPendingReview,
 }
 
+/// Query for [`Client::transaction_history_by`]. Unset fields are left out of the request.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TransactionHistoryQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub types: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct OrdersReportQuery {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MonthlySummaryByAssetQuery {
+    pub currency: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<usize>,
+}
+
+/// Filter for [`Client::transaction_history_stream`].
+#[derive(Clone, Debug, Default)]
+pub struct TransactionHistoryFilter {
+    /// Start of the range. Can't be combined with a [`TransactionHistoryCursor::Timestamp`],
+    /// which sets it.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub types: Option<String>,
+    /// Transactions requested per page; [`DEFAULT_TRANSACTION_PAGE_SIZE`] when unset.
+    pub page_size: Option<usize>,
+    /// Where a previous walk left off. An offset is only meaningful with the same filter as the
+    /// walk it was taken from.
+    pub cursor: Option<TransactionHistoryCursor>,
+}
+
+/// A saved position in the transaction history.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TransactionHistoryCursor {
+    /// Number of transactions already read; the walk continues at this offset.
+    Offset(usize),
+    /// The largest `timestamp` already read and the ids of the transactions read with it. The
+    /// timestamp is sent as `from`, and transactions from that millisecond on are yielded unless
+    /// their id is listed.
+    Timestamp { timestamp: usize, ids: Vec<usize> },
+}
+
+/// A page of [`Client::transaction_history_pages`].
+#[derive(Clone, Debug)]
+pub struct TransactionHistoryPage {
+    pub transactions: Vec<TransactionHistory>,
+    /// Where to resume after this page: a timestamp if the walk started from one, otherwise an
+    /// offset.
+    pub cursor: TransactionHistoryCursor,
+}
+
+/// Paging state of a transaction history walk.
+struct TransactionPages {
+    client: Client,
+    query: TransactionHistoryQuery,
+    cursor: TransactionHistoryCursor,
+    rejected: Option<HttpError>,
+    done: bool,
+}
+
+impl TransactionPages {
+    async fn next_page(mut self) -> Result<Option<(TransactionHistoryPage, Self)>, HttpError> {
+        if let Some(e) = self.rejected.take() {
+            return Err(e);
+        }
+        if self.done {
+            return Ok(None);
+        }
+
+        let page = self.client.transaction_history_by(&self.query).await?;
+        let page_size = self.query.limit.unwrap_or(DEFAULT_TRANSACTION_PAGE_SIZE);
+
+        self.done = page.len() < page_size;
+        let offset = self.query.offset.unwrap_or(0) + page.len();
+        self.query.offset = Some(offset);
+
+        let transactions = match &mut self.cursor {
+            TransactionHistoryCursor::Offset(cursor) => {
+                *cursor = offset;
+                page
+            }
+            TransactionHistoryCursor::Timestamp { timestamp, ids } => {
+                let page: Vec<TransactionHistory> = page
+                    .into_iter()
+                    .filter(|tx| {
+                        tx.timestamp > *timestamp
+                            || (tx.timestamp == *timestamp && !ids.contains(&tx.id))
+                    })
+                    .collect();
+
+                for tx in &page {
+                    if tx.timestamp > *timestamp {
+                        *timestamp = tx.timestamp;
+                        ids.clear();
+                    }
+                    if tx.timestamp == *timestamp {
+                        ids.push(tx.id);
+                    }
+                }
+                page
+            }
+        };
+
+        let page = TransactionHistoryPage {
+            transactions,
+            cursor: self.cursor.clone(),
+        };
+        Ok(Some((page, self)))
+    }
+}
+
 impl Client {
     pub fn transaction_history(
-        self,
+        &self,
         from: Option<String>,
         to: Option<String>,
         limit: Option<usize>,
         offset: Option<usize>,
         types: Option<String>,
-    ) -> impl Future<Output = Result<Vec<TransactionHistory>, HttpError>> {
-        let query_str = self.url_for_v1_resource(TRANSACTION_HISTORY_RESOURCE);
+    ) -> impl Future<Output = Result<Vec<TransactionHistory>, HttpError>> + Send + 'static {
+        let query = TransactionHistoryQuery {
+            from,
+            to,
+            limit,
+            offset,
+            types,
+        };
 
-        let mut params = HashMap::new();
-        if let Some(from) = from {
-            params.insert("from".to_string(), from);
-        }
-        if let Some(to) = to {
-            params.insert("to".to_string(), to);
-        }
-        if let Some(limit) = limit {
-            params.insert("limit".to_string(), limit.to_string());
-        }
+        self.transaction_history_by(&query)
+    }
 
-        if let Some(offset) = offset {
-            params.insert("offset".to_string(), offset.to_string());
-        }
-        if let Some(types) = types {
-            params.insert("types".to_string(), types);
-        }
+    /// Same as [`Client::transaction_history`], taking the query as a struct so it can be
+    /// built up and reused across pages.
+    pub fn transaction_history_by(
+        &self,
+        query: &TransactionHistoryQuery,
+    ) -> impl Future<Output = Result<Vec<TransactionHistory>, HttpError>> + Send + 'static {
+        let url = self.url_for_v1_resource(TRANSACTION_HISTORY_RESOURCE);
+
+        self.request_with_query(HttpVerb::Get, &url, query)
+    }
+
+    /// Every transaction matching `filter`, one page at a time. Pages are only requested as
+    /// the stream is polled and go through the client's rate limiter and retry policy.
+    ///
+    /// The stream ends after the first page shorter than the page size, or after the first
+    /// error. To resume later, read the pages with [`Client::transaction_history_pages`] and
+    /// save the [`TransactionHistoryCursor`] of the last one.
+    pub fn transaction_history_stream(
+        &self,
+        filter: TransactionHistoryFilter,
+    ) -> impl Stream<Item = Result<TransactionHistory, HttpError>> + Send + 'static {
+        self.transaction_history_pages(filter)
+            .map_ok(|page| stream::iter(page.transactions.into_iter().map(Ok)))
+            .try_flatten()
+    }
 
-        self.request(HttpVerb::Get, &query_str, None)
+    /// Same as [`Client::transaction_history_stream`], yielding each page with the cursor to
+    /// resume after it. A filter with both `from` and a timestamp cursor yields an error.
+    pub fn transaction_history_pages(
+        &self,
+        filter: TransactionHistoryFilter,
+    ) -> impl Stream<Item = Result<TransactionHistoryPage, HttpError>> + Send + 'static {
+        let rejected = match (&filter.from, &filter.cursor) {
+            (Some(_), Some(TransactionHistoryCursor::Timestamp { .. })) => {
+                Some(HttpError::InvalidRequest(
+                    "A timestamp cursor can't be combined with `from`".into(),
+                ))
+            }
+            _ => None,
+        };
+
+        let (from, offset) = match &filter.cursor {
+            Some(TransactionHistoryCursor::Offset(offset)) => (filter.from, Some(*offset)),
+            Some(TransactionHistoryCursor::Timestamp { timestamp, .. }) => {
+                (Some(timestamp.to_string()), None)
+            }
+            None => (filter.from, None),
+        };
+
+        let pages = TransactionPages {
+            client: self.clone(),
+            query: TransactionHistoryQuery {
+                from,
+                to: filter.to,
+                limit: Some(filter.page_size.unwrap_or(DEFAULT_TRANSACTION_PAGE_SIZE)),
+                offset,
+                types: filter.types,
+            },
+            cursor: filter.cursor.unwrap_or(TransactionHistoryCursor::Offset(0)),
+            rejected,
+            done: false,
+        };
+
+        stream::try_unfold(pages, TransactionPages::next_page)
     }
 
     pub fn orders_report(
-        self,
+        &self,
         end: usize,
         start: usize,
-    ) -> impl Future<Output = Result<String, HttpError>> {
-        let query_str = self.url_for_v1_resource(ORDERS_REPORT_RESOURCE);
-
-        let mut params = HashMap::new();
-        params.insert("start".to_string(), start);
-        params.insert("end".to_string(), end);
+    ) -> impl Future<Output = Result<String, HttpError>> + Send + 'static {
+        let url = self.url_for_v1_resource(ORDERS_REPORT_RESOURCE);
+        let query = OrdersReportQuery { start, end };
 
-        self.request_text(HttpVerb::Get, &query_str, None)
+        self.request_text_with_query(HttpVerb::Get, &url, &query)
     }
 
     pub fn monthly_summary_by_asset(
-        self,
+        &self,
         currency: String,
         end: Option<usize>,
         start: Option<usize>,
-    ) -> impl Future<Output = Result<String, HttpError>> {
-        let query_str = self.url_for_v1_resource(MONTHLY_SUMMARY_BY_ASSET_RESOURCE);
-
-        let mut params = HashMap::new();
-        params.insert("currency".to_string(), currency);
-        if let Some(ts) = end {
-            params.insert("end".to_string(), ts.to_string());
-        }
-        if let Some(ts) = start {
-            params.insert("start".to_string(), ts.to_string());
-        }
+    ) -> impl Future<Output = Result<String, HttpError>> + Send + 'static {
+        let url = self.url_for_v1_resource(MONTHLY_SUMMARY_BY_ASSET_RESOURCE);
+        let query = MonthlySummaryByAssetQuery {
+            currency,
+            start,
+            end,
+        };
 
-        self.request_text(HttpVerb::Get, &query_str, Some(&params))
+        self.request_text_with_query(HttpVerb::Get, &url, &query)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use futures_util::StreamExt;
+
     use super::*;
 
     use crate::util::server::{new_test_server_and_client, ApiMock};
@@ -183,12 +358,303 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_transaction_history_query() {
+        let mock = ApiMock {
+            action: HttpVerb::Get,
+            body: TRANSACTION_HISTORY_RESPONSE_BODY.into(),
+            path: format!(
+                "/v1/{}?from=2021-10-01T00%3A00%3A00Z&limit=50&offset=100&types=buy%2Csell",
+                TRANSACTION_HISTORY_RESOURCE
+            ),
+            response_code: 200,
+        };
+
+        let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
+
+        let query = TransactionHistoryQuery {
+            from: Some("2021-10-01T00:00:00Z".into()),
+            limit: Some(50),
+            offset: Some(100),
+            types: Some("buy,sell".into()),
+            ..Default::default()
+        };
+        let result = client.transaction_history_by(&query).await;
+
+        assert!(result.is_ok());
+
+        for mock in mock_results {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_monthly_summary_by_asset_omits_unset_range() {
+        let mock = ApiMock {
+            action: HttpVerb::Get,
+            body: MONTHLY_SUMMARY_BY_ASSET_RESPONSE_BODY.into(),
+            path: format!("/v1/{}?currency=usd", MONTHLY_SUMMARY_BY_ASSET_RESOURCE),
+            response_code: 200,
+        };
+
+        let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
+
+        let result = client
+            .monthly_summary_by_asset("usd".into(), None, None)
+            .await;
+
+        assert!(result.is_ok());
+
+        for mock in mock_results {
+            mock.assert_async().await;
+        }
+    }
+
+    /// A page of transactions with the given ids, each stamped `1634751361000 + id`.
+    fn transaction_page(ids: std::ops::Range<usize>) -> String {
+        let page: Vec<serde_json::Value> = ids
+            .map(|id| {
+                let mut tx: Vec<serde_json::Value> =
+                    serde_json::from_str(TRANSACTION_HISTORY_RESPONSE_BODY).unwrap();
+                tx[0]["id"] = id.into();
+                tx[0]["timestamp"] = (1634751361000 + id).into();
+                tx.remove(0)
+            })
+            .collect();
+
+        serde_json::to_string(&page).unwrap()
+    }
+
+    fn page_mock(query: &str, body: String) -> ApiMock {
+        ApiMock {
+            action: HttpVerb::Get,
+            body,
+            path: format!("/v1/{}?{}", TRANSACTION_HISTORY_RESOURCE, query),
+            response_code: 200,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_history_stream_walks_every_page() {
+        let mocks = vec![
+            page_mock("limit=2&offset=0", transaction_page(0..2)),
+            page_mock("limit=2&offset=2", transaction_page(2..4)),
+            page_mock("limit=2&offset=4", transaction_page(4..5)),
+        ];
+
+        let (client, _server, mock_results) = new_test_server_and_client(mocks).await;
+
+        let filter = TransactionHistoryFilter {
+            page_size: Some(2),
+            cursor: Some(TransactionHistoryCursor::Offset(0)),
+            ..Default::default()
+        };
+        let ids: Vec<usize> = client
+            .transaction_history_stream(filter)
+            .map_ok(|tx| tx.id)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+
+        for mock in mock_results {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_history_stream_is_lazy() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let first = server
+            .mock("GET", "/v1/account/transactions?limit=2")
+            .with_body(transaction_page(0..2))
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/v1/account/transactions?limit=2&offset=2")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let filter = TransactionHistoryFilter {
+            page_size: Some(2),
+            ..Default::default()
+        };
+        let stream = client.transaction_history_stream(filter);
+        futures_util::pin_mut!(stream);
+
+        assert_eq!(stream.try_next().await.unwrap().unwrap().id, 0);
+        assert_eq!(stream.try_next().await.unwrap().unwrap().id, 1);
+
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_history_stream_resumes_from_timestamp() {
+        let mocks = vec![page_mock(
+            "from=1634751361001&limit=100",
+            transaction_page(0..3),
+        )];
+
+        let (client, _server, mock_results) = new_test_server_and_client(mocks).await;
+
+        // Transaction 1 was read; others sharing its millisecond were not.
+        let filter = TransactionHistoryFilter {
+            cursor: Some(TransactionHistoryCursor::Timestamp {
+                timestamp: 1634751361001,
+                ids: vec![1],
+            }),
+            ..Default::default()
+        };
+        let ids: Vec<usize> = client
+            .transaction_history_stream(filter)
+            .map_ok(|tx| tx.id)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(ids, vec![2]);
+
+        for mock in mock_results {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_history_stream_keeps_boundary_transactions() {
+        let mut page: Vec<serde_json::Value> =
+            serde_json::from_str(&transaction_page(0..3)).unwrap();
+        for tx in &mut page {
+            tx["timestamp"] = 1634751361000u64.into();
+        }
+        let mocks = vec![page_mock(
+            "from=1634751361000&limit=100",
+            serde_json::to_string(&page).unwrap(),
+        )];
+
+        let (client, _server, _mocks) = new_test_server_and_client(mocks).await;
+
+        let filter = TransactionHistoryFilter {
+            cursor: Some(TransactionHistoryCursor::Timestamp {
+                timestamp: 1634751361000,
+                ids: vec![0],
+            }),
+            ..Default::default()
+        };
+        let pages: Vec<TransactionHistoryPage> = client
+            .transaction_history_pages(filter)
+            .try_collect()
+            .await
+            .unwrap();
+
+        let ids: Vec<usize> = pages[0].transactions.iter().map(|tx| tx.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(
+            pages[0].cursor,
+            TransactionHistoryCursor::Timestamp {
+                timestamp: 1634751361000,
+                ids: vec![0, 1, 2],
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transaction_history_pages_expose_cursors() {
+        let mocks = vec![
+            page_mock("limit=2", transaction_page(0..2)),
+            page_mock("limit=2&offset=2", transaction_page(2..3)),
+        ];
+
+        let (client, _server, _mocks) = new_test_server_and_client(mocks).await;
+
+        let filter = TransactionHistoryFilter {
+            page_size: Some(2),
+            ..Default::default()
+        };
+        let cursors: Vec<TransactionHistoryCursor> = client
+            .transaction_history_pages(filter)
+            .map_ok(|page| page.cursor)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            cursors,
+            vec![
+                TransactionHistoryCursor::Offset(2),
+                TransactionHistoryCursor::Offset(3)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transaction_history_stream_rejects_from_with_timestamp() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("GET", mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let filter = TransactionHistoryFilter {
+            from: Some("2021-10-01T00:00:00Z".into()),
+            cursor: Some(TransactionHistoryCursor::Timestamp {
+                timestamp: 1634751361000,
+                ids: vec![],
+            }),
+            ..Default::default()
+        };
+        let results: Vec<_> = client.transaction_history_stream(filter).collect().await;
+
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(HttpError::InvalidRequest(_))));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_transaction_history_stream_ends_on_error() {
+        let mocks = vec![
+            page_mock("limit=1&offset=5", transaction_page(5..6)),
+            ApiMock {
+                action: HttpVerb::Get,
+                body: r#"{"error": "invalid request"}"#.into(),
+                path: format!("/v1/{}?limit=1&offset=6", TRANSACTION_HISTORY_RESOURCE),
+                response_code: 400,
+            },
+        ];
+
+        let (client, _server, mock_results) = new_test_server_and_client(mocks).await;
+
+        let filter = TransactionHistoryFilter {
+            page_size: Some(1),
+            cursor: Some(TransactionHistoryCursor::Offset(5)),
+            ..Default::default()
+        };
+        let results: Vec<_> = client.transaction_history_stream(filter).collect().await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().id, 5);
+        assert!(matches!(
+            results[1],
+            Err(HttpError::Api { status: 400, .. })
+        ));
+
+        for mock in mock_results {
+            mock.assert_async().await;
+        }
+    }
+
     #[tokio::test]
     async fn test_orders_report() {
         let mock = ApiMock {
             action: HttpVerb::Get,
             body: ORDERS_REPORT_RESPONSE_BODY.into(),
-            path: format!("/v1/{}", ORDERS_REPORT_RESOURCE),
+            path: format!(
+                "/v1/{}?start=704255180&end=703915618",
+                ORDERS_REPORT_RESOURCE
+            ),
             response_code: 200,
         };
 
@@ -208,7 +674,10 @@ mod tests {
         let mock = ApiMock {
             action: HttpVerb::Get,
             body: MONTHLY_SUMMARY_BY_ASSET_RESPONSE_BODY.into(),
-            path: format!("/v1/{}", MONTHLY_SUMMARY_BY_ASSET_RESOURCE),
+            path: format!(
+                "/v1/{}?currency=btc&start=704255180&end=703915618",
+                MONTHLY_SUMMARY_BY_ASSET_RESOURCE
+            ),
             response_code: 200,
         };
 
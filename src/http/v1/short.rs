@@ -1,7 +1,5 @@
-use std::pin::Pin;
-
 use futures_util::Future;
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::http::{Client, HttpError, HttpVerb};
 
@@ -45,32 +43,37 @@ pub struct LoanPosition {
     pub proceeds: usize,
 }
 
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LoanPositionsQuery {
+    /// `active` or `closed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
 impl Client {
-    pub fn loan_metrics(self) -> impl Future<Output = Result<LoanMetrics, HttpError>> {
+    pub fn loan_metrics(
+        &self,
+    ) -> impl Future<Output = Result<LoanMetrics, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(METRICS_RESOURCE);
 
         self.request(HttpVerb::Get, &query_str, None)
     }
 
     pub fn loan_positions(
-        self,
+        &self,
         status: Option<String>,
-    ) -> Pin<Box<dyn Future<Output = Result<LoanPositionResponse, HttpError>> + Send>> {
-        let resource = POSITIONS_RESOURCE;
-        let query = match status {
-            Some(s) => {
-                if s != "active" && s != "closed" {
-                    return Box::pin(async move {
-                        Err(HttpError::InvalidRequest(format!("Invalid status: {}", s)))
-                    });
-                }
-                format!("{}?status={}", resource, s)
+    ) -> impl Future<Output = Result<LoanPositionResponse, HttpError>> + Send + 'static {
+        let url = self.url_for_v1_resource(POSITIONS_RESOURCE);
+        let request = match status {
+            Some(s) if s != "active" && s != "closed" => {
+                Err(HttpError::InvalidRequest(format!("Invalid status: {}", s)))
+            }
+            status => {
+                Ok(self.request_with_query(HttpVerb::Get, &url, &LoanPositionsQuery { status }))
             }
-            None => resource.into(),
         };
-        let url = self.url_for_v1_resource(&query);
 
-        Box::pin(self.request(HttpVerb::Get, &url, None))
+        async move { request?.await }
     }
 }
 
@@ -139,16 +142,11 @@ mod tests {
     #[tokio::test]
     async fn test_loan_positions() {
         let filter = Some("active".to_string());
-        let mut path = format!("/v1/{}", POSITIONS_RESOURCE);
-
-        if filter.clone().is_some() {
-            path = format!("{}?status={}", path, filter.clone().unwrap());
-        }
 
         let mock = ApiMock {
             action: HttpVerb::Get,
             body: POSITIONS_RESPONSE_BODY.into(),
-            path,
+            path: format!("/v1/{}?status=active", POSITIONS_RESOURCE),
             response_code: 200,
         };
 
@@ -1,9 +1,8 @@
-use std::collections::HashMap;
-
 use futures_util::Future;
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::http::{Client, HttpError, HttpVerb};
+use crate::num::{self, Number};
 
 static STAKING_CURRENCIES_RESOURCE: &str = "staking/currencies";
 static STAKING_TRANSACTIONS_RESOURCE: &str = "staking/transactions";
@@ -55,47 +54,49 @@ pub struct Unstake {
     pub id: usize,
 }
 
+/// Body of a stake or unstake request.
+#[derive(Clone, Debug, Serialize)]
+pub struct StakeRequest {
+    pub currency: String,
+    #[serde(serialize_with = "num::serialize")]
+    pub quantity: Number,
+}
+
 impl Client {
     pub fn staking_currencies(
-        self,
-    ) -> impl Future<Output = Result<StakingCurrenciesResponse, HttpError>> {
+        &self,
+    ) -> impl Future<Output = Result<StakingCurrenciesResponse, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(STAKING_CURRENCIES_RESOURCE);
         self.request(HttpVerb::Get, &query_str, None)
     }
 
     pub fn staking_transactions(
-        self,
-    ) -> impl Future<Output = Result<StakingTransactionsResponse, HttpError>> {
+        &self,
+    ) -> impl Future<Output = Result<StakingTransactionsResponse, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(STAKING_TRANSACTIONS_RESOURCE);
         self.request(HttpVerb::Get, &query_str, None)
     }
 
     pub fn stake(
-        self,
+        &self,
         currency: String,
-        quantity: f64,
-    ) -> impl Future<Output = Result<(), HttpError>> {
+        quantity: Number,
+    ) -> impl Future<Output = Result<(), HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(STAKE_RESOURCE);
+        let body = StakeRequest { currency, quantity };
 
-        let mut params = HashMap::new();
-        params.insert("currency".to_string(), currency);
-        params.insert("quantity".to_string(), quantity.to_string());
-
-        self.request(HttpVerb::Post, &query_str, Some(&params))
+        self.request_with_body(HttpVerb::Post, &query_str, &body)
     }
 
     pub fn unstake(
-        self,
+        &self,
         currency: String,
-        quantity: f64,
-    ) -> impl Future<Output = Result<UnstakeResponse, HttpError>> {
+        quantity: Number,
+    ) -> impl Future<Output = Result<UnstakeResponse, HttpError>> + Send + 'static {
         let query_str = self.url_for_v1_resource(UNSTAKE_RESOURCE);
+        let body = StakeRequest { currency, quantity };
 
-        let mut params = HashMap::new();
-        params.insert("currency".to_string(), currency);
-        params.insert("quantity".to_string(), quantity.to_string());
-
-        self.request(HttpVerb::Post, &query_str, Some(&params))
+        self.request_with_body(HttpVerb::Post, &query_str, &body)
     }
 }
 
@@ -103,6 +104,7 @@ impl Client {
 mod tests {
     use super::*;
 
+    use crate::num::number;
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
     const STAKING_CURRENCIES_RESPONSE_BODY: &str = r#"
@@ -211,7 +213,7 @@ mod tests {
 
         let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
 
-        let result = client.stake("avax".into(), 12.1).await;
+        let result = client.stake("avax".into(), number("12.1")).await;
 
         assert!(result.is_ok());
 
@@ -231,7 +233,7 @@ mod tests {
 
         let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
 
-        let result = client.unstake("avax".into(), 12.1).await;
+        let result = client.unstake("avax".into(), number("12.1")).await;
         println!("RESULT: {:?}", result);
         assert!(result.is_ok());
 
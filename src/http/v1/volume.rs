@@ -1,6 +1,5 @@
 use futures_util::Future;
-use serde::Deserialize;
-use serde::Deserializer;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
 use super::Client;
@@ -102,37 +101,49 @@ impl<'de> Deserialize<'de> for VolumeRecord {
         }
     }
 }
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VolumeQuery {
+    pub start_time: usize,
+    pub end_time: usize,
+    /// Bucket size in seconds; see [`Interval::seconds`].
+    pub interval: usize,
+    pub currency: String,
+    pub net: bool,
+    pub by_exchange: bool,
+}
+
+impl Interval {
+    pub fn seconds(&self) -> usize {
+        match self {
+            Interval::Minute => 60,
+            Interval::Hour => 3600,
+            Interval::Day => 86400,
+        }
+    }
+}
+
 impl Client {
     pub fn volume(
-        self,
+        &self,
         start_time: usize,
         end_time: usize,
         interval: Interval,
         currency: &str,
         net: bool,
         by_exchange: bool,
-    ) -> impl Future<Output = Result<VolumeRecord, HttpError>> {
-        let query_str = format!(
-            "{}?start_time={}&end_time={}&interval={}&currency={}&net={}&by_exchange={}",
-            VOLUME_RESOURCE,
+    ) -> impl Future<Output = Result<VolumeRecord, HttpError>> + Send + 'static {
+        let url = self.url_for_v1_resource(VOLUME_RESOURCE);
+        let query = VolumeQuery {
             start_time,
             end_time,
-            convert_interval(interval),
-            currency,
+            interval: interval.seconds(),
+            currency: currency.to_string(),
             net,
-            by_exchange
-        );
-        let url = self.url_for_v1_resource(&query_str);
-
-        self.request(HttpVerb::Get, &url, None)
-    }
-}
+            by_exchange,
+        };
 
-fn convert_interval<'a>(interval: Interval) -> &'a str {
-    match interval {
-        Interval::Minute => "60",
-        Interval::Hour => "3600",
-        Interval::Day => "86400",
+        self.request_with_query(HttpVerb::Get, &url, &query)
     }
 }
 
@@ -320,11 +331,8 @@ mod tests {
             .volume(1694374019, 1694384019, Interval::Hour, "btc", true, true)
             .await;
 
-        assert!(result.is_err());
-        assert!(
-            result.unwrap_err().to_string()
-                == "Error while making request: `\"invalid token. check authorization header.\"`"
-        );
+        let err = result.unwrap_err();
+        assert!(matches!(err, HttpError::Unauthorized { status: 401, .. }));
 
         for mock in mock_results {
             mock.assert_async().await;
@@ -346,11 +354,15 @@ mod tests {
             .volume(1694374019, 1694384019, Interval::Hour, "", true, true)
             .await;
 
-        assert!(result.is_err());
-        assert!(
-            result.unwrap_err().to_string()
-                == "Error while making request: `\"currency must be a non-blank string\"`"
-        );
+        match result.unwrap_err() {
+            HttpError::Api {
+                status, message, ..
+            } => {
+                assert_eq!(status, 422);
+                assert_eq!(message, "currency must be a non-blank string");
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
 
         for mock in mock_results {
             mock.assert_async().await;
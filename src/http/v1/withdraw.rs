@@ -1,9 +1,8 @@
-use std::collections::HashMap;
-
 use futures_util::Future;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::super::{Client, HttpError, HttpVerb};
+use crate::num::{self, Number};
 
 static WITHDRAW_RESOURCE: &str = "user/withdraw";
 
@@ -17,23 +16,46 @@ pub struct Withdrawal {
     pub tx_status: usize,
 }
 
+/// Body of a `POST /v1/user/withdraw` request.
+#[derive(Clone, Debug, Serialize)]
+pub struct WithdrawRequest {
+    pub address: String,
+    #[serde(serialize_with = "num::serialize")]
+    pub amount: Number,
+    pub currency: String,
+    #[serde(rename = "isWire")]
+    pub is_wire: bool,
+    /// Memo or destination tag, for networks that take one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+}
+
 impl Client {
     pub fn withdraw(
-        self,
+        &self,
         address: &str,
-        amount: f64,
+        amount: Number,
         currency: &str,
         is_wire: bool,
-    ) -> impl Future<Output = Result<Withdrawal, HttpError>> {
-        let mut params = HashMap::new();
-        params.insert("address".to_string(), address.to_string());
-        params.insert("amount".to_string(), amount.to_string());
-        params.insert("currency".to_string(), currency.to_string());
-        params.insert("isWire".to_string(), is_wire.to_string());
+    ) -> impl Future<Output = Result<Withdrawal, HttpError>> + Send + 'static {
+        let body = WithdrawRequest {
+            address: address.to_string(),
+            amount,
+            currency: currency.to_string(),
+            is_wire,
+            tag: None,
+        };
+
+        self.send_withdrawal(&body)
+    }
 
+    pub(crate) fn send_withdrawal(
+        &self,
+        body: &WithdrawRequest,
+    ) -> impl Future<Output = Result<Withdrawal, HttpError>> + Send + 'static {
         let url = self.url_for_v1_resource(WITHDRAW_RESOURCE);
 
-        self.request(HttpVerb::Post, &url, Some(&params))
+        self.request_with_body(HttpVerb::Post, &url, body)
     }
 }
 
@@ -41,6 +63,7 @@ impl Client {
 mod tests {
     use super::*;
 
+    use crate::num::number;
     use crate::util::server::{new_test_server_and_client, ApiMock};
 
     const WITHDRAW_RESPONSE_BODY: &str = r#"
@@ -66,7 +89,7 @@ mod tests {
 
         let (client, _server, mock_results) = new_test_server_and_client(vec![mock]).await;
 
-        let result = client.withdraw("0x00", 123.45, "btc", true).await;
+        let result = client.withdraw("0x00", number("123.45"), "btc", true).await;
 
         assert!(result.is_ok());
 
@@ -74,4 +97,22 @@ mod tests {
             mock.assert_async().await;
         }
     }
+
+    #[tokio::test]
+    async fn test_withdraw_sends_typed_body() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mock = server
+            .mock("POST", format!("/v1/{}", WITHDRAW_RESOURCE).as_str())
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"address": "0x00", "currency": "btc", "isWire": true}"#.into(),
+            ))
+            .with_body(WITHDRAW_RESPONSE_BODY)
+            .create_async()
+            .await;
+
+        let result = client.withdraw("0x00", number("123.45"), "btc", true).await;
+
+        assert!(result.is_ok());
+        mock.assert_async().await;
+    }
 }
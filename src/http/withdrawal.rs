@@ -0,0 +1,523 @@
+use futures_util::Future;
+use thiserror::Error;
+
+use super::v1::currency::Currency;
+use super::v1::custody::{ApprovalRule, ApprovalRuleType, CustodyAddress};
+use super::v1::withdraw::{WithdrawRequest, Withdrawal};
+use super::{Client, HttpError};
+use crate::address::{AddressError, Network, TagPolicy};
+use crate::num::Number;
+
+/// Why a withdrawal failed its pre-flight checks.
+#[derive(Debug, Error)]
+pub enum WithdrawalError {
+    #[error("Withdrawal amount must be positive, got {0}")]
+    InvalidAmount(Number),
+    #[error("Unknown currency: {0}")]
+    UnknownCurrency(String),
+    #[error("Withdrawals of {0} are disabled")]
+    WithdrawalsDisabled(String),
+    #[error(transparent)]
+    InvalidAddress(AddressError),
+    #[error("{address} is not a whitelisted {currency} address")]
+    NotWhitelisted { address: String, currency: String },
+    /// The address was whitelisted with a memo or destination tag that the withdrawal does not
+    /// carry.
+    #[error("{address} was whitelisted with the tag `{expected}`, got {tag:?}")]
+    TagMismatch {
+        address: String,
+        expected: String,
+        tag: Option<String>,
+    },
+    #[error("{amount} {currency} is below the minimum withdrawal of {minimum}")]
+    BelowMinimum {
+        currency: String,
+        amount: Number,
+        minimum: Number,
+    },
+    #[error("The withdrawal fee of {fee} {currency} exceeds the amount of {amount}")]
+    FeeExceedsAmount {
+        currency: String,
+        amount: Number,
+        fee: Number,
+    },
+    #[error(transparent)]
+    Http(#[from] HttpError),
+}
+
+/// A checked withdrawal, describing what would happen without sending anything. Call
+/// [`WithdrawalPlan::confirm`] and pass the result to [`Client::execute_withdrawal`] to send it.
+///
+/// A plan is read-only, so the request that is sent is always the one that was checked.
+#[derive(Clone, Debug)]
+pub struct WithdrawalPlan {
+    request: WithdrawRequest,
+    currency: Currency,
+    destination: Option<CustodyAddress>,
+    network: Option<Network>,
+    fee: Number,
+    net_amount: Number,
+    approval_rules: Vec<ApprovalRule>,
+}
+
+impl WithdrawalPlan {
+    /// The checked request, sent as is once the plan is confirmed.
+    pub fn request(&self) -> &WithdrawRequest {
+        &self.request
+    }
+
+    pub fn currency(&self) -> &Currency {
+        &self.currency
+    }
+
+    /// The whitelist entry of the destination; `None` for wire withdrawals.
+    pub fn destination(&self) -> Option<&CustodyAddress> {
+        self.destination.as_ref()
+    }
+
+    /// The network the address format was checked against; `None` for wire withdrawals and
+    /// networks [`crate::address`] can't check, which rely on the whitelist alone.
+    pub fn network(&self) -> Option<Network> {
+        self.network
+    }
+
+    /// The fee sFOX charges for the withdrawal, in the withdrawn currency.
+    pub fn fee(&self) -> Number {
+        self.fee
+    }
+
+    /// What arrives at the destination: the amount less the fee.
+    pub fn net_amount(&self) -> Number {
+        self.net_amount
+    }
+
+    /// Withdrawal approval rules on the account. Each one requires its approvals once the
+    /// withdrawal's value reaches its threshold; see [`WithdrawalPlan::approval_rules_at`].
+    pub fn approval_rules(&self) -> &[ApprovalRule] {
+        &self.approval_rules
+    }
+
+    /// The approval rules that apply to a withdrawal worth `value` in the rules' threshold
+    /// currency.
+    pub fn approval_rules_at(&self, value: Number) -> impl Iterator<Item = &ApprovalRule> {
+        self.approval_rules
+            .iter()
+            .filter(move |rule| rule.threshold <= value)
+    }
+
+    /// Confirms the plan so it can be executed.
+    pub fn confirm(self) -> ConfirmedWithdrawal {
+        ConfirmedWithdrawal(self)
+    }
+}
+
+/// A [`WithdrawalPlan`] the caller has confirmed. The only way to obtain one is
+/// [`WithdrawalPlan::confirm`].
+#[derive(Clone, Debug)]
+pub struct ConfirmedWithdrawal(WithdrawalPlan);
+
+impl ConfirmedWithdrawal {
+    pub fn plan(&self) -> &WithdrawalPlan {
+        &self.0
+    }
+}
+
+impl Client {
+    /// Checks a withdrawal and returns its plan without sending it, so this doubles as a dry
+    /// run.
+    ///
+    /// The currency must exist and allow withdrawals, the amount must reach its minimum and
+    /// exceed the withdrawal fee, and, unless it is a wire, the address must be well formed for
+    /// the currency's network (see [`crate::address`]) and whitelisted for the currency in the
+    /// custody addresses. An address whitelisted with a memo or destination tag must be given
+    /// that tag.
+    pub fn plan_withdrawal(
+        &self,
+        request: &WithdrawRequest,
+    ) -> impl Future<Output = Result<WithdrawalPlan, WithdrawalError>> + Send + 'static {
+        let request = request.clone();
+        let symbol = request.currency.to_lowercase();
+        let currencies = self.currencies();
+        let addresses = self.custody_addresses();
+        let fee = self.withdraw_fee(&symbol);
+        let rules = self.approval_rules();
+
+        async move {
+            if request.amount <= Number::default() {
+                return Err(WithdrawalError::InvalidAmount(request.amount));
+            }
+
+            let (currencies, addresses, fee, rules) =
+                futures_util::try_join!(currencies, addresses, fee, rules)?;
+
+            let currency = currencies
+                .into_iter()
+                .find(|currency| currency.symbol.eq_ignore_ascii_case(&symbol))
+                .ok_or_else(|| WithdrawalError::UnknownCurrency(symbol.clone()))?;
+            if !currency.can_withdraw {
+                return Err(WithdrawalError::WithdrawalsDisabled(symbol));
+            }
+            if request.amount < currency.min_withdrawal {
+                return Err(WithdrawalError::BelowMinimum {
+                    currency: symbol,
+                    amount: request.amount,
+                    minimum: currency.min_withdrawal,
+                });
+            }
+            if fee.fee >= request.amount {
+                return Err(WithdrawalError::FeeExceedsAmount {
+                    currency: symbol,
+                    amount: request.amount,
+                    fee: fee.fee,
+                });
+            }
+
+            let destination = match request.is_wire {
+                true => None,
+                false => addresses.data.into_iter().find(|entry| {
+                    entry.currency_symbol.eq_ignore_ascii_case(&symbol)
+                        && same_address(&entry.address, &request.address)
+                }),
+            };
+            // Funds sent to an address whitelisted with a tag, such as an exchange's, are lost
+            // without it.
+            let whitelisted_tag = destination
+                .as_ref()
+                .and_then(|entry| entry.tag.as_deref())
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(String::from);
+            let tag_policy = match whitelisted_tag {
+                Some(_) => TagPolicy::Required,
+                None => TagPolicy::Optional,
+            };
+
+            let network = if request.is_wire {
+                None
+            } else {
+                match Network::of_currency(&currency) {
+                    Ok(network) => {
+                        network
+                            .validate_with(&request.address, request.tag.as_deref(), tag_policy)
+                            .map_err(WithdrawalError::InvalidAddress)?;
+                        Some(network)
+                    }
+                    Err(AddressError::UnsupportedNetwork(_)) => None,
+                    Err(e) => return Err(WithdrawalError::InvalidAddress(e)),
+                }
+            };
+
+            if !request.is_wire && destination.is_none() {
+                return Err(WithdrawalError::NotWhitelisted {
+                    address: request.address.clone(),
+                    currency: symbol,
+                });
+            }
+            if let Some(expected) = whitelisted_tag {
+                if request.tag.as_deref().map(str::trim) != Some(expected.as_str()) {
+                    return Err(WithdrawalError::TagMismatch {
+                        address: request.address.clone(),
+                        expected,
+                        tag: request.tag.clone(),
+                    });
+                }
+            }
+
+            let approval_rules = rules
+                .data
+                .into_iter()
+                .filter(|rule| rule.rule_type == ApprovalRuleType::Withdraw)
+                .collect();
+
+            Ok(WithdrawalPlan {
+                net_amount: request.amount - fee.fee,
+                fee: fee.fee,
+                request,
+                currency,
+                destination,
+                network,
+                approval_rules,
+            })
+        }
+    }
+
+    /// Sends a confirmed withdrawal.
+    pub fn execute_withdrawal(
+        &self,
+        withdrawal: ConfirmedWithdrawal,
+    ) -> impl Future<Output = Result<Withdrawal, HttpError>> + Send + 'static {
+        self.send_withdrawal(&withdrawal.0.request)
+    }
+}
+
+/// Hex addresses such as Ethereum's may differ in letter case (a checksum); anything else must
+/// match exactly.
+fn same_address(whitelisted: &str, address: &str) -> bool {
+    if whitelisted.starts_with("0x") {
+        whitelisted.eq_ignore_ascii_case(address)
+    } else {
+        whitelisted == address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mockito::{Matcher, Mock, ServerGuard};
+
+    use crate::num::number;
+    use crate::util::server::new_test_server_and_client;
+
+    const CURRENCIES_RESPONSE_BODY: &str = r#"
+        [
+            {
+                "id": 1, "symbol": "btc", "name": "Bitcoin", "is_fiat": 0,
+                "is_lending_enabled": 0, "can_deposit": 1, "can_withdraw": 1,
+                "min_withdrawal": 0.001, "confirmations_needed": 3, "precision": 8,
+                "ascii_sign": "₿", "contract_address": null, "custody_enabled": 1,
+                "trading_enabled": 1, "primary_network": "bitcoin", "code": "btc",
+                "currency": "btc"
+            },
+            {
+                "id": 2, "symbol": "bch", "name": "Bitcoin Cash", "is_fiat": 0,
+                "is_lending_enabled": 0, "can_deposit": 1, "can_withdraw": 0,
+                "min_withdrawal": 0.001, "confirmations_needed": 3, "precision": 8,
+                "ascii_sign": "", "contract_address": null, "custody_enabled": 1,
+                "trading_enabled": 1, "primary_network": "bitcoincash", "code": "bch",
+                "currency": "bch"
+            }
+        ]
+    "#;
+
+    const CUSTODY_ADDRESSES_RESPONSE_BODY: &str = r#"
+        {
+            "data": [
+                {
+                    "alias": "Satoshis Fund",
+                    "address": "1NLqQmwkGxxQmzS9uwtCGXxbxrcNW4FpYp",
+                    "currency_symbol": "btc",
+                    "date_created": "2021-09-15T15:12:13.000Z",
+                    "date_updated": "2021-09-15T15:12:13.000Z"
+                }
+            ]
+        }
+    "#;
+
+    const APPROVAL_RULES_RESPONSE_BODY: &str = r#"
+        {
+            "data": [
+                {
+                    "id": 3, "available_approver_count": 2, "date_added": "2021-03-17T16:19:47.000Z",
+                    "required_approvals": 2, "rule_type": "WITHDRAW", "status": "Active",
+                    "threshold": 20
+                },
+                {
+                    "id": 4, "available_approver_count": 2, "date_added": "2021-03-17T16:19:47.000Z",
+                    "required_approvals": 1, "rule_type": "ALTER_SAFE", "status": "Active",
+                    "threshold": 0
+                }
+            ]
+        }
+    "#;
+
+    const ADDRESS: &str = "1NLqQmwkGxxQmzS9uwtCGXxbxrcNW4FpYp";
+
+    async fn mock_get(server: &mut ServerGuard, path: &str, body: &str) -> Mock {
+        server
+            .mock("GET", path)
+            .with_body(body)
+            .create_async()
+            .await
+    }
+
+    async fn mock_checks(server: &mut ServerGuard, currency: &str) -> Vec<Mock> {
+        vec![
+            mock_get(server, "/v1/currency", CURRENCIES_RESPONSE_BODY).await,
+            mock_get(
+                server,
+                "/v1/whitelisted-addresses",
+                CUSTODY_ADDRESSES_RESPONSE_BODY,
+            )
+            .await,
+            mock_get(
+                server,
+                &format!("/v1/withdraw-fee/{}", currency),
+                r#"{"fee": 0.0005}"#,
+            )
+            .await,
+            mock_get(server, "/v1/approval-rules", APPROVAL_RULES_RESPONSE_BODY).await,
+        ]
+    }
+
+    fn request(address: &str, amount: &str, currency: &str) -> WithdrawRequest {
+        WithdrawRequest {
+            address: address.into(),
+            amount: number(amount),
+            currency: currency.into(),
+            is_wire: false,
+            tag: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_withdrawal() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let mocks = mock_checks(&mut server, "btc").await;
+        let send = server
+            .mock("POST", Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let plan = client
+            .plan_withdrawal(&request(ADDRESS, "0.01", "BTC"))
+            .await
+            .unwrap();
+
+        assert_eq!(plan.fee(), number("0.0005"));
+        assert_eq!(plan.net_amount(), number("0.0095"));
+        assert_eq!(plan.destination().unwrap().alias, "Satoshis Fund");
+        assert_eq!(plan.network(), Some(Network::Bitcoin));
+        assert_eq!(plan.approval_rules().len(), 1);
+        assert_eq!(plan.approval_rules_at(number("19.99")).count(), 0);
+        assert_eq!(plan.approval_rules_at(number("20")).count(), 1);
+
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+        send.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_plan_withdrawal_rejections() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let _btc = mock_checks(&mut server, "btc").await;
+        let _bch = mock_get(&mut server, "/v1/withdraw-fee/bch", r#"{"fee": 0.0005}"#).await;
+        let _doge = mock_get(&mut server, "/v1/withdraw-fee/doge", r#"{"fee": 1}"#).await;
+
+        let result = client
+            .plan_withdrawal(&request(
+                "1BoatSLRHtKNngkdXEeobR76b53LETtpyT",
+                "0.01",
+                "btc",
+            ))
+            .await;
+        assert!(matches!(
+            result,
+            Err(WithdrawalError::NotWhitelisted { .. })
+        ));
+
+        // A typo in a whitelisted-looking address fails its checksum first.
+        let result = client
+            .plan_withdrawal(&request(
+                "1NLqQmwkGxxQmzS9uwtCGXxbxrcNW4FpYq",
+                "0.01",
+                "btc",
+            ))
+            .await;
+        assert!(matches!(
+            result,
+            Err(WithdrawalError::InvalidAddress(
+                AddressError::InvalidChecksum { .. }
+            ))
+        ));
+
+        let result = client
+            .plan_withdrawal(&request(ADDRESS, "0.0001", "btc"))
+            .await;
+        assert!(matches!(result, Err(WithdrawalError::BelowMinimum { .. })));
+
+        let result = client
+            .plan_withdrawal(&request(ADDRESS, "0.01", "bch"))
+            .await;
+        assert!(matches!(
+            result,
+            Err(WithdrawalError::WithdrawalsDisabled(_))
+        ));
+
+        let result = client
+            .plan_withdrawal(&request(ADDRESS, "0.01", "doge"))
+            .await;
+        assert!(matches!(result, Err(WithdrawalError::UnknownCurrency(_))));
+
+        let result = client.plan_withdrawal(&request(ADDRESS, "0", "btc")).await;
+        assert!(matches!(result, Err(WithdrawalError::InvalidAmount(_))));
+    }
+
+    #[tokio::test]
+    async fn test_plan_withdrawal_requires_whitelisted_tag() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let _mocks = [
+            mock_get(
+                &mut server,
+                "/v1/currency",
+                r#"[{
+                    "id": 3, "symbol": "xrp", "name": "XRP", "is_fiat": 0,
+                    "is_lending_enabled": 0, "can_deposit": 1, "can_withdraw": 1,
+                    "min_withdrawal": 1, "confirmations_needed": 1, "precision": 6,
+                    "ascii_sign": "", "contract_address": null, "custody_enabled": 1,
+                    "trading_enabled": 1, "primary_network": "ripple", "code": "xrp",
+                    "currency": "xrp"
+                }]"#,
+            )
+            .await,
+            mock_get(
+                &mut server,
+                "/v1/whitelisted-addresses",
+                r#"{"data": [{
+                    "alias": "Exchange", "address": "rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh",
+                    "currency_symbol": "xrp", "date_created": "2021-09-15T15:12:13.000Z",
+                    "date_updated": "2021-09-15T15:12:13.000Z", "tag": "12345"
+                }]}"#,
+            )
+            .await,
+            mock_get(&mut server, "/v1/withdraw-fee/xrp", r#"{"fee": 0.1}"#).await,
+            mock_get(&mut server, "/v1/approval-rules", r#"{"data": []}"#).await,
+        ];
+        let mut request = request("rHb9CJAWyB4rj91VRWn96DkukG4bwdtyTh", "10", "xrp");
+
+        let result = client.plan_withdrawal(&request).await;
+        assert!(matches!(
+            result,
+            Err(WithdrawalError::InvalidAddress(AddressError::MissingTag {
+                network: Network::Ripple
+            }))
+        ));
+
+        request.tag = Some("54321".into());
+        let result = client.plan_withdrawal(&request).await;
+        assert!(matches!(result, Err(WithdrawalError::TagMismatch { .. })));
+
+        request.tag = Some("12345".into());
+        let plan = client.plan_withdrawal(&request).await.unwrap();
+        assert_eq!(plan.request().tag.as_deref(), Some("12345"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_confirmed_withdrawal() {
+        let (client, mut server, _mocks) = new_test_server_and_client(vec![]).await;
+        let _checks = mock_checks(&mut server, "btc").await;
+        let send = server
+            .mock("POST", "/v1/user/withdraw")
+            .match_body(Matcher::PartialJsonString(format!(
+                r#"{{"address": "{}", "currency": "btc"}}"#,
+                ADDRESS
+            )))
+            .with_body(
+                r#"{"success": true, "atx_id": 1524562, "tx_status": 1100, "currency": "btc",
+                    "amount": 0.01, "address": "1NLqQmwkGxxQmzS9uwtCGXxbxrcNW4FpYp"}"#,
+            )
+            .create_async()
+            .await;
+
+        let plan = client
+            .plan_withdrawal(&request(ADDRESS, "0.01", "btc"))
+            .await
+            .unwrap();
+        let withdrawal = client.execute_withdrawal(plan.confirm()).await.unwrap();
+
+        assert_eq!(withdrawal.atx_id, 1524562);
+        send.assert_async().await;
+    }
+}
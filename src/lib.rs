@@ -7,7 +7,10 @@
 //!
 //! The `SFOX_API_KEY` environment variable is required. API keys can be obtained from the [dashboard](https://trade.sfox.com/account/api).
 //!
-//! `SFOX_HTTP_SERVER_URL` and `SFOX_WS_SERVER_URL` environment variables are optional. If not set, the default values are used.
+//! `SFOX_HTTP_SERVER_URL`, `SFOX_CANDLESTICK_SERVER_URL` and `SFOX_WS_SERVER_URL` environment variables are optional. If not set, the default values are used.
+//!
+//! To switch between environments by name, load an [`config::SfoxConfig`] profile and build the clients with
+//! `http::Client::from_config` and `websocket::Client::from_config`.
 //!
 //! # Usage
 //!
@@ -58,8 +61,18 @@
 //! });
 //! ```
 
+/// Withdrawal address formats per network, with checksum and memo/tag validation.
+pub mod address;
+/// Layered, profile-based configuration for the HTTP and WebSocket clients.
+pub mod config;
+/// API key providers shared by the HTTP and WebSocket clients.
+pub mod credentials;
 /// Models the resources of the SFox HTTP API with [tokio](https://crates.io/crates/tokio)-based convenience methods for making HTTP requests to the SFOX API.
 pub mod http;
+/// The numeric type of prices, quantities and amounts; an exact decimal with the `decimal` feature.
+pub mod num;
+/// Order sides, execution algorithms, routing types, time in force and currency pairs.
+pub mod trading;
 /// Offers convenience methods for authentication and feed subscription, as well as types for message deserialization.
 pub mod websocket;
 
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Deserializer, Serializer};
+use serde_json::Value;
+
+/// Prices, quantities and amounts.
+///
+/// `f64` by default. With the `decimal` feature, an exact [`rust_decimal::Decimal`], so values
+/// such as `0.1 + 0.2` are not subject to binary floating point rounding and are sent to the
+/// API exactly as given.
+#[cfg(not(feature = "decimal"))]
+pub type Number = f64;
+
+/// Prices, quantities and amounts.
+///
+/// An exact [`rust_decimal::Decimal`] because the `decimal` feature is enabled; `f64` otherwise.
+#[cfg(feature = "decimal")]
+pub type Number = rust_decimal::Decimal;
+
+/// Parses a number from its string form, accepting scientific notation such as `"1.5e-7"`.
+pub fn parse(s: &str) -> Result<Number, String> {
+    #[cfg(not(feature = "decimal"))]
+    let parsed = s.parse::<f64>().map_err(|e| e.to_string());
+
+    #[cfg(feature = "decimal")]
+    let parsed = if s.contains(['e', 'E']) {
+        rust_decimal::Decimal::from_scientific(s)
+    } else {
+        s.parse::<rust_decimal::Decimal>()
+    }
+    .map_err(|e| e.to_string());
+
+    parsed.map_err(|e| format!("invalid number {:?}: {}", s, e))
+}
+
+/// Deserializes a number that the API encodes as a JSON string, e.g. `"0.17572419"`.
+pub(crate) fn from_str<'de, D>(deserializer: D) -> Result<Number, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct NumberVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for NumberVisitor {
+        type Value = Number;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a number as a string")
+        }
+
+        // Parses the string where it lies instead of copying it out of the message first.
+        fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Number, E> {
+            parse(s).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_str(NumberVisitor)
+}
+
+/// Deserializes a number sent either as a JSON number or as a string, possibly in scientific
+/// notation.
+pub(crate) fn from_str_or_number<'de, D>(deserializer: D) -> Result<Number, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => parse(&s).map_err(serde::de::Error::custom),
+        // Parse the shortest representation of the JSON number rather than converting the
+        // float, so 0.1 stays 0.1 as a decimal.
+        Value::Number(n) => parse(&n.to_string()).map_err(serde::de::Error::custom),
+        _ => Err(serde::de::Error::custom("Expected string or number")),
+    }
+}
+
+/// Serializes a number as its decimal string, e.g. `"0.1"`, the way the API has always been sent
+/// amounts. The string is the same whether [`Number`] is an `f64` or a `Decimal`.
+pub(crate) fn serialize<S>(value: &Number, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    #[cfg(feature = "decimal")]
+    let value = value.normalize();
+
+    serializer.collect_str(&value)
+}
+
+/// [`serialize`] for optional numbers, which are normally also skipped when `None`.
+pub(crate) fn serialize_option<S>(value: &Option<Number>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(value) => serialize(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Which way [`round`] moves a value that has too many decimal places.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    /// Towards negative infinity.
+    Down,
+    /// Towards positive infinity.
+    Up,
+    /// To the nearest value, halves away from zero.
+    Nearest,
+}
+
+/// Rounds `value` to `places` decimal places.
+///
+/// With `f64`, values that are already within floating point error of `places` decimal places
+/// are left where they are rather than floored or ceiled past it, so `0.3` stays `0.3` when
+/// rounded down to eight places.
+pub fn round(value: Number, places: u32, rounding: Rounding) -> Number {
+    #[cfg(not(feature = "decimal"))]
+    let rounded = {
+        let factor = 10f64.powi(places as i32);
+        let scaled = value * factor;
+        let nearest = scaled.round();
+
+        let rounded = if (scaled - nearest).abs() <= f64::EPSILON * nearest.abs().max(1.0) * 4.0 {
+            nearest
+        } else {
+            match rounding {
+                Rounding::Down => scaled.floor(),
+                Rounding::Up => scaled.ceil(),
+                Rounding::Nearest => nearest,
+            }
+        };
+        rounded / factor
+    };
+
+    #[cfg(feature = "decimal")]
+    let rounded = {
+        use rust_decimal::RoundingStrategy;
+
+        let strategy = match rounding {
+            Rounding::Down => RoundingStrategy::ToNegativeInfinity,
+            Rounding::Up => RoundingStrategy::ToPositiveInfinity,
+            Rounding::Nearest => RoundingStrategy::MidpointAwayFromZero,
+        };
+        value.round_dp_with_strategy(places, strategy)
+    };
+
+    rounded
+}
+
+/// True when `value` has no more than `places` decimal places.
+pub fn has_precision(value: Number, places: u32) -> bool {
+    round(value, places, Rounding::Nearest) == value
+}
+
+/// A number from a literal such as `"0.123"`, for tests that compile with and without the
+/// `decimal` feature.
+#[cfg(test)]
+pub(crate) fn number(s: &str) -> Number {
+    parse(s).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Encoded {
+        #[serde(deserialize_with = "from_str")]
+        string: Number,
+        #[serde(deserialize_with = "from_str_or_number")]
+        scientific: Number,
+        #[serde(deserialize_with = "from_str_or_number")]
+        plain: Number,
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let encoded: Encoded =
+            serde_json::from_str(r#"{"string":"0.3","scientific":"1.5e-7","plain":0.1}"#).unwrap();
+
+        assert_eq!(encoded.string, number("0.3"));
+        assert_eq!(encoded.scientific, number("0.00000015"));
+        assert_eq!(encoded.plain, number("0.1"));
+        assert!(parse("abc").is_err());
+    }
+
+    #[test]
+    fn test_round() {
+        assert_eq!(
+            round(number("0.123456789"), 8, Rounding::Down),
+            number("0.12345678")
+        );
+        assert_eq!(
+            round(number("0.123456781"), 8, Rounding::Up),
+            number("0.12345679")
+        );
+        assert_eq!(round(number("2.345"), 2, Rounding::Nearest), number("2.35"));
+        assert_eq!(round(number("0.3"), 8, Rounding::Down), number("0.3"));
+        assert_eq!(round(number("16900.6"), 2, Rounding::Up), number("16900.6"));
+
+        assert!(has_precision(number("0.1"), 8));
+        assert!(has_precision(number("16900.61"), 2));
+        assert!(!has_precision(number("16900.615"), 2));
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal_is_exact() {
+        assert_eq!(number("0.1") + number("0.2"), number("0.3"));
+        assert_eq!((number("0.1") + number("0.2")).to_string(), "0.3");
+    }
+}
@@ -0,0 +1,323 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Defines an enum of the values sFOX uses for a field, with an `Unknown(String)` fallback.
+///
+/// Known values parse case-insensitively and ignore spaces, dashes and underscores, so `"BUY"`,
+/// `"Buy"` and `"buy"` are all [`Side::Buy`]. They are normalized: the received spelling is not
+/// kept, and they serialize as the listed name, so `"Sell"` is written back as `"sell"`. Parsing
+/// that output gives the same value again. Anything else is kept verbatim in `Unknown` and
+/// serialized back unchanged.
+macro_rules! vocabulary {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident => $text:literal,)+
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Eq, Hash, PartialEq)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)+
+            /// A value not known to this version of the crate, kept as received.
+            Unknown(String),
+        }
+
+        impl $name {
+            /// The name sFOX uses for this value.
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $text,)+
+                    $name::Unknown(s) => s,
+                }
+            }
+
+            pub fn is_unknown(&self) -> bool {
+                matches!(self, $name::Unknown(_))
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(s: &str) -> Self {
+                let key = $crate::trading::normalize(s);
+                $(
+                    if key == $crate::trading::normalize($text) {
+                        return $name::$variant;
+                    }
+                )+
+                $name::Unknown(s.to_string())
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = ::std::convert::Infallible;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Ok(s.into())
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl ::serde::Serialize for $name {
+            fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                let s = <String as ::serde::Deserialize>::deserialize(deserializer)?;
+                Ok(s.as_str().into())
+            }
+        }
+    };
+}
+
+pub(crate) use vocabulary;
+
+vocabulary! {
+    /// The side of an order or quote.
+    pub enum Side {
+        Buy => "buy",
+        Sell => "sell",
+    }
+}
+
+vocabulary! {
+    /// sFOX's execution algorithms, reported by name in the `algorithm` and `type` fields of
+    /// orders and selected by [`Algorithm::id`] when placing one.
+    pub enum Algorithm {
+        Market => "Market",
+        Instant => "Instant",
+        Smart => "Smart",
+        Limit => "Limit",
+        Gorilla => "Gorilla",
+        Tortoise => "Tortoise",
+        Hare => "Hare",
+        Stop => "Stop",
+        PolarBear => "Polar Bear",
+        Sniper => "Sniper",
+        Hidden => "Hidden",
+        Iceberg => "Iceberg",
+        Twap => "TWAP",
+        TrailingStop => "Trailing Stop",
+    }
+}
+
+vocabulary! {
+    /// How an order is routed across liquidity providers.
+    pub enum RoutingType {
+        /// Route by price net of fees.
+        NetPrice => "NetPrice",
+        /// Route by quoted price alone.
+        BestPrice => "BestPrice",
+    }
+}
+
+vocabulary! {
+    /// How long an order stays open.
+    pub enum TimeInForce {
+        GoodTilCanceled => "GTC",
+        GoodTilDate => "GTD",
+        ImmediateOrCancel => "IOC",
+        FillOrKill => "FOK",
+    }
+}
+
+const ALGORITHM_IDS: [(Algorithm, usize); 14] = [
+    (Algorithm::Market, 100),
+    (Algorithm::Instant, 150),
+    (Algorithm::Smart, 200),
+    (Algorithm::Limit, 201),
+    (Algorithm::Gorilla, 202),
+    (Algorithm::Tortoise, 203),
+    (Algorithm::Hare, 301),
+    (Algorithm::Stop, 302),
+    (Algorithm::PolarBear, 303),
+    (Algorithm::Sniper, 304),
+    (Algorithm::Hidden, 305),
+    (Algorithm::Iceberg, 306),
+    (Algorithm::Twap, 307),
+    (Algorithm::TrailingStop, 308),
+];
+
+impl Algorithm {
+    /// The `algorithm_id` sFOX expects when placing an order. An `Unknown` algorithm holding a
+    /// number is taken to be a raw id, so newly added algorithms can be used before this crate
+    /// knows their names.
+    pub fn id(&self) -> Option<usize> {
+        match self {
+            Algorithm::Unknown(s) => s.trim().parse().ok(),
+            known => ALGORITHM_IDS
+                .iter()
+                .find(|(algorithm, _)| algorithm == known)
+                .map(|(_, id)| *id),
+        }
+    }
+
+    /// The algorithm with the given `algorithm_id`, or `Unknown` holding the id.
+    pub fn from_id(id: usize) -> Self {
+        ALGORITHM_IDS
+            .iter()
+            .find(|(_, known)| *known == id)
+            .map(|(algorithm, _)| algorithm.clone())
+            .unwrap_or_else(|| Algorithm::Unknown(id.to_string()))
+    }
+}
+
+/// A currency pair symbol such as `btcusd`.
+///
+/// Converting from a string lowercases it and drops separators, so `"BTC/USD"` and `"btc-usd"`
+/// both become `btcusd`. Deserialized pairs are kept exactly as the API sent them.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct Pair(String);
+
+impl Pair {
+    pub fn new(base: &str, quote: &str) -> Self {
+        Pair::from(format!("{}{}", base, quote).as_str())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Pair {
+    fn from(s: &str) -> Self {
+        Pair(
+            s.chars()
+                .filter(|c| !matches!(c, '/' | '-' | '_' | ' '))
+                .flat_map(char::to_lowercase)
+                .collect(),
+        )
+    }
+}
+
+impl From<String> for Pair {
+    fn from(s: String) -> Self {
+        s.as_str().into()
+    }
+}
+
+impl From<&String> for Pair {
+    fn from(s: &String) -> Self {
+        s.as_str().into()
+    }
+}
+
+impl From<&Pair> for Pair {
+    fn from(pair: &Pair) -> Self {
+        pair.clone()
+    }
+}
+
+impl FromStr for Pair {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.into())
+    }
+}
+
+impl fmt::Display for Pair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+pub(crate) fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_values_parse_case_insensitively() {
+        assert_eq!(Side::from("BUY"), Side::Buy);
+        assert_eq!(Side::from("Sell"), Side::Sell);
+        assert_eq!(Algorithm::from("trailing_stop"), Algorithm::TrailingStop);
+        assert_eq!(Algorithm::from("Polar Bear"), Algorithm::PolarBear);
+        assert_eq!(TimeInForce::from("gtc"), TimeInForce::GoodTilCanceled);
+        assert_eq!(RoutingType::from("NetPrice"), RoutingType::NetPrice);
+    }
+
+    #[test]
+    fn test_unknown_values_round_trip() {
+        let side: Side = serde_json::from_str(r#""sel""#).unwrap();
+        assert_eq!(side, Side::Unknown("sel".into()));
+        assert_eq!(serde_json::to_string(&side).unwrap(), r#""sel""#);
+
+        let routing: RoutingType = serde_json::from_str(r#""None""#).unwrap();
+        assert!(routing.is_unknown());
+        assert_eq!(serde_json::to_string(&routing).unwrap(), r#""None""#);
+    }
+
+    #[test]
+    fn test_known_values_serialize_as_sfox_names() {
+        assert_eq!(serde_json::to_string(&Side::Buy).unwrap(), r#""buy""#);
+        assert_eq!(
+            serde_json::to_string(&Algorithm::Twap).unwrap(),
+            r#""TWAP""#
+        );
+        assert_eq!(
+            serde_json::to_string(&TimeInForce::FillOrKill).unwrap(),
+            r#""FOK""#
+        );
+    }
+
+    #[test]
+    fn test_open_order_values_round_trip() {
+        let orders: crate::websocket::message::OrderResponse =
+            serde_json::from_str(crate::util::fixtures::OPEN_ORDERS_PAYLOAD).unwrap();
+        let order = &orders.payload[0];
+
+        // The feed sends "Sell"; it is normalized to sFOX's canonical spelling.
+        let action = serde_json::to_string(&order.action).unwrap();
+        assert_eq!(action, r#""sell""#);
+        assert_eq!(serde_json::from_str::<Side>(&action).unwrap(), order.action);
+
+        let order_type = serde_json::to_string(&order.order_type).unwrap();
+        assert_eq!(order_type, r#""TWAP""#);
+        assert_eq!(
+            serde_json::from_str::<Algorithm>(&order_type).unwrap(),
+            order.order_type
+        );
+
+        let pair = serde_json::to_string(&order.pair).unwrap();
+        assert_eq!(serde_json::from_str::<Pair>(&pair).unwrap(), order.pair);
+    }
+
+    #[test]
+    fn test_algorithm_ids() {
+        assert_eq!(Algorithm::Limit.id(), Some(201));
+        assert_eq!(Algorithm::from_id(307), Algorithm::Twap);
+        assert_eq!(Algorithm::from_id(999), Algorithm::Unknown("999".into()));
+        assert_eq!(Algorithm::from_id(999).id(), Some(999));
+        assert_eq!(Algorithm::Unknown("Moonshot".into()).id(), None);
+    }
+
+    #[test]
+    fn test_pair_normalization() {
+        assert_eq!(Pair::from("BTC/USD").as_str(), "btcusd");
+        assert_eq!(Pair::new("ETH", "btc").as_str(), "ethbtc");
+
+        let pair: Pair = serde_json::from_str(r#""btcusd""#).unwrap();
+        assert_eq!(serde_json::to_string(&pair).unwrap(), r#""btcusd""#);
+    }
+}
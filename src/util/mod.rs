@@ -7,5 +7,5 @@ pub mod server;
 pub fn set_test_env() {
     use std::env;
 
-    let _ = env::set_var("SFOX_AUTH_TOKEN", "secret");
+    env::set_var("SFOX_AUTH_TOKEN", "secret");
 }
@@ -40,7 +40,7 @@ pub async fn new_test_server_and_client(
 
 /// Start a test server configured with the provided mock.
 pub async fn start_test_http_server(api_mocks: Vec<ApiMock>) -> (mockito::ServerGuard, Vec<Mock>) {
-    let _ = env::set_var("SFOX_AUTH_TOKEN", "secret");
+    env::set_var("SFOX_AUTH_TOKEN", "secret");
 
     let mut s = mockito::Server::new_async().await;
 
@@ -67,7 +67,7 @@ pub async fn start_test_ws_server() -> (Arc<AtomicBool>, SocketAddr, tokio::task
     // Create an Arc<AtomicBool> to share between the two threads.
     let stop = Arc::new(AtomicBool::new(false));
     let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
-    let addr = server.local_addr().unwrap().clone();
+    let addr = server.local_addr().unwrap();
 
     let stop_clone = Arc::clone(&stop);
     let listener_task = spawn(async move {
@@ -80,7 +80,7 @@ pub async fn start_test_ws_server() -> (Arc<AtomicBool>, SocketAddr, tokio::task
         }
     });
 
-    return (stop, addr, listener_task);
+    (stop, addr, listener_task)
 }
 
 /// Stop the websocket test server.
@@ -89,6 +89,7 @@ pub async fn stop_test_ws_server(stop: Arc<AtomicBool>) {
 }
 
 /// Accept a connection and echo messages back to the client.
+#[allow(clippy::result_large_err)]
 async fn accept_connection(stream: TcpStream) {
     let callback = |_req: &Request, response: Response| Ok(response);
     let mut ws_stream = accept_hdr_async(stream, callback)
@@ -1,11 +1,10 @@
-use std::env::{self, VarError};
-
 use futures_util::SinkExt;
 use serde_derive::Deserialize;
 use serde_json::{json, Error};
 use tokio_tungstenite::tungstenite::Message;
 
 use super::{message::WsSystemResponse, Client, WebsocketClientError, WsSink};
+use crate::credentials::{CredentialProvider, EnvCredentials, SecretString};
 
 #[derive(Debug, Deserialize)]
 pub struct WsAuthResponsePayload {
@@ -13,10 +12,19 @@ pub struct WsAuthResponsePayload {
 }
 
 impl Client {
-    /// Authenticate a connected socket
+    /// Authenticate a connected socket with the API key in `SFOX_AUTH_TOKEN`.
     pub async fn authenticate(write: &mut WsSink) -> Result<(), WebsocketClientError> {
-        let msg = match auth_message() {
-            Ok(msg) => msg,
+        Self::authenticate_with(write, &EnvCredentials::default()).await
+    }
+
+    /// Authenticate a connected socket with the key supplied by the given provider. Pass the
+    /// provider shared with the HTTP client so both use the same, possibly rotated, key.
+    pub async fn authenticate_with(
+        write: &mut WsSink,
+        credentials: &dyn CredentialProvider,
+    ) -> Result<(), WebsocketClientError> {
+        let msg = match credentials.api_key() {
+            Ok(api_key) => auth_message(&api_key),
             Err(e) => return Err(WebsocketClientError::AuthenticationError(e.to_string())),
         };
 
@@ -36,21 +44,62 @@ impl Client {
     }
 }
 
-fn auth_message() -> Result<Message, VarError> {
-    let auth_token = env::var("SFOX_AUTH_TOKEN")?;
-
+fn auth_message(api_key: &SecretString) -> Message {
     let msg = json!({
       "type": "authenticate",
-      "apiKey": auth_token
+      "apiKey": api_key.expose_secret()
     })
     .to_string();
 
-    Ok(Message::Text(msg))
+    Message::Text(msg)
 }
 
 #[cfg(test)]
 mod tests {
+    use futures_util::StreamExt;
+
     use super::*;
+    use crate::credentials::StaticCredentials;
+    use crate::util::server::{start_test_ws_server, stop_test_ws_server};
+
+    #[tokio::test]
+    async fn test_authenticate_with_provider() {
+        let (stop, addr, _handle) = start_test_ws_server().await;
+        let client = Client::new_with_server_url(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        let (mut write, mut read) = client.stream.split();
+
+        let credentials = StaticCredentials::new("ws-key");
+        Client::authenticate_with(&mut write, &credentials)
+            .await
+            .unwrap();
+
+        // The test server echoes messages back.
+        let echoed = read.next().await.unwrap().unwrap();
+        let echoed: serde_json::Value = serde_json::from_str(echoed.to_text().unwrap()).unwrap();
+        assert_eq!(echoed["type"], "authenticate");
+        assert_eq!(echoed["apiKey"], "ws-key");
+
+        stop_test_ws_server(stop).await;
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_with_missing_key() {
+        let (stop, addr, _handle) = start_test_ws_server().await;
+        let client = Client::new_with_server_url(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        let (mut write, _read) = client.stream.split();
+
+        let result = Client::authenticate_with(&mut write, &StaticCredentials::default()).await;
+
+        assert!(matches!(
+            result,
+            Err(WebsocketClientError::AuthenticationError(_))
+        ));
+        stop_test_ws_server(stop).await;
+    }
 
     #[test]
     fn test_auth_success_check() {
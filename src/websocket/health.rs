@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::message::event::WsEvent;
+use super::message::Feed;
+use super::Client;
+
+/// How long a feed may go without a message before it is reported as quiet, unless configured.
+pub const DEFAULT_QUIET_AFTER: Duration = Duration::from_secs(30);
+
+/// Controls how a [`ManagedConnection`](super::managed::ManagedConnection) checks the health of
+/// its feeds.
+#[derive(Clone, Debug)]
+pub struct HealthPolicy {
+    /// Report a feed as [`FeedHealth::Quiet`] once it has sent nothing for this long.
+    pub quiet_after: Duration,
+    /// Unsubscribe from and subscribe to a quiet feed again.
+    pub resubscribe: bool,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        HealthPolicy {
+            quiet_after: DEFAULT_QUIET_AFTER,
+            resubscribe: false,
+        }
+    }
+}
+
+/// A problem with the messages of one feed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FeedHealth {
+    /// Messages between `last` and `received` were not received.
+    Gap {
+        recipient: String,
+        last: usize,
+        received: usize,
+    },
+    /// The last message was received again.
+    Duplicate { recipient: String, sequence: usize },
+    /// The sequence went backwards, e.g. because the server restarted the feed. Later messages
+    /// are checked against `received`.
+    Regression {
+        recipient: String,
+        last: usize,
+        received: usize,
+    },
+    /// Nothing was received for `silent_for`. Reported once until the feed sends again.
+    Quiet {
+        recipient: String,
+        silent_for: Duration,
+    },
+}
+
+impl FeedHealth {
+    pub fn recipient(&self) -> &str {
+        match self {
+            FeedHealth::Gap { recipient, .. }
+            | FeedHealth::Duplicate { recipient, .. }
+            | FeedHealth::Regression { recipient, .. }
+            | FeedHealth::Quiet { recipient, .. } => recipient,
+        }
+    }
+}
+
+/// What has been received from one feed.
+#[derive(Clone, Debug)]
+pub struct FeedStats {
+    /// The sequence subsequent messages are checked against; `None` until the first message.
+    pub last_sequence: Option<usize>,
+    pub messages: u64,
+    pub gaps: u64,
+    /// Messages missing across every gap.
+    pub missed: u64,
+    pub duplicates: u64,
+    pub regressions: u64,
+    /// How long the last message took from the server's `timestamp` to being received. Includes
+    /// any difference between the server's clock and ours.
+    pub latency: Duration,
+    pub max_latency: Duration,
+    pub last_received: Instant,
+    /// Whether the feed has been reported as quiet since its last message.
+    pub quiet: bool,
+}
+
+impl FeedStats {
+    fn new(now: Instant) -> Self {
+        FeedStats {
+            last_sequence: None,
+            messages: 0,
+            gaps: 0,
+            missed: 0,
+            duplicates: 0,
+            regressions: 0,
+            latency: Duration::ZERO,
+            max_latency: Duration::ZERO,
+            last_received: now,
+            quiet: false,
+        }
+    }
+}
+
+/// Checks the `sequence` and `timestamp` of every message per recipient, and notices feeds that
+/// go quiet.
+///
+/// Each recipient (e.g. `ticker.sfox.btcusd`) is expected to number its messages one after
+/// another. Sequences start over on a new connection, so [`FeedMonitor::reset`] the monitor
+/// after reconnecting. [`ManagedConnection::monitor`](super::managed::ManagedConnection::monitor)
+/// does this, and the quiet checks, itself.
+///
+/// ```
+/// use sfox::websocket::health::FeedMonitor;
+/// use sfox::websocket::message::event::WsEvent;
+///
+/// let mut monitor = FeedMonitor::default();
+/// let event = WsEvent::from_text(
+///     r#"{"recipient": "private.user.balances", "sequence": 1, "timestamp": 1, "payload": []}"#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(monitor.observe(&event), None);
+/// assert_eq!(monitor.stats("private.user.balances").unwrap().messages, 1);
+/// for quiet in monitor.check_quiet() {
+///     println!("{:?}", quiet);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FeedMonitor {
+    quiet_after: Duration,
+    feeds: HashMap<String, FeedStats>,
+}
+
+impl Default for FeedMonitor {
+    fn default() -> Self {
+        FeedMonitor::new(DEFAULT_QUIET_AFTER)
+    }
+}
+
+impl FeedMonitor {
+    pub fn new(quiet_after: Duration) -> Self {
+        FeedMonitor {
+            quiet_after,
+            feeds: HashMap::new(),
+        }
+    }
+
+    pub fn quiet_after(&self) -> Duration {
+        self.quiet_after
+    }
+
+    /// Records a received event. Events without a recipient, such as system messages, are
+    /// ignored.
+    pub fn observe(&mut self, event: &WsEvent) -> Option<FeedHealth> {
+        let (recipient, sequence, timestamp) = match event {
+            WsEvent::Ticker(r) => (&r.recipient, r.sequence, r.timestamp),
+            WsEvent::Trade(r) => (&r.recipient, r.sequence, r.timestamp),
+            WsEvent::Orderbook { response: r, .. } => (&r.recipient, r.sequence, r.timestamp),
+            WsEvent::Balances(r) => (&r.recipient, r.sequence, r.timestamp),
+            WsEvent::Orders(r) => (&r.recipient, r.sequence, r.timestamp),
+            WsEvent::PostTradeSettlement(r) => (&r.recipient, r.sequence, r.timestamp),
+            WsEvent::System { .. } | WsEvent::Unknown { .. } => return None,
+        };
+
+        self.record(recipient, sequence, timestamp)
+    }
+
+    /// Records a message with the given `sequence` and `timestamp`, in nanoseconds since the
+    /// Unix epoch, from `recipient`.
+    pub fn record(
+        &mut self,
+        recipient: &str,
+        sequence: usize,
+        timestamp: usize,
+    ) -> Option<FeedHealth> {
+        self.record_at(
+            recipient,
+            sequence,
+            timestamp,
+            Instant::now(),
+            SystemTime::now(),
+        )
+    }
+
+    fn record_at(
+        &mut self,
+        recipient: &str,
+        sequence: usize,
+        timestamp: usize,
+        now: Instant,
+        clock: SystemTime,
+    ) -> Option<FeedHealth> {
+        let stats = match self.feeds.get_mut(recipient) {
+            Some(stats) => stats,
+            None => self
+                .feeds
+                .entry(recipient.to_string())
+                .or_insert_with(|| FeedStats::new(now)),
+        };
+
+        let sent = UNIX_EPOCH + Duration::from_nanos(timestamp as u64);
+        stats.latency = clock.duration_since(sent).unwrap_or_default();
+        stats.max_latency = stats.max_latency.max(stats.latency);
+        stats.messages += 1;
+        stats.last_received = now;
+        stats.quiet = false;
+
+        let last = match stats.last_sequence {
+            Some(last) => last,
+            None => {
+                stats.last_sequence = Some(sequence);
+                return None;
+            }
+        };
+
+        let health = if sequence == last {
+            stats.duplicates += 1;
+            Some(FeedHealth::Duplicate {
+                recipient: recipient.to_string(),
+                sequence,
+            })
+        } else if sequence < last {
+            stats.regressions += 1;
+            Some(FeedHealth::Regression {
+                recipient: recipient.to_string(),
+                last,
+                received: sequence,
+            })
+        } else if sequence > last + 1 {
+            stats.gaps += 1;
+            stats.missed += (sequence - last - 1) as u64;
+            Some(FeedHealth::Gap {
+                recipient: recipient.to_string(),
+                last,
+                received: sequence,
+            })
+        } else {
+            None
+        };
+
+        stats.last_sequence = Some(sequence);
+        health
+    }
+
+    /// Feeds that have sent nothing for longer than the quiet threshold and have not been
+    /// reported yet.
+    pub fn check_quiet(&mut self) -> Vec<FeedHealth> {
+        self.check_quiet_at(Instant::now())
+    }
+
+    fn check_quiet_at(&mut self, now: Instant) -> Vec<FeedHealth> {
+        let mut quiet: Vec<FeedHealth> = self
+            .feeds
+            .iter_mut()
+            .filter(|(_, stats)| !stats.quiet)
+            .filter_map(|(recipient, stats)| {
+                let silent_for = now.saturating_duration_since(stats.last_received);
+                if silent_for <= self.quiet_after {
+                    return None;
+                }
+
+                stats.quiet = true;
+                Some(FeedHealth::Quiet {
+                    recipient: recipient.clone(),
+                    silent_for,
+                })
+            })
+            .collect();
+
+        quiet.sort_by(|a, b| a.recipient().cmp(b.recipient()));
+        quiet
+    }
+
+    /// When the next feed will become quiet if nothing more is received.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.feeds
+            .values()
+            .filter(|stats| !stats.quiet)
+            .map(|stats| stats.last_received + self.quiet_after)
+            .min()
+    }
+
+    pub fn stats(&self, recipient: &str) -> Option<&FeedStats> {
+        self.feeds.get(recipient)
+    }
+
+    pub fn recipients(&self) -> impl Iterator<Item = &str> {
+        self.feeds.keys().map(String::as_str)
+    }
+
+    /// Tracks a feed that has not sent anything yet, e.g. after subscribing to it, so it is
+    /// reported as quiet if it never does. Feeds already tracked are left alone.
+    pub fn watch(&mut self, recipient: &str) {
+        if !self.feeds.contains_key(recipient) {
+            self.feeds
+                .insert(recipient.to_string(), FeedStats::new(Instant::now()));
+        }
+    }
+
+    /// Starts a feed's quiet period over and accepts whatever sequence it sends next, e.g. after
+    /// subscribing to it again.
+    pub fn rearm(&mut self, recipient: &str) {
+        if let Some(stats) = self.feeds.get_mut(recipient) {
+            stats.last_sequence = None;
+            stats.last_received = Instant::now();
+            stats.quiet = false;
+        }
+    }
+
+    /// Stops tracking a feed, e.g. after unsubscribing from it.
+    pub fn forget(&mut self, recipient: &str) {
+        self.feeds.remove(recipient);
+    }
+
+    /// Rearms every feed, e.g. after reconnecting.
+    pub fn reset(&mut self) {
+        let recipients: Vec<String> = self.feeds.keys().cloned().collect();
+        for recipient in recipients {
+            self.rearm(&recipient);
+        }
+    }
+}
+
+/// The feed and name a recipient is subscribed with, e.g. `Feed::Ticker` and `btcusd` for
+/// `ticker.sfox.btcusd`. Private feeds have no name.
+pub(crate) fn subscription_of(recipient: &str) -> Option<(Feed, Option<&str>)> {
+    let feed = Client::identify_recipient(recipient)?;
+    let name = match feed.is_named() {
+        true => Some(recipient.splitn(3, '.').nth(2)?),
+        false => None,
+    };
+
+    Some((feed, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::fixtures;
+
+    fn health(monitor: &mut FeedMonitor, sequence: usize) -> Option<FeedHealth> {
+        monitor.record("ticker.sfox.btcusd", sequence, 0)
+    }
+
+    #[test]
+    fn test_record_sequences() {
+        let mut monitor = FeedMonitor::default();
+        let recipient = "ticker.sfox.btcusd".to_string();
+
+        assert_eq!(health(&mut monitor, 7), None);
+        assert_eq!(health(&mut monitor, 8), None);
+        assert_eq!(
+            health(&mut monitor, 8),
+            Some(FeedHealth::Duplicate {
+                recipient: recipient.clone(),
+                sequence: 8
+            })
+        );
+        assert_eq!(
+            health(&mut monitor, 11),
+            Some(FeedHealth::Gap {
+                recipient: recipient.clone(),
+                last: 8,
+                received: 11
+            })
+        );
+        assert_eq!(
+            health(&mut monitor, 2),
+            Some(FeedHealth::Regression {
+                recipient: recipient.clone(),
+                last: 11,
+                received: 2
+            })
+        );
+        assert_eq!(health(&mut monitor, 3), None);
+
+        let stats = monitor.stats(&recipient).unwrap();
+        assert_eq!(stats.messages, 6);
+        assert_eq!(stats.last_sequence, Some(3));
+        assert_eq!(
+            (
+                stats.gaps,
+                stats.missed,
+                stats.duplicates,
+                stats.regressions
+            ),
+            (1, 2, 1, 1)
+        );
+
+        // Other recipients are numbered independently.
+        assert_eq!(monitor.record("trades.sfox.btcusd", 1, 0), None);
+
+        monitor.reset();
+        assert_eq!(health(&mut monitor, 1), None);
+    }
+
+    #[test]
+    fn test_latency() {
+        let mut monitor = FeedMonitor::default();
+        let now = Instant::now();
+        let clock = UNIX_EPOCH + Duration::from_secs(100);
+
+        monitor.record_at("a", 1, 99_750_000_000, now, clock);
+        assert_eq!(
+            monitor.stats("a").unwrap().latency,
+            Duration::from_millis(250)
+        );
+
+        // A timestamp ahead of our clock counts as no latency.
+        monitor.record_at("a", 2, 101_000_000_000, now, clock);
+        let stats = monitor.stats("a").unwrap();
+        assert_eq!(stats.latency, Duration::ZERO);
+        assert_eq!(stats.max_latency, Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_check_quiet() {
+        let mut monitor = FeedMonitor::new(Duration::from_secs(5));
+        let start = Instant::now();
+        let clock = SystemTime::now();
+        monitor.record_at("a", 1, 0, start, clock);
+        monitor.record_at("b", 1, 0, start + Duration::from_secs(3), clock);
+
+        assert_eq!(
+            monitor.next_deadline(),
+            Some(start + Duration::from_secs(5))
+        );
+        assert!(monitor
+            .check_quiet_at(start + Duration::from_secs(5))
+            .is_empty());
+        assert_eq!(
+            monitor.check_quiet_at(start + Duration::from_secs(6)),
+            vec![FeedHealth::Quiet {
+                recipient: "a".into(),
+                silent_for: Duration::from_secs(6)
+            }]
+        );
+        // Reported once, until the feed sends again.
+        assert_eq!(
+            monitor.check_quiet_at(start + Duration::from_secs(7)).len(),
+            0
+        );
+        assert_eq!(
+            monitor.next_deadline(),
+            Some(start + Duration::from_secs(8))
+        );
+
+        monitor.record_at("a", 2, 0, start + Duration::from_secs(7), clock);
+        let quiet = monitor.check_quiet_at(start + Duration::from_secs(20));
+        assert_eq!(
+            quiet.iter().map(FeedHealth::recipient).collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+
+        monitor.forget("a");
+        assert_eq!(monitor.recipients().collect::<Vec<_>>(), vec!["b"]);
+    }
+
+    #[test]
+    fn test_watch() {
+        let mut monitor = FeedMonitor::new(Duration::ZERO);
+        monitor.watch("ticker.sfox.btcusd");
+        monitor.watch("ticker.sfox.ethusd");
+        health(&mut monitor, 1);
+        monitor.watch("ticker.sfox.btcusd");
+
+        assert_eq!(monitor.stats("ticker.sfox.btcusd").unwrap().messages, 1);
+        assert_eq!(monitor.stats("ticker.sfox.ethusd").unwrap().messages, 0);
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(monitor.check_quiet().len(), 2);
+    }
+
+    #[test]
+    fn test_observe() {
+        let mut monitor = FeedMonitor::default();
+
+        for payload in [fixtures::TICKER_PAYLOAD, fixtures::SUBSCRIBE_PAYLOAD] {
+            let event = WsEvent::from_text(payload).unwrap();
+            assert_eq!(monitor.observe(&event), None);
+        }
+
+        assert_eq!(
+            monitor.recipients().collect::<Vec<_>>(),
+            vec!["ticker.sfox.btcusd"]
+        );
+        assert_eq!(
+            monitor.stats("ticker.sfox.btcusd").unwrap().last_sequence,
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn test_subscription_of() {
+        assert_eq!(
+            subscription_of("orderbook.net.btcusd"),
+            Some((Feed::NetOrderbook, Some("btcusd")))
+        );
+        assert_eq!(
+            subscription_of("private.user.balances"),
+            Some((Feed::Balances, None))
+        );
+        assert_eq!(subscription_of("ticker"), None);
+        assert_eq!(subscription_of("something.else"), None);
+    }
+}
@@ -0,0 +1,516 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_util::Stream;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use super::message::event::WsEvent;
+use super::message::market::orderbook::{BookType, Order};
+use super::message::OrderbookResponse;
+
+/// Why an orderbook message was not applied. The book is left as it was.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum LocalBookError {
+    #[error("orderbook message {sequence} for {pair} is not after {last_sequence}")]
+    OutOfOrder {
+        pair: String,
+        sequence: usize,
+        last_sequence: usize,
+    },
+    #[error("orderbook for {pair} last updated at {lastupdated} is older than {last_updated}")]
+    Stale {
+        pair: String,
+        lastupdated: usize,
+        last_updated: usize,
+    },
+}
+
+/// A side of the book.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+/// The orders resting at one price, summed across sources.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+    pub price: f64,
+    pub quantity: f64,
+    pub sources: Vec<String>,
+}
+
+/// An `f64` price ordered with `total_cmp`, so it can key a `BTreeMap`.
+#[derive(Clone, Copy, Debug)]
+struct Price(f64);
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Bid and ask levels sorted by price.
+#[derive(Clone, Debug, Default)]
+pub struct Ladder {
+    bids: BTreeMap<Price, Level>,
+    asks: BTreeMap<Price, Level>,
+}
+
+impl Ladder {
+    fn from_orders(bids: &[Order], asks: &[Order]) -> Self {
+        Ladder {
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    /// Bids from the highest price down.
+    pub fn bids(&self) -> impl DoubleEndedIterator<Item = &Level> {
+        self.bids.values().rev()
+    }
+
+    /// Asks from the lowest price up.
+    pub fn asks(&self) -> impl DoubleEndedIterator<Item = &Level> {
+        self.asks.values()
+    }
+
+    pub fn best_bid(&self) -> Option<&Level> {
+        self.bids().next()
+    }
+
+    pub fn best_ask(&self) -> Option<&Level> {
+        self.asks().next()
+    }
+
+    /// The best ask less the best bid. Negative when the book is crossed.
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    /// The midpoint of the best bid and ask.
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.best_ask()?.price + self.best_bid()?.price) / 2.0)
+    }
+
+    /// The quantity resting at exactly `price`.
+    pub fn quantity_at(&self, side: BookSide, price: f64) -> f64 {
+        let levels = match side {
+            BookSide::Bid => &self.bids,
+            BookSide::Ask => &self.asks,
+        };
+        levels
+            .get(&Price(price))
+            .map_or(0.0, |level| level.quantity)
+    }
+
+    /// The quantity available at `price` or better: bids at or above it, asks at or below it.
+    pub fn depth(&self, side: BookSide, price: f64) -> f64 {
+        let levels = match side {
+            BookSide::Bid => self.bids.range(Price(price)..),
+            BookSide::Ask => self.asks.range(..=Price(price)),
+        };
+        levels.map(|(_, level)| level.quantity).sum()
+    }
+}
+
+fn levels(orders: &[Order]) -> BTreeMap<Price, Level> {
+    let mut levels = BTreeMap::new();
+    for order in orders {
+        let level = levels.entry(Price(order.price)).or_insert_with(|| Level {
+            price: order.price,
+            quantity: 0.0,
+            sources: vec![],
+        });
+        level.quantity += order.quantity;
+        level.sources.push(order.source.clone());
+    }
+    levels
+}
+
+/// The current book for one pair.
+#[derive(Clone, Debug)]
+pub struct PairBook {
+    pub pair: String,
+    pub sequence: usize,
+    /// When sFOX last updated the book, in milliseconds since the Unix epoch.
+    pub lastupdated: usize,
+    pub lastpublished: usize,
+    levels: Ladder,
+    market_making: Ladder,
+}
+
+impl PairBook {
+    /// The book's levels.
+    pub fn levels(&self) -> &Ladder {
+        &self.levels
+    }
+
+    /// The levels of the `market_making` book.
+    pub fn market_making(&self) -> &Ladder {
+        &self.market_making
+    }
+
+    pub fn best_bid(&self) -> Option<&Level> {
+        self.levels.best_bid()
+    }
+
+    pub fn best_ask(&self) -> Option<&Level> {
+        self.levels.best_ask()
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        self.levels.spread()
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        self.levels.mid()
+    }
+
+    pub fn depth(&self, side: BookSide, price: f64) -> f64 {
+        self.levels.depth(side, price)
+    }
+
+    /// How long ago sFOX last updated the book, or zero if `lastupdated` is in the future.
+    pub fn age(&self) -> Duration {
+        let updated = UNIX_EPOCH + Duration::from_millis(self.lastupdated as u64);
+        SystemTime::now()
+            .duration_since(updated)
+            .unwrap_or_default()
+    }
+
+    /// Whether the book was last updated more than `max_age` ago.
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.age() > max_age
+    }
+}
+
+/// Sent to [`BookUpdates`] streams each time a message is applied.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookUpdate {
+    pub pair: String,
+    pub sequence: usize,
+    pub lastupdated: usize,
+    pub best_bid: Option<Level>,
+    pub best_ask: Option<Level>,
+}
+
+/// A stream of [`BookUpdate`]s from a [`LocalOrderBook`]. It ends when the book is dropped.
+#[derive(Debug)]
+pub struct BookUpdates {
+    updates: mpsc::UnboundedReceiver<BookUpdate>,
+}
+
+impl Stream for BookUpdates {
+    type Item = BookUpdate;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.updates.poll_recv(cx)
+    }
+}
+
+/// Order books for every pair of one book type, maintained from successive `orderbook.net` or
+/// `orderbook.sfox` messages.
+///
+/// Each message is a full snapshot of a pair's book. Messages whose `sequence` is not after the
+/// last one applied, or whose `lastupdated` is older, are rejected. Sequences start over on a new
+/// connection, so [`LocalOrderBook::clear`] the books after reconnecting.
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use sfox::websocket::local_book::LocalOrderBook;
+/// use sfox::websocket::managed::{ConnectionEvent, ManagedConnection};
+/// use sfox::websocket::message::{market::orderbook::BookType, Feed};
+///
+/// tokio_test::block_on(async {
+///     let mut connection = ManagedConnection::connect("wss://ws.sfox.com/ws".into())
+///         .await
+///         .unwrap();
+///     connection
+///         .subscribe(Feed::RawOrderbook, vec!["btcusd".into()])
+///         .unwrap();
+///
+///     let mut books = LocalOrderBook::new(BookType::Unadjusted);
+///     while let Some(event) = connection.next().await {
+///         match event {
+///             ConnectionEvent::Connected => books.clear(),
+///             ConnectionEvent::Event(Ok(event)) => {
+///                 if let Some(Ok(book)) = books.apply_event(&event) {
+///                     println!("{}: {:?} / {:?}", book.pair, book.mid(), book.spread());
+///                 }
+///             }
+///             _ => {}
+///         }
+///     }
+/// });
+/// ```
+#[derive(Debug)]
+pub struct LocalOrderBook {
+    book_type: BookType,
+    books: HashMap<String, PairBook>,
+    subscribers: Vec<mpsc::UnboundedSender<BookUpdate>>,
+}
+
+impl LocalOrderBook {
+    pub fn new(book_type: BookType) -> Self {
+        LocalOrderBook {
+            book_type,
+            books: HashMap::new(),
+            subscribers: vec![],
+        }
+    }
+
+    pub fn book_type(&self) -> BookType {
+        self.book_type
+    }
+
+    /// The book for `pair`, once a message for it has been applied.
+    pub fn book(&self, pair: &str) -> Option<&PairBook> {
+        self.books.get(pair)
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = &str> {
+        self.books.keys().map(String::as_str)
+    }
+
+    /// Applies an orderbook event of this book's type. Other events give `None`.
+    pub fn apply_event(&mut self, event: &WsEvent) -> Option<Result<&PairBook, LocalBookError>> {
+        match event {
+            WsEvent::Orderbook {
+                book_type,
+                response,
+            } if *book_type == self.book_type => Some(self.apply(response)),
+            _ => None,
+        }
+    }
+
+    /// Replaces the book of the message's pair and notifies [`BookUpdates`] streams.
+    pub fn apply(&mut self, response: &OrderbookResponse) -> Result<&PairBook, LocalBookError> {
+        let orderbook = &response.payload;
+
+        if let Some(current) = self.books.get(&orderbook.pair) {
+            if response.sequence <= current.sequence {
+                return Err(LocalBookError::OutOfOrder {
+                    pair: orderbook.pair.clone(),
+                    sequence: response.sequence,
+                    last_sequence: current.sequence,
+                });
+            }
+            if orderbook.lastupdated < current.lastupdated {
+                return Err(LocalBookError::Stale {
+                    pair: orderbook.pair.clone(),
+                    lastupdated: orderbook.lastupdated,
+                    last_updated: current.lastupdated,
+                });
+            }
+        }
+
+        let book = PairBook {
+            pair: orderbook.pair.clone(),
+            sequence: response.sequence,
+            lastupdated: orderbook.lastupdated,
+            lastpublished: orderbook.lastpublished,
+            levels: Ladder::from_orders(&orderbook.bids, &orderbook.asks),
+            market_making: Ladder::from_orders(
+                &orderbook.market_making.bids,
+                &orderbook.market_making.asks,
+            ),
+        };
+        self.notify(&book);
+
+        match self.books.entry(book.pair.clone()) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(book);
+                Ok(entry.into_mut())
+            }
+            Entry::Vacant(entry) => Ok(entry.insert(book)),
+        }
+    }
+
+    /// Forgets the book for `pair`, so the next message for it is accepted whatever its sequence.
+    pub fn reset(&mut self, pair: &str) {
+        self.books.remove(pair);
+    }
+
+    /// Forgets every book, e.g. after reconnecting.
+    pub fn clear(&mut self) {
+        self.books.clear();
+    }
+
+    /// A stream of updates for every message applied from now on.
+    pub fn updates(&mut self) -> BookUpdates {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.push(tx);
+        BookUpdates { updates: rx }
+    }
+
+    fn notify(&mut self, book: &PairBook) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let update = BookUpdate {
+            pair: book.pair.clone(),
+            sequence: book.sequence,
+            lastupdated: book.lastupdated,
+            best_bid: book.best_bid().cloned(),
+            best_ask: book.best_ask().cloned(),
+        };
+        self.subscribers
+            .retain(|subscriber| subscriber.send(update.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+    use crate::util::fixtures;
+    use crate::websocket::message::market::orderbook::{MarketMaking, Orderbook};
+    use crate::websocket::message::WsResponse;
+
+    fn order(price: f64, quantity: f64, source: &str) -> Order {
+        Order {
+            price,
+            quantity,
+            source: source.into(),
+        }
+    }
+
+    fn response(sequence: usize, lastupdated: usize, bids: Vec<Order>) -> OrderbookResponse {
+        WsResponse {
+            recipient: "orderbook.sfox.ethusd".into(),
+            payload: Orderbook {
+                asks: vec![order(101.0, 1.0, "a"), order(103.0, 2.0, "b")],
+                bids,
+                lastpublished: lastupdated,
+                lastupdated,
+                market_making: MarketMaking {
+                    asks: vec![order(102.0, 4.0, "c")],
+                    bids: vec![],
+                },
+                pair: "ethusd".into(),
+            },
+            sequence,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut books = LocalOrderBook::new(BookType::Unadjusted);
+        let bids = vec![
+            order(99.0, 1.0, "a"),
+            order(100.0, 0.5, "a"),
+            order(100.0, 0.25, "b"),
+        ];
+
+        let book = books.apply(&response(1, 10, bids)).unwrap();
+
+        assert_eq!(book.best_bid().unwrap().price, 100.0);
+        assert_eq!(book.best_bid().unwrap().quantity, 0.75);
+        assert_eq!(book.best_bid().unwrap().sources, vec!["a", "b"]);
+        assert_eq!(book.best_ask().unwrap().price, 101.0);
+        assert_eq!(book.spread(), Some(1.0));
+        assert_eq!(book.mid(), Some(100.5));
+        assert_eq!(book.depth(BookSide::Bid, 99.0), 1.75);
+        assert_eq!(book.depth(BookSide::Ask, 102.0), 1.0);
+        assert_eq!(book.levels().quantity_at(BookSide::Ask, 103.0), 2.0);
+        assert_eq!(book.levels().quantity_at(BookSide::Ask, 102.0), 0.0);
+        assert_eq!(book.market_making().best_ask().unwrap().price, 102.0);
+        assert_eq!(book.market_making().spread(), None);
+        assert_eq!(books.pairs().collect::<Vec<_>>(), vec!["ethusd"]);
+    }
+
+    #[test]
+    fn test_apply_rejects_out_of_order_and_stale() {
+        let mut books = LocalOrderBook::new(BookType::Unadjusted);
+        books.apply(&response(5, 10, vec![])).unwrap();
+
+        assert_eq!(
+            books.apply(&response(5, 11, vec![])).unwrap_err(),
+            LocalBookError::OutOfOrder {
+                pair: "ethusd".into(),
+                sequence: 5,
+                last_sequence: 5,
+            }
+        );
+        assert_eq!(
+            books.apply(&response(6, 9, vec![])).unwrap_err(),
+            LocalBookError::Stale {
+                pair: "ethusd".into(),
+                lastupdated: 9,
+                last_updated: 10,
+            }
+        );
+        assert_eq!(books.book("ethusd").unwrap().sequence, 5);
+
+        books.reset("ethusd");
+        assert!(books.apply(&response(1, 12, vec![])).is_ok());
+    }
+
+    #[test]
+    fn test_apply_event() {
+        let mut books = LocalOrderBook::new(BookType::FeeAdjusted);
+        let event = WsEvent::from_text(fixtures::NET_ORDERBOOK_PAYLOAD).unwrap();
+
+        let book = books.apply_event(&event).unwrap().unwrap();
+        assert_eq!(book.pair, "btcusd");
+        assert_eq!(book.best_bid().unwrap().price, 43222.05828009);
+        assert_eq!(book.best_ask().unwrap().price, 43234.16026124);
+        // Two sources quote the same ask price.
+        assert_eq!(
+            book.levels().quantity_at(BookSide::Ask, 43253.0),
+            7.0 + 0.09259548
+        );
+        assert_eq!(book.market_making().best_ask().unwrap().price, 43221.0);
+        assert!(book.is_stale(Duration::from_secs(60)));
+
+        let mut raw = LocalOrderBook::new(BookType::Unadjusted);
+        assert!(raw.apply_event(&event).is_none());
+        let ticker = WsEvent::from_text(fixtures::TICKER_PAYLOAD).unwrap();
+        assert!(books.apply_event(&ticker).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_updates() {
+        let mut books = LocalOrderBook::new(BookType::Unadjusted);
+        let updates = books.updates();
+        drop(books.updates());
+
+        books
+            .apply(&response(1, 10, vec![order(100.0, 1.0, "a")]))
+            .unwrap();
+        books.apply(&response(1, 10, vec![])).unwrap_err();
+        books.apply(&response(2, 11, vec![])).unwrap();
+        assert_eq!(books.subscribers.len(), 1);
+        drop(books);
+
+        let updates: Vec<BookUpdate> = updates.collect().await;
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].best_bid.as_ref().unwrap().price, 100.0);
+        assert_eq!(updates[0].best_ask.as_ref().unwrap().price, 101.0);
+        assert_eq!(updates[1].sequence, 2);
+        assert_eq!(updates[1].best_bid, None);
+    }
+}
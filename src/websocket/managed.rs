@@ -0,0 +1,1103 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::stream::SplitStream;
+use futures_util::{SinkExt, Stream, StreamExt};
+use rand::Rng;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use super::health::{self, FeedHealth, FeedMonitor, HealthPolicy};
+use super::message::event::WsEvent;
+use super::message::Feed;
+use super::{Client, WebsocketClientError, WsSink};
+use crate::config::SfoxConfig;
+use crate::credentials::CredentialProvider;
+
+type WsSource = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+
+/// Events buffered for a [`ManagedConnection`] that is not being read. Once full, the socket is
+/// not read until events are consumed.
+const EVENT_BUFFER: usize = 1024;
+
+/// Controls how a [`ManagedConnection`] reconnects after its socket drops.
+///
+/// The delay before each attempt grows exponentially from `initial_delay` up to `max_delay`. The
+/// default policy retries forever, starting at half a second with full jitter.
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    /// Delay before the first reconnection attempt; doubled on every subsequent attempt.
+    pub initial_delay: Duration,
+    /// Upper bound for a single delay.
+    pub max_delay: Duration,
+    /// Randomize each delay between zero and the computed backoff.
+    pub jitter: bool,
+    /// Attempts after which the connection gives up and its stream ends. `None` never gives up.
+    ///
+    /// Attempts are counted until a socket receives its first message, so a server that accepts
+    /// connections and drops them straight away still exhausts them.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// How long to wait before the given (1-based) reconnection attempt.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+
+        if self.jitter && !backoff.is_zero() {
+            rand::thread_rng().gen_range(Duration::ZERO..=backoff)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Feeds of one type that a [`ManagedConnection`] is subscribed to. Private feeds such as
+/// [`Feed::Balances`] have no names.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subscription {
+    pub feed: Feed,
+    pub feeds: Vec<String>,
+}
+
+/// What a [`ManagedConnection`] yields: data from the socket, interleaved with changes in the
+/// state of the connection.
+// Events are by far the most frequent variant, so they are not boxed.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum ConnectionEvent {
+    /// The socket connected, either initially or after a reconnection attempt.
+    Connected,
+    /// The socket dropped or a reconnection attempt failed.
+    Disconnected(WebsocketClientError),
+    /// A reconnection attempt will be made after `delay`.
+    Reconnecting { attempt: u32, delay: Duration },
+    /// After a reconnection, the socket was authenticated again (if it had been) and these
+    /// subscriptions were sent again.
+    Resubscribed(Vec<Subscription>),
+    /// A message received from the server, decoded as by [`Client::events`].
+    Event(Result<WsEvent, WebsocketClientError>),
+    /// A feed missed, repeated or went quiet, once [`ManagedConnection::monitor`] is enabled.
+    /// Sequence problems are reported just before the event that revealed them.
+    Health(FeedHealth),
+}
+
+/// A WebSocket connection that survives dropped sockets.
+///
+/// The connection remembers its subscriptions and credentials. When the socket drops it
+/// reconnects according to its [`ReconnectPolicy`], authenticates again and replays every
+/// active subscription. Received messages and lifecycle events are read by polling the
+/// connection as a [`Stream`], which ends once the connection is closed or gives up.
+///
+/// ```no_run
+/// use futures_util::StreamExt;
+/// use sfox::websocket::managed::{ConnectionEvent, ManagedConnection};
+/// use sfox::websocket::message::Feed;
+///
+/// tokio_test::block_on(async {
+///     let mut connection = ManagedConnection::connect("wss://ws.sfox.com/ws".into())
+///         .await
+///         .unwrap();
+///     connection.subscribe(Feed::Ticker, vec!["btcusd".into()]).unwrap();
+///
+///     while let Some(event) = connection.next().await {
+///         match event {
+///             ConnectionEvent::Event(Ok(event)) => println!("{:?}", event),
+///             other => println!("connection: {:?}", other),
+///         }
+///     }
+/// });
+/// ```
+#[derive(Debug)]
+pub struct ManagedConnection {
+    commands: mpsc::UnboundedSender<Command>,
+    events: mpsc::Receiver<ConnectionEvent>,
+}
+
+impl ManagedConnection {
+    /// Connect to the given server with the default [`ReconnectPolicy`].
+    pub async fn connect(server_url: String) -> Result<Self, WebsocketClientError> {
+        Self::connect_with(server_url, ReconnectPolicy::default()).await
+    }
+
+    /// Connect to the WebSocket server of a loaded [`SfoxConfig`], authenticating with its
+    /// credentials if it has any.
+    pub async fn from_config(config: &SfoxConfig) -> Result<Self, WebsocketClientError> {
+        let connection = Self::connect(config.ws_server_url.clone()).await?;
+        if let Some(credentials) = config.credentials() {
+            connection.authenticate_with(credentials)?;
+        }
+
+        Ok(connection)
+    }
+
+    /// Connect to the given server. Only the first connection is made here and its failure is
+    /// returned; later ones are attempted in the background according to `policy`.
+    pub async fn connect_with(
+        server_url: String,
+        policy: ReconnectPolicy,
+    ) -> Result<Self, WebsocketClientError> {
+        let client = Client::new_with_server_url(server_url.clone()).await?;
+
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::channel(EVENT_BUFFER);
+        let _ = event_tx.try_send(ConnectionEvent::Connected);
+
+        let connection = Connection {
+            server_url,
+            policy,
+            subscriptions: vec![],
+            credentials: None,
+            monitor: None,
+            resubscribe_quiet: false,
+            attempts: 0,
+            commands: command_rx,
+            events: event_tx,
+        };
+        tokio::spawn(connection.run(client));
+
+        Ok(ManagedConnection {
+            commands: command_tx,
+            events: event_rx,
+        })
+    }
+
+    /// Subscribe to the provided feeds, now and after every reconnection.
+    pub fn subscribe(&self, feed: Feed, feeds: Vec<String>) -> Result<(), WebsocketClientError> {
+        self.send(Command::Subscribe(feed, feeds))
+    }
+
+    /// Unsubscribe from feeds, so they are no longer replayed after a reconnection. An empty list
+    /// unsubscribes from every feed of the type that the connection is subscribed to.
+    pub fn unsubscribe(&self, feed: Feed, feeds: Vec<String>) -> Result<(), WebsocketClientError> {
+        self.send(Command::Unsubscribe(feed, feeds))
+    }
+
+    /// Authenticate the socket with the key supplied by the given provider, now and after every
+    /// reconnection. The key is read again on each reconnection, so rotated keys take effect.
+    pub fn authenticate_with(
+        &self,
+        credentials: Arc<dyn CredentialProvider>,
+    ) -> Result<(), WebsocketClientError> {
+        if let Err(e) = credentials.api_key() {
+            return Err(WebsocketClientError::AuthenticationError(e.to_string()));
+        }
+
+        self.send(Command::Authenticate(credentials))
+    }
+
+    /// Check the sequence of every feed's messages and report feeds that go quiet as
+    /// [`ConnectionEvent::Health`]. With [`HealthPolicy::resubscribe`], a quiet feed is also
+    /// subscribed to again and reported as [`ConnectionEvent::Resubscribed`].
+    pub fn monitor(&self, policy: HealthPolicy) -> Result<(), WebsocketClientError> {
+        self.send(Command::Monitor(policy))
+    }
+
+    /// Close the socket without reconnecting. The stream ends once buffered events are read.
+    pub fn close(&self) {
+        let _ = self.commands.send(Command::Close);
+    }
+
+    fn send(&self, command: Command) -> Result<(), WebsocketClientError> {
+        self.commands
+            .send(command)
+            .map_err(|_| WebsocketClientError::TxError("The connection is closed".into()))
+    }
+}
+
+impl Stream for ManagedConnection {
+    type Item = ConnectionEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Command {
+    Subscribe(Feed, Vec<String>),
+    Unsubscribe(Feed, Vec<String>),
+    Authenticate(Arc<dyn CredentialProvider>),
+    Monitor(HealthPolicy),
+    Close,
+}
+
+/// The background task owning the socket of a [`ManagedConnection`].
+struct Connection {
+    server_url: String,
+    policy: ReconnectPolicy,
+    subscriptions: Vec<Subscription>,
+    credentials: Option<Arc<dyn CredentialProvider>>,
+    monitor: Option<FeedMonitor>,
+    resubscribe_quiet: bool,
+    /// Reconnection attempts since a socket last received a message.
+    attempts: u32,
+    commands: mpsc::UnboundedReceiver<Command>,
+    events: mpsc::Sender<ConnectionEvent>,
+}
+
+impl Connection {
+    async fn run(mut self, client: Client) {
+        let mut pending = Some(client);
+        loop {
+            let reconnected = pending.is_none();
+            let client = match pending.take() {
+                Some(client) => client,
+                None => match self.reconnect().await {
+                    Some(client) => client,
+                    None => return,
+                },
+            };
+
+            let (mut write, mut read) = client.stream.split();
+            let result = match reconnected {
+                true => self.restore(&mut write).await,
+                false => Ok(()),
+            };
+            let result = match result {
+                Ok(()) => self.pump(&mut write, &mut read).await,
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(()) => {
+                    let _ = write.close().await;
+                    return;
+                }
+                Err(e) => {
+                    if !self.emit(ConnectionEvent::Disconnected(e)).await {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Forwards messages and commands until the socket drops (`Err`) or the connection is
+    /// closed (`Ok`).
+    async fn pump(
+        &mut self,
+        write: &mut WsSink,
+        read: &mut WsSource,
+    ) -> Result<(), WebsocketClientError> {
+        loop {
+            let deadline = self.monitor.as_ref().and_then(FeedMonitor::next_deadline);
+            let wake = deadline.unwrap_or_else(std::time::Instant::now);
+
+            tokio::select! {
+                command = self.commands.recv() => match command {
+                    None | Some(Command::Close) => return Ok(()),
+                    Some(command) => self.apply(command, write).await?,
+                },
+                _ = tokio::time::sleep_until(wake.into()), if deadline.is_some() => {
+                    if !self.check_quiet(write).await? {
+                        return Ok(());
+                    }
+                }
+                message = read.next() => match message {
+                    Some(Ok(Message::Close(frame))) => {
+                        return Err(WebsocketClientError::Disconnected(format!(
+                            "closed by the server: {:?}",
+                            frame
+                        )))
+                    }
+                    Some(Ok(message)) => {
+                        if let Some(event) = WsEvent::from_message(message) {
+                            self.attempts = 0;
+                            let health = match (&mut self.monitor, &event) {
+                                (Some(monitor), Ok(event)) => monitor.observe(event),
+                                _ => None,
+                            };
+                            if let Some(health) = health {
+                                if !self.emit(ConnectionEvent::Health(health)).await {
+                                    return Ok(());
+                                }
+                            }
+                            if !self.emit(ConnectionEvent::Event(event)).await {
+                                return Ok(());
+                            }
+                        }
+                    }
+                    Some(Err(e)) => return Err(WebsocketClientError::Disconnected(e.to_string())),
+                    None => {
+                        return Err(WebsocketClientError::Disconnected(
+                            "the socket was closed".into(),
+                        ))
+                    }
+                },
+            }
+        }
+    }
+
+    /// Waits and reconnects until a connection is made, returning `None` once the policy gives up
+    /// or the connection is closed.
+    async fn reconnect(&mut self) -> Option<Client> {
+        loop {
+            self.attempts += 1;
+            let attempt = self.attempts;
+            if matches!(self.policy.max_attempts, Some(max) if attempt > max) {
+                return None;
+            }
+
+            let delay = self.policy.delay(attempt);
+            if !self
+                .emit(ConnectionEvent::Reconnecting { attempt, delay })
+                .await
+            {
+                return None;
+            }
+
+            // Keep track of (un)subscriptions made while disconnected.
+            let sleep = tokio::time::sleep(delay);
+            tokio::pin!(sleep);
+            loop {
+                tokio::select! {
+                    _ = &mut sleep => break,
+                    command = self.commands.recv() => match command {
+                        None | Some(Command::Close) => return None,
+                        Some(command) => self.record(command),
+                    },
+                }
+            }
+
+            let event = match Client::new_with_server_url(self.server_url.clone()).await {
+                Ok(client) => match self.emit(ConnectionEvent::Connected).await {
+                    true => return Some(client),
+                    false => return None,
+                },
+                Err(e) => ConnectionEvent::Disconnected(e),
+            };
+            if !self.emit(event).await {
+                return None;
+            }
+        }
+    }
+
+    /// Reports feeds that went quiet, subscribing to them again if configured. Returns `false`
+    /// once the [`ManagedConnection`] is dropped.
+    async fn check_quiet(&mut self, write: &mut WsSink) -> Result<bool, WebsocketClientError> {
+        let quiet = match &mut self.monitor {
+            Some(monitor) => monitor.check_quiet(),
+            None => return Ok(true),
+        };
+
+        for health in quiet {
+            let recipient = health.recipient().to_string();
+            if !self.emit(ConnectionEvent::Health(health)).await {
+                return Ok(false);
+            }
+            if !self.resubscribe_quiet {
+                continue;
+            }
+
+            let subscription = match health::subscription_of(&recipient) {
+                Some((feed, name)) if is_subscribed(&self.subscriptions, feed, name) => {
+                    Subscription {
+                        feed,
+                        feeds: name.map(String::from).into_iter().collect(),
+                    }
+                }
+                _ => continue,
+            };
+            Client::unsubscribe(write, subscription.feed, subscription.feeds.clone()).await?;
+            Client::subscribe(write, subscription.feed, subscription.feeds.clone()).await?;
+            if let Some(monitor) = &mut self.monitor {
+                monitor.rearm(&recipient);
+            }
+            if !self
+                .emit(ConnectionEvent::Resubscribed(vec![subscription]))
+                .await
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Authenticates a new socket and replays the active subscriptions.
+    async fn restore(&mut self, write: &mut WsSink) -> Result<(), WebsocketClientError> {
+        // Sequences start over on a new socket.
+        if let Some(monitor) = &mut self.monitor {
+            monitor.reset();
+        }
+        self.watch_subscriptions();
+        if let Some(credentials) = &self.credentials {
+            Client::authenticate_with(write, credentials.as_ref()).await?;
+        }
+        for subscription in &self.subscriptions {
+            Client::subscribe(write, subscription.feed, subscription.feeds.clone()).await?;
+        }
+
+        self.emit(ConnectionEvent::Resubscribed(self.subscriptions.clone()))
+            .await;
+        Ok(())
+    }
+
+    async fn apply(
+        &mut self,
+        command: Command,
+        write: &mut WsSink,
+    ) -> Result<(), WebsocketClientError> {
+        // The server only stops the feeds it is named, so an empty list sends every name
+        // subscribed to.
+        let command = match command {
+            Command::Unsubscribe(feed, feeds) if feeds.is_empty() && feed.is_named() => {
+                Command::Unsubscribe(feed, subscribed_names(&self.subscriptions, feed))
+            }
+            command => command,
+        };
+
+        // Recorded before sending, so a command that fails on a dropped socket is still replayed
+        // after reconnecting.
+        self.record(command.clone());
+
+        match command {
+            Command::Subscribe(feed, feeds) => Client::subscribe(write, feed, feeds).await,
+            Command::Unsubscribe(feed, feeds) if feeds.is_empty() && feed.is_named() => Ok(()),
+            Command::Unsubscribe(feed, feeds) => Client::unsubscribe(write, feed, feeds).await,
+            Command::Authenticate(credentials) => {
+                Client::authenticate_with(write, credentials.as_ref()).await
+            }
+            Command::Monitor(_) | Command::Close => Ok(()),
+        }
+    }
+
+    /// Updates the state replayed after a reconnection.
+    fn record(&mut self, command: Command) {
+        match command {
+            Command::Subscribe(feed, feeds) => {
+                add_subscription(&mut self.subscriptions, feed, feeds);
+                self.watch_subscriptions();
+            }
+            Command::Unsubscribe(feed, feeds) => {
+                remove_subscription(&mut self.subscriptions, feed, &feeds);
+                if let Some(monitor) = &mut self.monitor {
+                    forget_unsubscribed(monitor, &self.subscriptions);
+                }
+            }
+            Command::Authenticate(credentials) => self.credentials = Some(credentials),
+            Command::Monitor(policy) => {
+                self.monitor = Some(FeedMonitor::new(policy.quiet_after));
+                self.resubscribe_quiet = policy.resubscribe;
+                self.watch_subscriptions();
+            }
+            Command::Close => {}
+        }
+    }
+
+    /// Has the monitor track every subscribed feed, so feeds that never send anything are
+    /// reported as quiet too.
+    fn watch_subscriptions(&mut self) {
+        if let Some(monitor) = &mut self.monitor {
+            for subscription in &self.subscriptions {
+                for recipient in recipients(subscription) {
+                    monitor.watch(&recipient);
+                }
+            }
+        }
+    }
+
+    async fn emit(&self, event: ConnectionEvent) -> bool {
+        self.events.send(event).await.is_ok()
+    }
+}
+
+fn add_subscription(subscriptions: &mut Vec<Subscription>, feed: Feed, feeds: Vec<String>) {
+    match subscriptions.iter_mut().find(|s| s.feed == feed) {
+        Some(subscription) => {
+            for name in feeds {
+                if !subscription.feeds.contains(&name) {
+                    subscription.feeds.push(name);
+                }
+            }
+        }
+        None => subscriptions.push(Subscription { feed, feeds }),
+    }
+}
+
+fn remove_subscription(subscriptions: &mut Vec<Subscription>, feed: Feed, feeds: &[String]) {
+    subscriptions.retain_mut(|subscription| {
+        if subscription.feed != feed {
+            return true;
+        }
+        if feeds.is_empty() {
+            return false;
+        }
+
+        subscription.feeds.retain(|name| !feeds.contains(name));
+        !subscription.feeds.is_empty()
+    });
+}
+
+fn subscribed_names(subscriptions: &[Subscription], feed: Feed) -> Vec<String> {
+    subscriptions
+        .iter()
+        .filter(|subscription| subscription.feed == feed)
+        .flat_map(|subscription| subscription.feeds.iter().cloned())
+        .collect()
+}
+
+/// The recipients of a subscription's messages, e.g. `ticker.sfox.btcusd`. System messages have
+/// none.
+fn recipients(subscription: &Subscription) -> Vec<String> {
+    let prefix = subscription.feed.prefix();
+    match subscription.feed {
+        Feed::System => vec![],
+        feed if feed.is_named() => subscription
+            .feeds
+            .iter()
+            .map(|name| format!("{}.{}", prefix, name))
+            .collect(),
+        _ => vec![prefix.to_string()],
+    }
+}
+
+fn is_subscribed(subscriptions: &[Subscription], feed: Feed, name: Option<&str>) -> bool {
+    subscriptions.iter().any(|subscription| {
+        subscription.feed == feed
+            && match name {
+                Some(name) => subscription.feeds.iter().any(|f| f == name),
+                None => true,
+            }
+    })
+}
+
+/// Stops monitoring feeds that are no longer subscribed to, so they are not reported as quiet.
+fn forget_unsubscribed(monitor: &mut FeedMonitor, subscriptions: &[Subscription]) {
+    let unsubscribed: Vec<String> = monitor
+        .recipients()
+        .filter(|recipient| match health::subscription_of(recipient) {
+            Some((feed, name)) => !is_subscribed(subscriptions, feed, name),
+            None => false,
+        })
+        .map(String::from)
+        .collect();
+
+    for recipient in unsubscribed {
+        monitor.forget(&recipient);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    use super::*;
+    use crate::credentials::StaticCredentials;
+    use crate::util::fixtures;
+
+    fn policy(max_attempts: Option<u32>) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::ZERO,
+            jitter: false,
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    /// Accepts `accepts` connections, reporting each text message with the index of its
+    /// connection. The first `drops` connections are dropped after their first message; the
+    /// others echo every message back.
+    async fn flaky_server(
+        drops: usize,
+        accepts: usize,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<(usize, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            for index in 0..accepts {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut socket = accept_async(stream).await.unwrap();
+                let tx = tx.clone();
+                let task = async move {
+                    while let Some(Ok(message)) = socket.next().await {
+                        if let Message::Text(text) = &message {
+                            let _ = tx.send((index, text.clone()));
+                        }
+                        if index < drops {
+                            return;
+                        }
+                        let _ = socket.send(message).await;
+                    }
+                };
+                match index < drops {
+                    true => task.await,
+                    false => {
+                        tokio::spawn(task);
+                    }
+                }
+            }
+        });
+
+        (addr, rx)
+    }
+
+    async fn feed_type(received: &mut mpsc::UnboundedReceiver<(usize, String)>) -> (usize, String) {
+        let (index, text) = received.recv().await.unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        (index, value["type"].as_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_resubscribes_after_reconnect() {
+        let (addr, mut received) = flaky_server(1, 2).await;
+        let mut connection =
+            ManagedConnection::connect_with(format!("ws://{}", addr), policy(None))
+                .await
+                .unwrap();
+
+        connection
+            .subscribe(Feed::Ticker, vec!["btcusd".into()])
+            .unwrap();
+        connection
+            .authenticate_with(Arc::new(StaticCredentials::new("ws-key")))
+            .unwrap();
+
+        assert!(matches!(
+            connection.next().await,
+            Some(ConnectionEvent::Connected)
+        ));
+        assert!(matches!(
+            connection.next().await,
+            Some(ConnectionEvent::Disconnected(_))
+        ));
+        assert!(matches!(
+            connection.next().await,
+            Some(ConnectionEvent::Reconnecting { attempt: 1, .. })
+        ));
+        assert!(matches!(
+            connection.next().await,
+            Some(ConnectionEvent::Connected)
+        ));
+        match connection.next().await {
+            Some(ConnectionEvent::Resubscribed(subscriptions)) => assert_eq!(
+                subscriptions,
+                vec![Subscription {
+                    feed: Feed::Ticker,
+                    feeds: vec!["btcusd".into()],
+                }]
+            ),
+            other => panic!("expected a resubscription, got {:?}", other),
+        }
+        assert!(matches!(
+            connection.next().await,
+            Some(ConnectionEvent::Event(Ok(WsEvent::System { .. })))
+        ));
+
+        assert_eq!(feed_type(&mut received).await, (0, "subscribe".into()));
+        assert_eq!(feed_type(&mut received).await, (1, "authenticate".into()));
+        assert_eq!(feed_type(&mut received).await, (1, "subscribe".into()));
+
+        connection.close();
+    }
+
+    #[tokio::test]
+    async fn test_from_config_authenticates() {
+        let (addr, mut received) = flaky_server(0, 1).await;
+        let config = SfoxConfig {
+            profile: "test".into(),
+            api_key: Some("ws-key".into()),
+            api_key_file: None,
+            http_server_url: "http://127.0.0.1".into(),
+            candlestick_server_url: "http://127.0.0.1".into(),
+            ws_server_url: format!("ws://{}", addr),
+            timeout_secs: None,
+            connect_timeout_secs: None,
+            user_agent: None,
+        };
+
+        let connection = ManagedConnection::from_config(&config).await.unwrap();
+
+        assert_eq!(feed_type(&mut received).await, (0, "authenticate".into()));
+        connection.close();
+    }
+
+    #[tokio::test]
+    async fn test_replays_commands_sent_to_a_dropped_socket() {
+        let (addr, mut received) = flaky_server(0, 2).await;
+        let (_commands, command_rx) = mpsc::unbounded_channel();
+        let (event_tx, _event_rx) = mpsc::channel(EVENT_BUFFER);
+        let mut connection = Connection {
+            server_url: format!("ws://{}", addr),
+            policy: policy(None),
+            subscriptions: vec![],
+            credentials: None,
+            monitor: None,
+            resubscribe_quiet: false,
+            attempts: 0,
+            commands: command_rx,
+            events: event_tx,
+        };
+
+        // The socket drops before the pump notices.
+        let client = Client::new_with_server_url(connection.server_url.clone())
+            .await
+            .unwrap();
+        let (mut write, _read) = client.stream.split();
+        write.close().await.unwrap();
+        let result = connection
+            .apply(
+                Command::Subscribe(Feed::Ticker, vec!["btcusd".into()]),
+                &mut write,
+            )
+            .await;
+        assert!(result.is_err());
+
+        let client = Client::new_with_server_url(connection.server_url.clone())
+            .await
+            .unwrap();
+        let (mut write, _read) = client.stream.split();
+        connection.restore(&mut write).await.unwrap();
+
+        let (index, text) = received.recv().await.unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(
+            text,
+            r#"{"type":"subscribe","feeds":["ticker.sfox.btcusd"]}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (addr, _received) = flaky_server(1, 1).await;
+        let connection = ManagedConnection::connect_with(format!("ws://{}", addr), policy(Some(2)))
+            .await
+            .unwrap();
+        connection
+            .subscribe(Feed::Trade, vec!["btcusd".into()])
+            .unwrap();
+
+        let events: Vec<ConnectionEvent> = connection.collect().await;
+
+        let attempts: Vec<u32> = events
+            .iter()
+            .filter_map(|event| match event {
+                ConnectionEvent::Reconnecting { attempt, .. } => Some(*attempt),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(attempts, vec![1, 2]);
+        assert!(matches!(
+            events.last(),
+            Some(ConnectionEvent::Disconnected(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_from_every_feed() {
+        let (addr, mut received) = flaky_server(0, 1).await;
+        let connection = ManagedConnection::connect(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        connection
+            .subscribe(Feed::Ticker, vec!["btcusd".into(), "ethusd".into()])
+            .unwrap();
+        connection.unsubscribe(Feed::Ticker, vec![]).unwrap();
+        // Nothing is subscribed to any more, so nothing is sent.
+        connection.unsubscribe(Feed::Ticker, vec![]).unwrap();
+        connection
+            .subscribe(Feed::Trade, vec!["btcusd".into()])
+            .unwrap();
+
+        let mut frames = vec![];
+        for _ in 0..3 {
+            let (_, text) = received.recv().await.unwrap();
+            frames.push(serde_json::from_str::<serde_json::Value>(&text).unwrap());
+        }
+        assert_eq!(
+            frames[1],
+            serde_json::json!({
+                "type": "unsubscribe",
+                "feeds": ["ticker.sfox.btcusd", "ticker.sfox.ethusd"],
+            })
+        );
+        assert_eq!(frames[2]["type"], "subscribe");
+        assert_eq!(
+            frames[2]["feeds"],
+            serde_json::json!(["trades.sfox.btcusd"])
+        );
+
+        connection.close();
+    }
+
+    #[tokio::test]
+    async fn test_counts_attempts_until_a_message_is_received() {
+        // Every connection is accepted and dropped before anything is received.
+        let (addr, _received) = flaky_server(3, 3).await;
+        let connection = ManagedConnection::connect_with(format!("ws://{}", addr), policy(Some(2)))
+            .await
+            .unwrap();
+        connection
+            .subscribe(Feed::Trade, vec!["btcusd".into()])
+            .unwrap();
+
+        let events: Vec<ConnectionEvent> = connection.collect().await;
+
+        let attempts: Vec<u32> = events
+            .iter()
+            .filter_map(|event| match event {
+                ConnectionEvent::Reconnecting { attempt, .. } => Some(*attempt),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(attempts, vec![1, 2]);
+        assert!(matches!(
+            events.last(),
+            Some(ConnectionEvent::Disconnected(_))
+        ));
+    }
+
+    /// Answers every subscription with ticker messages numbered 1, 2 and 4, then goes quiet.
+    async fn gappy_ticker_server() -> (SocketAddr, mpsc::UnboundedReceiver<(usize, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = accept_async(stream).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = socket.next().await {
+                let subscribe = text.contains(r#""type":"subscribe""#);
+                let _ = tx.send((0, text));
+                if !subscribe {
+                    continue;
+                }
+                for sequence in [1, 2, 4] {
+                    let ticker = fixtures::TICKER_PAYLOAD
+                        .replace(r#""sequence":4"#, &format!(r#""sequence":{}"#, sequence));
+                    socket.send(Message::Text(ticker)).await.unwrap();
+                }
+            }
+        });
+
+        (addr, rx)
+    }
+
+    fn ticker_sequence(event: Option<ConnectionEvent>) -> usize {
+        match event {
+            Some(ConnectionEvent::Event(Ok(WsEvent::Ticker(ticker)))) => ticker.sequence,
+            other => panic!("expected a ticker, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_monitor() {
+        let (addr, mut received) = gappy_ticker_server().await;
+        let mut connection = ManagedConnection::connect(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        connection
+            .monitor(HealthPolicy {
+                quiet_after: Duration::from_millis(100),
+                resubscribe: true,
+            })
+            .unwrap();
+        connection
+            .subscribe(Feed::Ticker, vec!["btcusd".into()])
+            .unwrap();
+
+        assert!(matches!(
+            connection.next().await,
+            Some(ConnectionEvent::Connected)
+        ));
+        assert_eq!(ticker_sequence(connection.next().await), 1);
+        assert_eq!(ticker_sequence(connection.next().await), 2);
+        assert_eq!(
+            connection.next().await.map(|event| match event {
+                ConnectionEvent::Health(health) => health,
+                other => panic!("expected a gap, got {:?}", other),
+            }),
+            Some(FeedHealth::Gap {
+                recipient: "ticker.sfox.btcusd".into(),
+                last: 2,
+                received: 4,
+            })
+        );
+        assert_eq!(ticker_sequence(connection.next().await), 4);
+
+        match connection.next().await {
+            Some(ConnectionEvent::Health(FeedHealth::Quiet {
+                recipient,
+                silent_for,
+            })) => {
+                assert_eq!(recipient, "ticker.sfox.btcusd");
+                assert!(silent_for >= Duration::from_millis(100));
+            }
+            other => panic!("expected a quiet feed, got {:?}", other),
+        }
+        match connection.next().await {
+            Some(ConnectionEvent::Resubscribed(subscriptions)) => assert_eq!(
+                subscriptions,
+                vec![Subscription {
+                    feed: Feed::Ticker,
+                    feeds: vec!["btcusd".into()],
+                }]
+            ),
+            other => panic!("expected a resubscription, got {:?}", other),
+        }
+        // The resubscribed feed starts its sequence over without a regression.
+        assert_eq!(ticker_sequence(connection.next().await), 1);
+
+        assert_eq!(feed_type(&mut received).await, (0, "subscribe".into()));
+        assert_eq!(feed_type(&mut received).await, (0, "unsubscribe".into()));
+        assert_eq!(feed_type(&mut received).await, (0, "subscribe".into()));
+
+        connection.close();
+    }
+
+    #[tokio::test]
+    async fn test_monitor_reports_feeds_that_never_send() {
+        // Echoes the subscriptions back, but never sends ticker messages.
+        let (addr, mut received) = flaky_server(0, 1).await;
+        let mut connection = ManagedConnection::connect(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        connection
+            .subscribe(Feed::Ticker, vec!["btcusd".into()])
+            .unwrap();
+        connection
+            .monitor(HealthPolicy {
+                quiet_after: Duration::from_millis(100),
+                resubscribe: true,
+            })
+            .unwrap();
+
+        let mut events = vec![];
+        while events.len() < 2 {
+            match connection.next().await {
+                Some(ConnectionEvent::Connected) | Some(ConnectionEvent::Event(_)) => {}
+                Some(event) => events.push(event),
+                None => panic!("the connection ended"),
+            }
+        }
+        match &events[0] {
+            ConnectionEvent::Health(FeedHealth::Quiet { recipient, .. }) => {
+                assert_eq!(recipient, "ticker.sfox.btcusd")
+            }
+            other => panic!("expected a quiet feed, got {:?}", other),
+        }
+        match &events[1] {
+            ConnectionEvent::Resubscribed(subscriptions) => assert_eq!(
+                subscriptions,
+                &vec![Subscription {
+                    feed: Feed::Ticker,
+                    feeds: vec!["btcusd".into()],
+                }]
+            ),
+            other => panic!("expected a resubscription, got {:?}", other),
+        }
+
+        assert_eq!(feed_type(&mut received).await, (0, "subscribe".into()));
+        assert_eq!(feed_type(&mut received).await, (0, "unsubscribe".into()));
+        assert_eq!(feed_type(&mut received).await, (0, "subscribe".into()));
+
+        connection.close();
+    }
+
+    #[test]
+    fn test_recipients() {
+        let ticker = Subscription {
+            feed: Feed::Ticker,
+            feeds: vec!["btcusd".into(), "ethusd".into()],
+        };
+        let balances = Subscription {
+            feed: Feed::Balances,
+            feeds: vec![],
+        };
+
+        assert_eq!(
+            recipients(&ticker),
+            vec!["ticker.sfox.btcusd", "ticker.sfox.ethusd"]
+        );
+        assert_eq!(recipients(&balances), vec!["private.user.balances"]);
+    }
+
+    #[test]
+    fn test_forget_unsubscribed() {
+        let mut monitor = FeedMonitor::default();
+        for recipient in [
+            "ticker.sfox.btcusd",
+            "ticker.sfox.ethusd",
+            "private.user.balances",
+        ] {
+            monitor.record(recipient, 1, 0);
+        }
+        let subscriptions = vec![
+            Subscription {
+                feed: Feed::Ticker,
+                feeds: vec!["ethusd".into()],
+            },
+            Subscription {
+                feed: Feed::Balances,
+                feeds: vec![],
+            },
+        ];
+
+        forget_unsubscribed(&mut monitor, &subscriptions);
+
+        let mut recipients: Vec<&str> = monitor.recipients().collect();
+        recipients.sort();
+        assert_eq!(
+            recipients,
+            vec!["private.user.balances", "ticker.sfox.ethusd"]
+        );
+    }
+
+    #[test]
+    fn test_subscriptions() {
+        let mut subscriptions = vec![];
+        add_subscription(&mut subscriptions, Feed::Ticker, vec!["btcusd".into()]);
+        add_subscription(
+            &mut subscriptions,
+            Feed::Ticker,
+            vec!["btcusd".into(), "ethusd".into()],
+        );
+        add_subscription(&mut subscriptions, Feed::Balances, vec![]);
+        assert_eq!(subscriptions[0].feeds, vec!["btcusd", "ethusd"]);
+        assert_eq!(subscriptions.len(), 2);
+
+        remove_subscription(&mut subscriptions, Feed::Ticker, &["btcusd".into()]);
+        assert_eq!(subscriptions[0].feeds, vec!["ethusd"]);
+        remove_subscription(&mut subscriptions, Feed::Balances, &[]);
+        remove_subscription(&mut subscriptions, Feed::Ticker, &["ethusd".into()]);
+        assert!(subscriptions.is_empty());
+    }
+
+    #[test]
+    fn test_delay_backoff() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(350),
+            jitter: false,
+            max_attempts: None,
+        };
+
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(350));
+    }
+}
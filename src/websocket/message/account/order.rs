@@ -1,5 +1,8 @@
 use serde_derive::Deserialize;
 
+use crate::num::{self, Number};
+use crate::trading::{Algorithm, Pair, Side};
+
 pub static OPEN_ORDER_FEED: &str = "private.user.open-orders";
 
 #[derive(Debug, Deserialize)]
@@ -7,17 +10,23 @@ pub struct OrderPayload {
     pub id: usize,
     pub client_order_id: String,
     pub status: String,
-    pub filled: String,
-    pub filled_amount: String,
-    pub vwap: String,
-    pub price: String,
-    pub quantity: String,
-    pub pair: String,
-    pub action: String,
+    #[serde(deserialize_with = "num::from_str")]
+    pub filled: Number,
+    #[serde(deserialize_with = "num::from_str")]
+    pub filled_amount: Number,
+    #[serde(deserialize_with = "num::from_str")]
+    pub vwap: Number,
+    #[serde(deserialize_with = "num::from_str")]
+    pub price: Number,
+    #[serde(deserialize_with = "num::from_str")]
+    pub quantity: Number,
+    pub pair: Pair,
+    pub action: Side,
     #[serde(rename = "type")]
-    pub order_type: String,
+    pub order_type: Algorithm,
     pub algorithm_id: usize,
-    pub fees: String,
+    #[serde(deserialize_with = "num::from_str")]
+    pub fees: Number,
 }
 
 pub fn open_order_feed() -> String {
@@ -1,15 +1,22 @@
 use serde_derive::Deserialize;
 
+use crate::num::{self, Number};
+
 static POST_TRADE_SETTLEMENT_FEED: &str = "private.user.post-trade-settlement";
 
 #[derive(Debug, Deserialize)]
 pub struct PostTradeSettlementPayload {
-    pub enabled: String,
-    pub equity: String,
-    pub equity_for_withdrawals: String,
-    pub available_exposure: String,
-    pub exposure: String,
-    pub exposure_limit: String,
+    pub enabled: bool,
+    #[serde(deserialize_with = "num::from_str")]
+    pub equity: Number,
+    #[serde(deserialize_with = "num::from_str")]
+    pub equity_for_withdrawals: Number,
+    #[serde(deserialize_with = "num::from_str")]
+    pub available_exposure: Number,
+    #[serde(deserialize_with = "num::from_str")]
+    pub exposure: Number,
+    #[serde(deserialize_with = "num::from_str")]
+    pub exposure_limit: Number,
 }
 
 pub fn post_trade_settlement_feed() -> String {
@@ -0,0 +1,494 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use futures_util::{future, Stream, StreamExt};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::{Error as TungsteniteError, Message};
+
+use super::account::balance::BalancePayload;
+use super::account::order::OrderPayload;
+use super::account::post_trade_settlement::PostTradeSettlementPayload;
+use super::market::orderbook::{BookType, Orderbook};
+use super::market::ticker::Ticker;
+use super::market::trade::Trade;
+use super::{
+    BalancesResponse, Feed, OrderResponse, OrderbookResponse, PostTradeSettlementResponse,
+    TickerResponse, TradeResponse, WsResponse,
+};
+use crate::websocket::{Client, WebsocketClientError};
+
+/// A decoded message received from the websocket server.
+#[derive(Debug)]
+pub enum WsEvent {
+    Ticker(TickerResponse),
+    Trade(TradeResponse),
+    /// An order book update, either fee-adjusted (`orderbook.net`) or raw (`orderbook.sfox`).
+    Orderbook {
+        book_type: BookType,
+        response: OrderbookResponse,
+    },
+    Balances(BalancesResponse),
+    Orders(OrderResponse),
+    PostTradeSettlement(PostTradeSettlementResponse),
+    /// A message without a recipient, such as the response to a subscription or authentication.
+    System {
+        message_type: String,
+        payload: Value,
+        sequence: Option<usize>,
+        timestamp: Option<usize>,
+    },
+    /// A message for a recipient this crate does not know, or with neither a recipient nor a
+    /// type.
+    Unknown {
+        recipient: Option<String>,
+        payload: Value,
+    },
+}
+
+impl WsEvent {
+    /// Decode the text of a message. Fails only when the text is not JSON or a known feed's
+    /// payload does not match its type.
+    pub fn from_text(text: &str) -> Result<WsEvent, WebsocketClientError> {
+        #[cfg(feature = "simd")]
+        let frame = decode(&mut text.as_bytes().to_vec());
+
+        #[cfg(not(feature = "simd"))]
+        let frame = serde_json::from_str::<Frame>(text).map_err(parse_error);
+
+        frame.map(|frame| frame.0)
+    }
+
+    /// Decode a received message, reusing its buffer. Control frames (ping, pong and close)
+    /// carry no event and give `None`.
+    pub fn from_message(message: Message) -> Option<Result<WsEvent, WebsocketClientError>> {
+        let mut bytes = match message {
+            Message::Text(text) => text.into_bytes(),
+            Message::Binary(data) => data,
+            _ => return None,
+        };
+
+        Some(decode(&mut bytes).map(|frame| frame.0))
+    }
+}
+
+impl Client {
+    /// Turn the read half of a socket into a stream of decoded events.
+    ///
+    /// ```no_run
+    /// use futures_util::StreamExt;
+    /// use sfox::websocket::message::{event::WsEvent, Feed};
+    /// use sfox::websocket::Client;
+    ///
+    /// tokio_test::block_on(async {
+    ///     let client = Client::new().await.unwrap();
+    ///     let (mut write, read) = client.stream.split();
+    ///     Client::subscribe(&mut write, Feed::Ticker, vec!["btcusd".into()])
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     let mut events = Client::events(read);
+    ///     while let Some(event) = events.next().await {
+    ///         if let Ok(WsEvent::Ticker(ticker)) = event {
+    ///             println!("{}: {}", ticker.payload.pair, ticker.payload.last);
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn events<S>(read: S) -> impl Stream<Item = Result<WsEvent, WebsocketClientError>>
+    where
+        S: Stream<Item = Result<Message, TungsteniteError>>,
+    {
+        read.filter_map(|message| {
+            future::ready(match message {
+                Ok(message) => WsEvent::from_message(message),
+                Err(e) => Some(Err(WebsocketClientError::Disconnected(e.to_string()))),
+            })
+        })
+    }
+}
+
+/// Parses a frame in place with simd-json, which unescapes strings within the buffer.
+#[cfg(feature = "simd")]
+fn decode(bytes: &mut [u8]) -> Result<Frame, WebsocketClientError> {
+    simd_json::serde::from_slice(bytes).map_err(parse_error)
+}
+
+#[cfg(not(feature = "simd"))]
+fn decode(bytes: &mut [u8]) -> Result<Frame, WebsocketClientError> {
+    serde_json::from_slice(bytes).map_err(parse_error)
+}
+
+fn parse_error(e: impl fmt::Display) -> WebsocketClientError {
+    WebsocketClientError::ParseError(format!("could not decode message: {}", e))
+}
+
+/// A whole frame, decoded in a single pass: the payload is deserialized straight into the type
+/// of its feed when the recipient precedes it, as it does in messages sent by sFOX.
+struct Frame(WsEvent);
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Key {
+    Recipient,
+    Type,
+    Sequence,
+    Timestamp,
+    Payload,
+    #[serde(other)]
+    Other,
+}
+
+impl<'de> Deserialize<'de> for Frame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(FrameVisitor)
+    }
+}
+
+struct FrameVisitor;
+
+impl<'de> Visitor<'de> for FrameVisitor {
+    type Value = Frame;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a websocket message object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Frame, A::Error> {
+        let mut recipient: Option<Cow<'de, str>> = None;
+        let mut message_type: Option<Cow<'de, str>> = None;
+        let mut sequence: Option<usize> = None;
+        let mut timestamp: Option<usize> = None;
+        let mut payload: Option<Payload> = None;
+
+        while let Some(key) = map.next_key()? {
+            match key {
+                Key::Recipient => recipient = Some(map.next_value::<Str>()?.0),
+                Key::Type => message_type = Some(map.next_value::<Str>()?.0),
+                Key::Sequence => sequence = Some(map.next_value()?),
+                Key::Timestamp => timestamp = Some(map.next_value()?),
+                Key::Payload => {
+                    let feed = recipient.as_deref().and_then(Client::identify_recipient);
+                    payload = Some(map.next_value_seed(PayloadSeed(feed))?);
+                }
+                Key::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let feed = recipient.as_deref().and_then(Client::identify_recipient);
+        let event = match (feed, recipient, message_type) {
+            (Some(feed), Some(recipient), _) => {
+                // A payload that came before its recipient was kept as a value.
+                let payload = match payload {
+                    Some(Payload::Untyped(value)) => PayloadSeed(Some(feed))
+                        .deserialize(value)
+                        .map_err(de::Error::custom)?,
+                    Some(payload) => payload,
+                    None => return Err(de::Error::missing_field("payload")),
+                };
+                let sequence = sequence.ok_or_else(|| de::Error::missing_field("sequence"))?;
+                let timestamp = timestamp.ok_or_else(|| de::Error::missing_field("timestamp"))?;
+
+                payload.into_event(feed, recipient.into_owned(), sequence, timestamp)?
+            }
+            (_, None, Some(message_type)) => WsEvent::System {
+                message_type: message_type.into_owned(),
+                payload: payload.map(Payload::into_value).unwrap_or_default(),
+                sequence,
+                timestamp,
+            },
+            (_, recipient, _) => WsEvent::Unknown {
+                recipient: recipient.map(Cow::into_owned),
+                payload: payload.map(Payload::into_value).unwrap_or_default(),
+            },
+        };
+
+        Ok(Frame(event))
+    }
+}
+
+/// The payload of a frame, typed when its feed is known.
+enum Payload {
+    Ticker(Ticker),
+    Trade(Trade),
+    Orderbook(Orderbook),
+    Balances(Vec<BalancePayload>),
+    Orders(Vec<OrderPayload>),
+    PostTradeSettlement(PostTradeSettlementPayload),
+    Untyped(Value),
+}
+
+impl Payload {
+    fn into_event<E: de::Error>(
+        self,
+        feed: Feed,
+        recipient: String,
+        sequence: usize,
+        timestamp: usize,
+    ) -> Result<WsEvent, E> {
+        let response = Envelope {
+            recipient,
+            sequence,
+            timestamp,
+        };
+
+        let event = match self {
+            Payload::Ticker(p) => WsEvent::Ticker(response.wrap(p)),
+            Payload::Trade(p) => WsEvent::Trade(response.wrap(p)),
+            Payload::Orderbook(p) => WsEvent::Orderbook {
+                book_type: match feed {
+                    Feed::NetOrderbook => BookType::FeeAdjusted,
+                    _ => BookType::Unadjusted,
+                },
+                response: response.wrap(p),
+            },
+            Payload::Balances(p) => WsEvent::Balances(response.wrap(p)),
+            Payload::Orders(p) => WsEvent::Orders(response.wrap(p)),
+            Payload::PostTradeSettlement(p) => WsEvent::PostTradeSettlement(response.wrap(p)),
+            Payload::Untyped(_) => {
+                return Err(E::custom(format!("no payload type for feed {:?}", feed)))
+            }
+        };
+
+        Ok(event)
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Payload::Untyped(value) => value,
+            _ => Value::Null,
+        }
+    }
+}
+
+/// The fields every feed message has besides its payload.
+struct Envelope {
+    recipient: String,
+    sequence: usize,
+    timestamp: usize,
+}
+
+impl Envelope {
+    fn wrap<T>(self, payload: T) -> WsResponse<T> {
+        WsResponse {
+            recipient: self.recipient,
+            payload,
+            sequence: self.sequence,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// Deserializes a payload into the type of the given feed, or into a [`Value`] when the feed is
+/// not known yet.
+struct PayloadSeed(Option<Feed>);
+
+impl<'de> DeserializeSeed<'de> for PayloadSeed {
+    type Value = Payload;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Payload, D::Error> {
+        let payload = match self.0 {
+            Some(Feed::Ticker) => Payload::Ticker(Deserialize::deserialize(deserializer)?),
+            Some(Feed::Trade) => Payload::Trade(Deserialize::deserialize(deserializer)?),
+            Some(Feed::NetOrderbook) | Some(Feed::RawOrderbook) => {
+                Payload::Orderbook(Deserialize::deserialize(deserializer)?)
+            }
+            Some(Feed::Balances) => Payload::Balances(Deserialize::deserialize(deserializer)?),
+            Some(Feed::Orders) => Payload::Orders(Deserialize::deserialize(deserializer)?),
+            Some(Feed::PostTradeSettlement) => {
+                Payload::PostTradeSettlement(Deserialize::deserialize(deserializer)?)
+            }
+            Some(Feed::System) | None => Payload::Untyped(Value::deserialize(deserializer)?),
+        };
+
+        Ok(payload)
+    }
+}
+
+/// A string borrowed from the frame when it contains no escapes.
+struct Str<'a>(Cow<'a, str>);
+
+impl<'de> Deserialize<'de> for Str<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrVisitor;
+
+        impl<'de> Visitor<'de> for StrVisitor {
+            type Value = Str<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Str<'de>, E> {
+                Ok(Str(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Str<'de>, E> {
+                Ok(Str(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Str<'de>, E> {
+                Ok(Str(Cow::Owned(v)))
+            }
+        }
+
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+    use crate::num::number;
+    use crate::util::fixtures;
+
+    #[test]
+    fn test_from_text() {
+        match WsEvent::from_text(fixtures::TICKER_PAYLOAD).unwrap() {
+            WsEvent::Ticker(ticker) => {
+                assert_eq!(ticker.recipient, "ticker.sfox.btcusd");
+                assert_eq!(ticker.payload.last, 41420.58);
+            }
+            other => panic!("expected a ticker, got {:?}", other),
+        }
+        match WsEvent::from_text(fixtures::NET_ORDERBOOK_PAYLOAD).unwrap() {
+            WsEvent::Orderbook {
+                book_type,
+                response,
+            } => {
+                assert_eq!(book_type, BookType::FeeAdjusted);
+                assert_eq!(response.payload.pair, "btcusd");
+            }
+            other => panic!("expected an order book, got {:?}", other),
+        }
+        match WsEvent::from_text(fixtures::POST_TRADE_SETTLEMENT_PAYLOAD).unwrap() {
+            WsEvent::PostTradeSettlement(settlement) => {
+                assert!(settlement.payload.enabled);
+                assert_eq!(settlement.payload.exposure_limit, number("5000"));
+            }
+            other => panic!("expected a settlement, got {:?}", other),
+        }
+        assert!(matches!(
+            WsEvent::from_text(fixtures::TRADE_PAYLOAD),
+            Ok(WsEvent::Trade(_))
+        ));
+        assert!(matches!(
+            WsEvent::from_text(fixtures::BALANCES_PAYLOAD),
+            Ok(WsEvent::Balances(_))
+        ));
+        assert!(matches!(
+            WsEvent::from_text(fixtures::OPEN_ORDERS_PAYLOAD),
+            Ok(WsEvent::Orders(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_text_payload_before_recipient() {
+        let text = r#"{"sequence": 1, "payload": {"amount": 1, "exchange": "x", "last": 2,
+            "high": 3, "low": 1, "open": 2, "pair": "btcusd", "route": "Smart", "source": "s",
+            "timestamp": "t", "volume": 5, "vwap": 2}, "recipient": "ticker.sfox.btcusd",
+            "timestamp": 2}"#;
+
+        match WsEvent::from_text(text).unwrap() {
+            WsEvent::Ticker(ticker) => {
+                assert_eq!(ticker.recipient, "ticker.sfox.btcusd");
+                assert_eq!(ticker.payload.vwap, 2.0);
+            }
+            other => panic!("expected a ticker, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_text_system() {
+        match WsEvent::from_text(fixtures::SUBSCRIBE_PAYLOAD).unwrap() {
+            WsEvent::System {
+                message_type,
+                payload,
+                sequence,
+                ..
+            } => {
+                assert_eq!(message_type, "success");
+                assert_eq!(payload["action"], "subscribe");
+                assert_eq!(sequence, Some(1));
+            }
+            other => panic!("expected a system message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_text_unknown_and_invalid() {
+        let unknown = r#"{"recipient": "private.user.something-new", "payload": {"a": 1}}"#;
+        match WsEvent::from_text(unknown).unwrap() {
+            WsEvent::Unknown { recipient, payload } => {
+                assert_eq!(recipient.as_deref(), Some("private.user.something-new"));
+                assert_eq!(payload["a"], 1);
+            }
+            other => panic!("expected an unknown message, got {:?}", other),
+        }
+        assert!(matches!(
+            WsEvent::from_text("{}"),
+            Ok(WsEvent::Unknown {
+                recipient: None,
+                ..
+            })
+        ));
+
+        for invalid in [
+            r#"{"recipient": "ticker.sfox.btcusd", "payload": {"last": "x"}}"#,
+            r#"{"recipient": "ticker.sfox.btcusd", "sequence": 1, "timestamp": 1}"#,
+            r#"{"recipient": "orderbook.sfox.btcusd", "payload": {"bids": [[1, 2]]}}"#,
+            "[]",
+            "not json",
+        ] {
+            assert!(
+                matches!(
+                    WsEvent::from_text(invalid),
+                    Err(WebsocketClientError::ParseError(_))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_message() {
+        let text = Message::Text(fixtures::TRADE_PAYLOAD.into());
+        let binary = Message::Binary(fixtures::BALANCES_PAYLOAD.as_bytes().to_vec());
+
+        assert!(matches!(
+            WsEvent::from_message(text),
+            Some(Ok(WsEvent::Trade(_)))
+        ));
+        assert!(matches!(
+            WsEvent::from_message(binary),
+            Some(Ok(WsEvent::Balances(_)))
+        ));
+        assert!(WsEvent::from_message(Message::Pong(vec![])).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let read = stream::iter(vec![
+            Ok(Message::Ping(vec![])),
+            Ok(Message::Text(fixtures::TICKER_PAYLOAD.into())),
+            Ok(Message::Binary(fixtures::TRADE_PAYLOAD.as_bytes().to_vec())),
+            Err(TungsteniteError::ConnectionClosed),
+        ]);
+
+        let events: Vec<_> = Client::events(read).collect().await;
+
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[0], Ok(WsEvent::Ticker(_))));
+        assert!(matches!(events[1], Ok(WsEvent::Trade(_))));
+        assert!(matches!(
+            events[2],
+            Err(WebsocketClientError::Disconnected(_))
+        ));
+    }
+}
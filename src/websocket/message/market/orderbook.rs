@@ -1,3 +1,5 @@
+use std::fmt;
+
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -34,20 +36,21 @@ impl From<(f64, f64, String)> for Order {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum BookType {
     FeeAdjusted,
     Unadjusted,
 }
 
-impl ToString for BookType {
-    fn to_string(&self) -> String {
+impl fmt::Display for BookType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            BookType::FeeAdjusted => "net".to_string(),
-            BookType::Unadjusted => "sfox".to_string(),
+            BookType::FeeAdjusted => f.write_str("net"),
+            BookType::Unadjusted => f.write_str("sfox"),
         }
     }
 }
 
 pub fn order_book_feed(basequote: &str, book_type: BookType) -> String {
-    format!("orderbook.{}.{}", book_type.to_string(), basequote)
+    format!("orderbook.{}.{}", book_type, basequote)
 }
@@ -1,5 +1,7 @@
+use std::borrow::Cow;
+
 use serde::de::value::Error;
-use serde::de::{DeserializeOwned, Error as DeError};
+use serde::de::{DeserializeOwned, Error as DeError, IgnoredAny};
 use serde::ser::{SerializeStruct, Serializer};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -16,6 +18,9 @@ use super::{Client, WebsocketClientError};
 
 /// Types and subscription builders for balances, orders, and post-trade settlement.
 pub mod account;
+/// Typed events decoded from received messages, and a stream of them over the read half of the
+/// socket.
+pub mod event;
 /// Types and subscription builders for orderbook, ticker, and trade.
 pub mod market;
 
@@ -36,7 +41,7 @@ trait FromJson {
 }
 
 /// Websocket messages fall under one of these categories.
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum Feed {
     Balances,
     Orders,
@@ -48,6 +53,31 @@ pub enum Feed {
     Trade,
 }
 
+impl Feed {
+    /// The recipient of the feed's messages, or the prefix of their recipients for a named feed.
+    pub(crate) fn prefix(self) -> &'static str {
+        match self {
+            Feed::Balances => "private.user.balances",
+            Feed::NetOrderbook => "orderbook.net",
+            Feed::Orders => "private.user.open-orders",
+            Feed::PostTradeSettlement => "private.user.post-trade-settlement",
+            Feed::RawOrderbook => "orderbook.sfox",
+            Feed::System => "system",
+            Feed::Ticker => "ticker.sfox",
+            Feed::Trade => "trades.sfox",
+        }
+    }
+
+    /// Whether subscriptions to the feed name what they subscribe to, e.g. the pairs of
+    /// [`Feed::Ticker`]. Private feeds and [`Feed::System`] have no names.
+    pub(crate) fn is_named(self) -> bool {
+        matches!(
+            self,
+            Feed::NetOrderbook | Feed::RawOrderbook | Feed::Ticker | Feed::Trade
+        )
+    }
+}
+
 /// The outer shape of a message received from an active subscription.
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct WsResponse<T> {
@@ -72,7 +102,7 @@ where
         };
 
         let payload: T = match value.get("payload") {
-            Some(payload) => serde_json::from_value(payload.clone()).unwrap(),
+            Some(payload) => T::deserialize(payload).map_err(Error::custom)?,
             None => {
                 return Err(Error::custom(
                     "could not find 'payload' key in message".to_string(),
@@ -132,7 +162,7 @@ where
         };
 
         let payload: T = match value.get("payload") {
-            Some(payload) => serde_json::from_value(payload.clone()).unwrap(),
+            Some(payload) => T::deserialize(payload).map_err(Error::custom)?,
             None => {
                 return Err(Error::custom(
                     "could not find 'payload' key in message".to_string(),
@@ -199,22 +229,9 @@ impl Serialize for SubscribeMsg {
     where
         S: Serializer,
     {
-        let prefix_or_msg = match self.feed_type {
-            Feed::Balances => "private.user.balances",
-            Feed::NetOrderbook => "orderbook.net",
-            Feed::Orders => "private.user.open-orders",
-            Feed::PostTradeSettlement => "private.user.post-trade-settlement",
-            Feed::RawOrderbook => "orderbook.sfox",
-            Feed::System => "system",
-            Feed::Ticker => "ticker.sfox",
-            Feed::Trade => "trades.sfox",
-        };
+        let prefix_or_msg = self.feed_type.prefix();
 
-        let feeds: Vec<String> = if self.feed_type == Feed::NetOrderbook
-            || self.feed_type == Feed::RawOrderbook
-            || self.feed_type == Feed::Ticker
-            || self.feed_type == Feed::Trade
-        {
+        let feeds: Vec<String> = if self.feed_type.is_named() {
             self.feeds
                 .iter()
                 .map(|feed| format!("{}.{}", prefix_or_msg, feed))
@@ -230,6 +247,15 @@ impl Serialize for SubscribeMsg {
     }
 }
 
+/// The keys of a message that identify its feed. The payload is skipped without being parsed.
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(borrow)]
+    recipient: Option<Cow<'a, str>>,
+    #[serde(rename = "type")]
+    message_type: Option<IgnoredAny>,
+}
+
 impl Client {
     /// Given a websocket message, determine the type of feed it is. This can be
     /// used for deserialization in a message handler.
@@ -244,8 +270,8 @@ impl Client {
             }
         };
 
-        let msg_json = match serde_json::from_str::<Value>(message) {
-            Ok(json) => json,
+        let envelope = match serde_json::from_str::<Envelope>(message) {
+            Ok(envelope) => envelope,
             Err(e) => {
                 return Err(WebsocketClientError::ParseError(format!(
                     "could not parse json: {}",
@@ -254,9 +280,9 @@ impl Client {
             }
         };
 
-        let recipient = match msg_json.get("recipient").and_then(Value::as_str) {
+        let recipient = match envelope.recipient {
             Some(recipient) => recipient,
-            None => match msg_json.get("type").and_then(Value::as_str) {
+            None => match envelope.message_type {
                 Some(_msg_type) => return Ok(Feed::System),
                 None => {
                     return Err(WebsocketClientError::ParseError(
@@ -266,7 +292,7 @@ impl Client {
             },
         };
 
-        let msg_type = match Self::identify_recipient(recipient) {
+        let msg_type = match Self::identify_recipient(&recipient) {
             Some(msg_type) => msg_type,
             None => {
                 return Err(WebsocketClientError::ParseError(format!(
@@ -279,7 +305,7 @@ impl Client {
         Ok(msg_type)
     }
 
-    fn identify_recipient(recipient: &str) -> Option<Feed> {
+    pub(crate) fn identify_recipient(recipient: &str) -> Option<Feed> {
         if recipient.starts_with("orderbook.net") {
             Some(Feed::NetOrderbook)
         } else if recipient.starts_with("orderbook.sfox") {
@@ -324,8 +350,8 @@ mod tests {
         util::fixtures,
         websocket::{
             message::{
-                BalancesResponse, Feed, FromJson, OrderResponse, OrderbookResponse, TickerResponse,
-                TradeResponse, WsResponse, WsSystemResponse,
+                market::ticker::Ticker, BalancesResponse, Feed, FromJson, OrderResponse,
+                OrderbookResponse, TickerResponse, TradeResponse, WsResponse, WsSystemResponse,
             },
             Client,
         },
@@ -416,8 +442,12 @@ mod tests {
     async fn test_deserialize_open_orders() {
         let open_orders_payload = fixtures::OPEN_ORDERS_PAYLOAD;
 
-        let _open_orders_response: OrderResponse =
+        let open_orders_response: OrderResponse =
             serde_json::from_str(open_orders_payload).unwrap();
+
+        let order = &open_orders_response.payload[0];
+        assert_eq!(order.price, crate::num::number("37227.82"));
+        assert_eq!(order.fees, crate::num::number("0.02850176"));
     }
 
     #[tokio::test]
@@ -535,4 +565,18 @@ mod tests {
         let payload = ws_response.payload.as_object().unwrap();
         assert_eq!(payload.get("some_field").unwrap(), "some_value");
     }
+
+    #[test]
+    fn test_deserialize_ws_response_mismatched_payload() {
+        let ws_response_payload = json!({
+            "recipient": "ticker.sfox.btcusd",
+            "payload": { "last": "not a number" },
+            "sequence": 123,
+            "timestamp": 456
+        });
+
+        let ws_response: Result<WsResponse<Ticker>, _> = WsResponse::from_json(ws_response_payload);
+
+        assert!(ws_response.is_err());
+    }
 }
@@ -1,21 +1,28 @@
 use futures_util::{stream::SplitSink, Future, SinkExt, TryFutureExt};
 use serde_derive::Deserialize;
-use std::env;
 use thiserror::Error;
 use tokio::net::TcpStream;
 use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
 use self::message::{Feed, SubscribeAction, SubscribeMsg};
+use crate::config::{self, SfoxConfig};
 
 /// Handles authentication and response with the websocket server.
 pub mod auth;
+/// Sequence gap, latency and quiet feed detection for received messages.
+pub mod health;
+/// Order books maintained from successive orderbook messages, with best prices, depth and update
+/// notifications.
+pub mod local_book;
+/// A connection that reconnects, re-authenticates and resubscribes after the socket drops.
+pub mod managed;
 /// This module contains methods for subscribing/unsubscribing to feeds and determining
 /// received message type. It also contains types for deserializing received messages.
 pub mod message;
 
 type WsSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
 
-static DEFAULT_WS_SERVER_URL: &str = "wss://ws.sfox.com/ws";
+pub const DEFAULT_WS_SERVER_URL: &str = "wss://ws.sfox.com/ws";
 
 #[derive(Clone, Error, Debug, Deserialize)]
 pub enum WebsocketClientError {
@@ -23,6 +30,8 @@ pub enum WebsocketClientError {
     AuthenticationError(String),
     #[error("could not create http client: {0}")]
     InitializationError(String),
+    #[error("connection lost: {0}")]
+    Disconnected(String),
     #[error("could not lock the write stream {0}")]
     LockError(String),
     #[error("could not parse: {0}")]
@@ -45,12 +54,20 @@ pub struct Client {
 impl Client {
     /// Create a new server with the URL set in the environment.
     pub fn new() -> impl Future<Output = Result<Client, WebsocketClientError>> {
-        let server_url =
-            env::var("SFOX_WS_SERVER_URL").unwrap_or_else(|_| DEFAULT_WS_SERVER_URL.to_string());
+        let server_url = config::env_var(config::WS_SERVER_URL_ENV_VAR)
+            .unwrap_or_else(|| DEFAULT_WS_SERVER_URL.to_string());
 
         Client::new_with_server_url(server_url)
     }
 
+    /// Connect to the WebSocket server of a loaded [`SfoxConfig`]. Authenticate with
+    /// [`Client::authenticate_with`] and the config's credentials to access private feeds.
+    pub fn from_config(
+        config: &SfoxConfig,
+    ) -> impl Future<Output = Result<Client, WebsocketClientError>> {
+        Client::new_with_server_url(config.ws_server_url.clone())
+    }
+
     /// Create a new server with the given server URL; used for testing.
     pub fn new_with_server_url(
         server_url: String,